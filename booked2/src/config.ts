@@ -81,6 +81,19 @@ export interface ProgressUpdate {
   reading_status: ReadingStatus | null;
 }
 
+// One page of a list view; pass next_index as from_index for the next page
+export interface Page<T> {
+  items: T[];
+  total: number;
+  next_index: number | null;
+}
+
+// NEP-145 storage balance, in yoctoNEAR
+export interface StorageBalance {
+  total: string;
+  available: string;
+}
+
 export interface ReadingStats {
   total_books: number;
   currently_reading: number;
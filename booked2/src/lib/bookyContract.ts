@@ -1,7 +1,9 @@
 import {
   BookEntry,
+  Page,
   ProgressUpdate,
   ReadingStats,
+  StorageBalance,
   BookyContract,
 } from '@/config';
 import { FollowedAccountDetails } from '@/utils/types';
@@ -18,12 +20,22 @@ interface WalletSelectorHook {
     contractId: string;
     method: string;
     args?: Record<string, unknown>;
+    deposit?: string;
   }) => Promise<any>;
 }
 
+// get_followed_accounts_with_details as returned, before libraries are completed
+type FollowedAccountPage = Omit<FollowedAccountDetails, 'library'> & {
+  library: Page<BookEntry>;
+};
+
 const CONTRACT = BookyContract as string;
 const MAX_RETRIES = 3;
 const INITIAL_DELAY = 1000; // 1 second
+// Largest page the contract returns
+const PAGE_LIMIT = 100;
+// Deposited when registering for storage: 0.1 NEAR, enough for a few dozen books
+const STORAGE_DEPOSIT = '100000000000000000000000';
 
 // Retry helper with exponential backoff
 const retryWithBackoff = async <T>(
@@ -56,25 +68,53 @@ const retryWithBackoff = async <T>(
   throw new Error(`Failed ${operation} after ${MAX_RETRIES} retries`);
 };
 
+// List views return one page at a time; follow next_index until it is null
+const fetchAllPages = async <T>(
+  fetchPage: (fromIndex: number) => Promise<Page<T>>,
+  fromIndex = 0,
+): Promise<T[]> => {
+  const items: T[] = [];
+  let next: number | null = fromIndex;
+  while (next !== null) {
+    const page: Page<T> = await fetchPage(next);
+    items.push(...page.items);
+    next = page.next_index;
+  }
+  return items;
+};
+
 // View Functions (read-only, no gas)
 export const getLibrary = async (
   viewFunction: WalletSelectorHook['viewFunction'],
   accountId: string,
+  viewer: string | null,
+  method = 'get_library',
+  fromIndex = 0,
 ): Promise<BookEntry[]> => {
-  return await retryWithBackoff(
-    () =>
-      viewFunction({
-        contractId: CONTRACT,
-        method: 'get_library',
-        args: { account_id: accountId },
-      }),
-    'getLibrary',
+  return await fetchAllPages(
+    (from_index) =>
+      retryWithBackoff(
+        () =>
+          viewFunction({
+            contractId: CONTRACT,
+            method,
+            args: {
+              account_id: accountId,
+              from_index,
+              limit: PAGE_LIMIT,
+              viewer,
+            },
+          }),
+        method,
+      ),
+    fromIndex,
   );
 };
 
 export const getBook = async (
   viewFunction: WalletSelectorHook['viewFunction'],
   accountId: string,
+  viewer: string | null,
   isbn: string,
 ): Promise<BookEntry | null> => {
   return await retryWithBackoff(
@@ -82,7 +122,7 @@ export const getBook = async (
       viewFunction({
         contractId: CONTRACT,
         method: 'get_book',
-        args: { account_id: accountId, isbn },
+        args: { account_id: accountId, isbn, viewer },
       }),
     'getBook',
   );
@@ -91,19 +131,22 @@ export const getBook = async (
 export const getTotalBooks = async (
   viewFunction: WalletSelectorHook['viewFunction'],
 ): Promise<number> => {
-  return await retryWithBackoff(
+  // The count is a U64, which the contract returns as a string
+  const total = await retryWithBackoff(
     () =>
       viewFunction({
         contractId: CONTRACT,
         method: 'get_total_books',
-      }),
+      }) as Promise<string>,
     'getTotalBooks',
   );
+  return Number(total);
 };
 
 export const getChapterNote = async (
   viewFunction: WalletSelectorHook['viewFunction'],
   accountId: string,
+  viewer: string | null,
   isbn: string,
   chapter: number,
 ): Promise<string | null> => {
@@ -112,7 +155,7 @@ export const getChapterNote = async (
       viewFunction({
         contractId: CONTRACT,
         method: 'get_chapter_note',
-        args: { account_id: accountId, isbn, chapter },
+        args: { account_id: accountId, isbn, chapter, viewer },
       }),
     'getChapterNote',
   );
@@ -121,6 +164,7 @@ export const getChapterNote = async (
 export const getAllChapterNotes = async (
   viewFunction: WalletSelectorHook['viewFunction'],
   accountId: string,
+  viewer: string | null,
   isbn: string,
 ): Promise<Record<number, string>> => {
   return await retryWithBackoff(
@@ -128,7 +172,7 @@ export const getAllChapterNotes = async (
       viewFunction({
         contractId: CONTRACT,
         method: 'get_all_chapter_notes',
-        args: { account_id: accountId, isbn },
+        args: { account_id: accountId, isbn, viewer },
       }),
     'getAllChapterNotes',
   );
@@ -137,13 +181,14 @@ export const getAllChapterNotes = async (
 export const getReadingStats = async (
   viewFunction: WalletSelectorHook['viewFunction'],
   accountId: string,
+  viewer: string | null,
 ): Promise<ReadingStats> => {
   return await retryWithBackoff(
     () =>
       viewFunction({
         contractId: CONTRACT,
         method: 'get_reading_stats',
-        args: { account_id: accountId },
+        args: { account_id: accountId, viewer },
       }),
     'getReadingStats',
   );
@@ -152,15 +197,13 @@ export const getReadingStats = async (
 export const getCurrentlyReading = async (
   viewFunction: WalletSelectorHook['viewFunction'],
   accountId: string,
+  viewer: string | null,
 ): Promise<BookEntry[]> => {
-  return await retryWithBackoff(
-    () =>
-      viewFunction({
-        contractId: CONTRACT,
-        method: 'get_currently_reading',
-        args: { account_id: accountId },
-      }),
-    'getCurrentlyReading',
+  return await getLibrary(
+    viewFunction,
+    accountId,
+    viewer,
+    'get_currently_reading',
   );
 };
 
@@ -168,63 +211,119 @@ export const getFollowedAccounts = async (
   viewFunction: WalletSelectorHook['viewFunction'],
   accountId?: string,
 ): Promise<string[]> => {
-  return await retryWithBackoff(
-    () =>
-      viewFunction({
-        contractId: CONTRACT,
-        method: 'get_followed_accounts',
-        args: { account_id: accountId },
-      }),
-    'getFollowedAccounts',
+  return await fetchAllPages((from_index) =>
+    retryWithBackoff(
+      () =>
+        viewFunction({
+          contractId: CONTRACT,
+          method: 'get_followed_accounts',
+          args: { account_id: accountId, from_index, limit: PAGE_LIMIT },
+        }),
+      'getFollowedAccounts',
+    ),
   );
 };
 
 export const getUserLibrary = async (
   viewFunction: WalletSelectorHook['viewFunction'],
   accountId: string,
+  viewer: string | null,
 ): Promise<BookEntry[]> => {
-  return await retryWithBackoff(
-    () =>
-      viewFunction({
-        contractId: CONTRACT,
-        method: 'get_user_library',
-        args: { account_id: accountId },
-      }),
-    'getUserLibrary',
-  );
+  return await getLibrary(viewFunction, accountId, viewer, 'get_user_library');
 };
 
 export const getUserStats = async (
   viewFunction: WalletSelectorHook['viewFunction'],
   accountId: string,
+  viewer: string | null,
 ): Promise<ReadingStats> => {
   return await retryWithBackoff(
     () =>
       viewFunction({
         contractId: CONTRACT,
         method: 'get_user_stats',
-        args: { account_id: accountId },
+        args: { account_id: accountId, viewer },
       }),
     'getUserStats',
   );
 };
 
+// Each account's library comes back as its first page; the rest is fetched
+// with get_library from where that page ends
 export const getFollowedAccountsWithDetails = async (
   viewFunction: WalletSelectorHook['viewFunction'],
-  accountId?: string,
+  accountId: string,
+  viewer: string | null,
 ): Promise<FollowedAccountDetails[]> => {
+  const accounts = await fetchAllPages(
+    (from_index): Promise<Page<FollowedAccountPage>> =>
+      retryWithBackoff(
+        () =>
+          viewFunction({
+            contractId: CONTRACT,
+            method: 'get_followed_accounts_with_details',
+            args: {
+              account_id: accountId,
+              from_index,
+              limit: PAGE_LIMIT,
+              viewer,
+            },
+          }),
+        'getFollowedAccountsWithDetails',
+      ),
+  );
+  return await Promise.all(
+    accounts.map(async ({ library, ...details }) => {
+      const rest =
+        library.next_index === null
+          ? []
+          : await getLibrary(
+              viewFunction,
+              details.account_id,
+              viewer,
+              'get_library',
+              library.next_index,
+            );
+      return { ...details, library: [...library.items, ...rest] };
+    }),
+  );
+};
+
+export const getStorageBalance = async (
+  viewFunction: WalletSelectorHook['viewFunction'],
+  accountId: string,
+): Promise<StorageBalance | null> => {
   return await retryWithBackoff(
     () =>
       viewFunction({
         contractId: CONTRACT,
-        method: 'get_followed_accounts_with_details',
+        method: 'storage_balance_of',
         args: { account_id: accountId },
       }),
-    'getFollowedAccountsWithDetails',
+    'getStorageBalance',
   );
 };
 
 // Call Functions (write, require gas and wallet signature)
+
+// Writes are paid for from the account's storage deposit, so register
+// accounts that haven't deposited yet. Not retried, so it can't deposit twice.
+export const registerStorage = async (
+  viewFunction: WalletSelectorHook['viewFunction'],
+  callFunction: WalletSelectorHook['callFunction'],
+  accountId: string,
+): Promise<void> => {
+  if (await getStorageBalance(viewFunction, accountId)) {
+    return;
+  }
+  await callFunction({
+    contractId: CONTRACT,
+    method: 'storage_deposit',
+    args: {},
+    deposit: STORAGE_DEPOSIT,
+  });
+};
+
 export const addBook = async (
   callFunction: WalletSelectorHook['callFunction'],
   book: BookEntry,
@@ -381,7 +480,8 @@ export const unfollowAccount = async (
   );
 };
 
-// Custom hook for convenient contract interaction
+// Custom hook for convenient contract interaction. Views pass the signed-in
+// account as viewer, so owners and allowed followers see hidden fields.
 export const useBookyContract = () => {
   const { signedAccountId, viewFunction, callFunction } =
     useWalletSelector() as WalletSelectorHook;
@@ -390,14 +490,24 @@ export const useBookyContract = () => {
     accountId: signedAccountId,
     // View functions
     getLibrary: (accountId?: string) =>
-      getLibrary(viewFunction, accountId || signedAccountId || ''),
+      getLibrary(
+        viewFunction,
+        accountId || signedAccountId || '',
+        signedAccountId,
+      ),
     getBook: (isbn: string, accountId?: string) =>
-      getBook(viewFunction, accountId || signedAccountId || '', isbn),
+      getBook(
+        viewFunction,
+        accountId || signedAccountId || '',
+        signedAccountId,
+        isbn,
+      ),
     getTotalBooks: () => getTotalBooks(viewFunction),
     getChapterNote: (isbn: string, chapter: number, accountId?: string) =>
       getChapterNote(
         viewFunction,
         accountId || signedAccountId || '',
+        signedAccountId,
         isbn,
         chapter,
       ),
@@ -405,15 +515,32 @@ export const useBookyContract = () => {
       getAllChapterNotes(
         viewFunction,
         accountId || signedAccountId || '',
+        signedAccountId,
         isbn,
       ),
     getReadingStats: (accountId?: string) =>
-      getReadingStats(viewFunction, accountId || signedAccountId || ''),
+      getReadingStats(
+        viewFunction,
+        accountId || signedAccountId || '',
+        signedAccountId,
+      ),
     getCurrentlyReading: (accountId?: string) =>
-      getCurrentlyReading(viewFunction, accountId || signedAccountId || ''),
+      getCurrentlyReading(
+        viewFunction,
+        accountId || signedAccountId || '',
+        signedAccountId,
+      ),
+
+    getStorageBalance: (accountId?: string) =>
+      getStorageBalance(viewFunction, accountId || signedAccountId || ''),
 
     // Call functions (require wallet connection)
-    addBook: (book: BookEntry) => addBook(callFunction, book),
+    registerStorage: () =>
+      registerStorage(viewFunction, callFunction, signedAccountId || ''),
+    addBook: async (book: BookEntry) => {
+      await registerStorage(viewFunction, callFunction, signedAccountId || '');
+      return addBook(callFunction, book);
+    },
     updateBook: (isbn: string, updatedBook: BookEntry) =>
       updateBook(callFunction, isbn, updatedBook),
     deleteBook: (isbn: string) => deleteBook(callFunction, isbn),
@@ -431,15 +558,19 @@ export const useBookyContract = () => {
     getFollowedAccounts: (accountId?: string) =>
       getFollowedAccounts(viewFunction, accountId || signedAccountId || ''),
     getUserLibrary: (accountId: string) =>
-      getUserLibrary(viewFunction, accountId),
-    getUserStats: (accountId: string) => getUserStats(viewFunction, accountId),
+      getUserLibrary(viewFunction, accountId, signedAccountId),
+    getUserStats: (accountId: string) =>
+      getUserStats(viewFunction, accountId, signedAccountId),
     getFollowedAccountsWithDetails: (accountId?: string) =>
       getFollowedAccountsWithDetails(
         viewFunction,
         accountId || signedAccountId || '',
+        signedAccountId,
       ),
-    followAccount: (accountIdToFollow: string) =>
-      followAccount(callFunction, accountIdToFollow),
+    followAccount: async (accountIdToFollow: string) => {
+      await registerStorage(viewFunction, callFunction, signedAccountId || '');
+      return followAccount(callFunction, accountIdToFollow);
+    },
     unfollowAccount: (accountIdToUnfollow: string) =>
       unfollowAccount(callFunction, accountIdToUnfollow),
   };
@@ -8,6 +8,8 @@ set -e
 # Default values
 CONTRACT_ID="${CONTRACT_ID:-quixotic-hour.testnet}"
 ACCOUNT_ID="${ACCOUNT_ID:-quixotic-hour.testnet}"
+# NEAR deposited for storage the first time an account adds a book
+STORAGE_DEPOSIT="${STORAGE_DEPOSIT:-0.1}"
 
 # Function to display usage
 usage() {
@@ -38,6 +40,9 @@ EXAMPLES:
     # With environment variables
     CONTRACT_ID=library.near ACCOUNT_ID=alice.near $0 -i "978-0451524935" -t "1984" -u "George Orwell" -d "2024-01-15"
 
+Accounts not yet registered for storage are registered first with a deposit of
+\$STORAGE_DEPOSIT NEAR (default: 0.1).
+
 EOF
     exit 1
 }
@@ -150,6 +155,13 @@ if [[ ! $REPLY =~ ^[Yy]$ ]]; then
     exit 0
 fi
 
+# Books are paid for from the account's storage deposit, so register first if needed
+BALANCE=$(near view "$CONTRACT_ID" storage_balance_of "{\"account_id\": \"$ACCOUNT_ID\"}" | tail -n 1)
+if [[ "$BALANCE" == "null" ]]; then
+    echo "Registering $ACCOUNT_ID for storage with a $STORAGE_DEPOSIT NEAR deposit..."
+    near call "$CONTRACT_ID" storage_deposit '{}' --accountId "$ACCOUNT_ID" --deposit "$STORAGE_DEPOSIT"
+fi
+
 # Call the contract
 echo "Calling contract..."
 near call "$CONTRACT_ID" add_book "$JSON" --accountId "$ACCOUNT_ID"
@@ -92,7 +92,7 @@ fi
 
 # Fetch current book details
 echo "Fetching book details..."
-CURRENT_BOOK=$(near view "$CONTRACT_ID" get_book "{\"account_id\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\"}" 2>&1)
+CURRENT_BOOK=$(near view "$CONTRACT_ID" get_book "{\"account_id\":\"$ACCOUNT_ID\",\"viewer\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\"}" 2>&1)
 
 # Check if book exists
 if echo "$CURRENT_BOOK" | grep -q "null"; then
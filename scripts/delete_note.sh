@@ -96,7 +96,7 @@ if [[ -z "$ISBN" || -z "$CHAPTER" ]]; then
     # Fetch current book details
     echo ""
     echo "Fetching book details..."
-    CURRENT_BOOK=$(near view "$CONTRACT_ID" get_book "{\"account_id\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\"}" 2>&1)
+    CURRENT_BOOK=$(near view "$CONTRACT_ID" get_book "{\"account_id\":\"$ACCOUNT_ID\",\"viewer\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\"}" 2>&1)
 
     # Check if book exists
     if echo "$CURRENT_BOOK" | grep -q "null"; then
@@ -129,7 +129,7 @@ if [[ -z "$ISBN" || -z "$CHAPTER" ]]; then
     fi
 
     # Check for existing note
-    EXISTING_NOTE=$(near view "$CONTRACT_ID" get_chapter_note "{\"account_id\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\",\"chapter\":$CHAPTER}" 2>&1)
+    EXISTING_NOTE=$(near view "$CONTRACT_ID" get_chapter_note "{\"account_id\":\"$ACCOUNT_ID\",\"viewer\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\",\"chapter\":$CHAPTER}" 2>&1)
 
     if echo "$EXISTING_NOTE" | grep -q "null"; then
         echo ""
@@ -168,7 +168,7 @@ fi
 
 # Fetch book details if not already fetched
 if [[ -z "$CURRENT_BOOK" ]]; then
-    CURRENT_BOOK=$(near view "$CONTRACT_ID" get_book "{\"account_id\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\"}" 2>&1)
+    CURRENT_BOOK=$(near view "$CONTRACT_ID" get_book "{\"account_id\":\"$ACCOUNT_ID\",\"viewer\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\"}" 2>&1)
 fi
 
 if echo "$CURRENT_BOOK" | grep -q "null"; then
@@ -186,7 +186,7 @@ fi
 
 # Fetch the note if not already fetched
 if [[ -z "$EXISTING_NOTE" ]]; then
-    EXISTING_NOTE=$(near view "$CONTRACT_ID" get_chapter_note "{\"account_id\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\",\"chapter\":$CHAPTER}" 2>&1)
+    EXISTING_NOTE=$(near view "$CONTRACT_ID" get_chapter_note "{\"account_id\":\"$ACCOUNT_ID\",\"viewer\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\",\"chapter\":$CHAPTER}" 2>&1)
 fi
 
 # Check if note exists
@@ -113,7 +113,7 @@ if [[ -z "$ISBN" ]]; then
     # Fetch current book details
     echo ""
     echo "Fetching current book details..."
-    CURRENT_BOOK=$(near view "$CONTRACT_ID" get_book "{\"account_id\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\"}" 2>&1)
+    CURRENT_BOOK=$(near view "$CONTRACT_ID" get_book "{\"account_id\":\"$ACCOUNT_ID\",\"viewer\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\"}" 2>&1)
 
     # Check if book exists
     if echo "$CURRENT_BOOK" | grep -q "null"; then
@@ -196,7 +196,7 @@ fi
 # In non-interactive mode, we need to fetch the current book first
 if [[ -z "$TITLE" || -z "$AUTHOR" || -z "$DATE" || -z "$CONDITION" || -z "$COMMENTS" ]]; then
     echo "Fetching current book details..."
-    CURRENT_BOOK=$(near view "$CONTRACT_ID" get_book "{\"account_id\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\"}" 2>&1)
+    CURRENT_BOOK=$(near view "$CONTRACT_ID" get_book "{\"account_id\":\"$ACCOUNT_ID\",\"viewer\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\"}" 2>&1)
 
     if echo "$CURRENT_BOOK" | grep -q "null"; then
         echo "✗ Book not found in your library"
@@ -118,7 +118,7 @@ if [[ -z "$ISBN" ]]; then
     # Fetch current book details
     echo ""
     echo "Fetching current book details..."
-    CURRENT_BOOK=$(near view "$CONTRACT_ID" get_book "{\"account_id\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\"}" 2>&1)
+    CURRENT_BOOK=$(near view "$CONTRACT_ID" get_book "{\"account_id\":\"$ACCOUNT_ID\",\"viewer\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\"}" 2>&1)
 
     # Check if book exists
     if echo "$CURRENT_BOOK" | grep -q "null"; then
@@ -224,7 +224,7 @@ fi
 # Fetch current book in non-interactive mode if needed
 if [[ -z "$CHAPTER" || -z "$STATUS" ]] && [[ -z "$CURRENT_BOOK" ]]; then
     echo "Fetching current book details..."
-    CURRENT_BOOK=$(near view "$CONTRACT_ID" get_book "{\"account_id\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\"}" 2>&1)
+    CURRENT_BOOK=$(near view "$CONTRACT_ID" get_book "{\"account_id\":\"$ACCOUNT_ID\",\"viewer\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\"}" 2>&1)
 fi
 
 # Parse current values if not provided
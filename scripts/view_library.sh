@@ -8,6 +8,8 @@ set -e
 # Default values
 CONTRACT_ID="${CONTRACT_ID:-quixotic-hour.testnet}"
 ACCOUNT_ID="${ACCOUNT_ID:-quixotic-hour.testnet}"
+# Books fetched per get_library call (the contract caps pages at 100)
+PAGE_LIMIT="${PAGE_LIMIT:-100}"
 
 # Function to display usage
 usage() {
@@ -98,7 +100,7 @@ if [[ -n "$ISBN" ]]; then
     echo "Contract: $CONTRACT_ID"
     echo ""
 
-    RESULT=$(near view "$CONTRACT_ID" get_book "{\"account_id\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\"}" 2>/dev/null)
+    RESULT=$(near view "$CONTRACT_ID" get_book "{\"account_id\":\"$ACCOUNT_ID\",\"viewer\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\"}" 2>/dev/null)
 
     if [[ $OUTPUT_JSON == true ]]; then
         echo "$RESULT"
@@ -146,7 +148,7 @@ else
     echo "Contract: $CONTRACT_ID"
     echo ""
 
-    ERROR=$(near view "$CONTRACT_ID" get_library "{\"account_id\":\"$ACCOUNT_ID\"}" 2>&1)
+    ERROR=$(near view "$CONTRACT_ID" get_library "{\"account_id\":\"$ACCOUNT_ID\",\"viewer\":\"$ACCOUNT_ID\",\"limit\":1}" 2>&1)
     
     if [[ -n "$ERROR" ]] && [[ ! "$ERROR" =~ "Result" ]]; then
         echo "❌ Error querying library:"
@@ -157,13 +159,25 @@ else
         exit 1
     fi
     
-    RESULT=$(near view "$CONTRACT_ID" get_library "{\"account_id\":\"$ACCOUNT_ID\"}" 2>/dev/null)
+    # get_library returns one page, {"items": [...], "total": n, "next_index": i};
+    # follow next_index until it is null and merge the pages
+    if command -v jq &> /dev/null; then
+        RESULT='{"items":[],"total":0,"next_index":null}'
+        FROM_INDEX=0
+        while [[ -n "$FROM_INDEX" ]]; do
+            PAGE=$(near view "$CONTRACT_ID" get_library "{\"account_id\":\"$ACCOUNT_ID\",\"viewer\":\"$ACCOUNT_ID\",\"from_index\":$FROM_INDEX,\"limit\":$PAGE_LIMIT}" 2>/dev/null)
+            RESULT=$(jq -c --argjson page "$PAGE" '.items += $page.items | .total = $page.total' <<< "$RESULT")
+            FROM_INDEX=$(jq -r '.next_index // empty' <<< "$PAGE")
+        done
+    else
+        RESULT=$(near view "$CONTRACT_ID" get_library "{\"account_id\":\"$ACCOUNT_ID\",\"viewer\":\"$ACCOUNT_ID\",\"limit\":$PAGE_LIMIT}" 2>/dev/null)
+    fi
 
     if [[ $OUTPUT_JSON == true ]]; then
         echo "$RESULT"
     else
         # Check if library is empty
-        if echo "$RESULT" | grep -q "^\[\]"; then
+        if echo "$RESULT" | grep -q '"total": *0[,}]'; then
             echo "📚 Library is empty"
             echo ""
             echo "Add your first book:"
@@ -173,14 +187,14 @@ else
 
         # Format library output
         if command -v jq &> /dev/null; then
-            BOOK_COUNT=$(echo "$RESULT" | jq 'length' 2>/dev/null || echo "0")
+            BOOK_COUNT=$(echo "$RESULT" | jq '.total' 2>/dev/null || echo "0")
             echo "Total Books: $BOOK_COUNT"
             echo ""
             echo "Book List:"
             echo "---------"
             
             # Simple loop to display each book
-            echo "$RESULT" | jq -r '.[] | 
+            echo "$RESULT" | jq -r '.items[] | 
                 "Title: \(.title)",
                 "Author: \(.author)",
                 "ISBN: \(.isbn)",
@@ -190,8 +204,9 @@ else
                 ""
             ' 2>/dev/null || echo "Error parsing library"
         else
-            # Fallback: pretty-print JSON
+            # Fallback: pretty-print the first page of JSON
             echo "$RESULT" | python3 -m json.tool 2>/dev/null || echo "$RESULT"
+            echo "(First $PAGE_LIMIT books only; install jq to page through the rest)"
         fi
     fi
 fi
@@ -124,7 +124,7 @@ fi
 
 # Fetch book details to verify it exists
 echo "Fetching book details..."
-CURRENT_BOOK=$(near view "$CONTRACT_ID" get_book "{\"account_id\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\"}" 2>&1)
+CURRENT_BOOK=$(near view "$CONTRACT_ID" get_book "{\"account_id\":\"$ACCOUNT_ID\",\"viewer\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\"}" 2>&1)
 
 if echo "$CURRENT_BOOK" | grep -q "null"; then
     echo ""
@@ -152,7 +152,7 @@ if [[ -n "$CHAPTER" ]]; then
     echo "Chapter: $CHAPTER"
     echo ""
 
-    RESULT=$(near view "$CONTRACT_ID" get_chapter_note "{\"account_id\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\",\"chapter\":$CHAPTER}" 2>&1)
+    RESULT=$(near view "$CONTRACT_ID" get_chapter_note "{\"account_id\":\"$ACCOUNT_ID\",\"viewer\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\",\"chapter\":$CHAPTER}" 2>&1)
 
     if [[ $OUTPUT_JSON == true ]]; then
         echo "$RESULT"
@@ -183,7 +183,7 @@ else
     echo "Total Chapters: $TOTAL_CHAPTERS"
     echo ""
 
-    RESULT=$(near view "$CONTRACT_ID" get_all_chapter_notes "{\"account_id\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\"}" 2>&1)
+    RESULT=$(near view "$CONTRACT_ID" get_all_chapter_notes "{\"account_id\":\"$ACCOUNT_ID\",\"viewer\":\"$ACCOUNT_ID\",\"isbn\":\"$ISBN\"}" 2>&1)
 
     if [[ $OUTPUT_JSON == true ]]; then
         echo "$RESULT"
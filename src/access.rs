@@ -0,0 +1,183 @@
+// Contract owner, admin roles and the pause switch
+use crate::query::paginate;
+use crate::{BookyError, BookyEvent, Contract, ContractExt, Page, StorageKey};
+use near_sdk::store::IterableSet;
+use near_sdk::{env, near, AccountId};
+
+/// Admin roles the owner can grant. The owner implicitly holds every role.
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Role {
+    /// Curates shared data
+    Moderator,
+    /// Can pause and unpause user writes
+    Pauser,
+}
+
+/// Owner, role members and pause flag
+#[near(serializers = [borsh])]
+pub struct AccessControl {
+    owner_id: AccountId,
+    /// Account proposed as the next owner, until it accepts
+    pending_owner_id: Option<AccountId>,
+    moderators: IterableSet<AccountId>,
+    pausers: IterableSet<AccountId>,
+    paused: bool,
+}
+
+impl AccessControl {
+    pub fn new(owner_id: AccountId) -> Self {
+        Self {
+            owner_id,
+            pending_owner_id: None,
+            moderators: IterableSet::new(StorageKey::Moderators),
+            pausers: IterableSet::new(StorageKey::Pausers),
+            paused: false,
+        }
+    }
+
+    fn members(&self, role: Role) -> &IterableSet<AccountId> {
+        match role {
+            Role::Moderator => &self.moderators,
+            Role::Pauser => &self.pausers,
+        }
+    }
+
+    fn members_mut(&mut self, role: Role) -> &mut IterableSet<AccountId> {
+        match role {
+            Role::Moderator => &mut self.moderators,
+            Role::Pauser => &mut self.pausers,
+        }
+    }
+
+    pub fn has_role(&self, account_id: &AccountId, role: Role) -> bool {
+        *account_id == self.owner_id || self.members(role).contains(account_id)
+    }
+
+    /// Fail unless the caller is the owner
+    pub fn require_owner(&self) -> Result<AccountId, BookyError> {
+        let caller = env::predecessor_account_id();
+        if caller != self.owner_id {
+            return Err(BookyError::NotOwner(caller));
+        }
+        Ok(caller)
+    }
+
+    /// Fail unless the caller is the owner or holds `role`
+    pub fn require_role(&self, role: Role) -> Result<AccountId, BookyError> {
+        let caller = env::predecessor_account_id();
+        if !self.has_role(&caller, role) {
+            return Err(BookyError::MissingRole { account_id: caller, role });
+        }
+        Ok(caller)
+    }
+
+    /// Fail while the contract is paused
+    pub fn require_not_paused(&self) -> Result<(), BookyError> {
+        if self.paused {
+            return Err(BookyError::Paused);
+        }
+        Ok(())
+    }
+}
+
+#[near]
+impl Contract {
+    /// Initialize the contract with `owner_id` as its owner
+    #[init]
+    pub fn new(owner_id: AccountId) -> Self {
+        Self {
+            access: AccessControl::new(owner_id),
+            ..Default::default()
+        }
+    }
+
+    pub fn get_owner(&self) -> AccountId {
+        self.access.owner_id.clone()
+    }
+
+    pub fn get_pending_owner(&self) -> Option<AccountId> {
+        self.access.pending_owner_id.clone()
+    }
+
+    /// Start an ownership transfer; `new_owner_id` must call `accept_ownership` to complete it.
+    /// Proposing again replaces the pending owner.
+    #[handle_result]
+    pub fn propose_owner(&mut self, new_owner_id: AccountId) -> Result<(), BookyError> {
+        let owner_id = self.access.require_owner()?;
+        BookyEvent::OwnershipProposed { owner_id: &owner_id, pending_owner_id: &new_owner_id }.emit();
+        self.access.pending_owner_id = Some(new_owner_id);
+        Ok(())
+    }
+
+    /// Complete an ownership transfer proposed to the caller
+    #[handle_result]
+    pub fn accept_ownership(&mut self) -> Result<(), BookyError> {
+        let caller = env::predecessor_account_id();
+        if self.access.pending_owner_id.as_ref() != Some(&caller) {
+            return Err(BookyError::NotPendingOwner(caller));
+        }
+
+        self.access.pending_owner_id = None;
+        let previous_owner_id = std::mem::replace(&mut self.access.owner_id, caller);
+        BookyEvent::OwnershipTransferred {
+            previous_owner_id: &previous_owner_id,
+            owner_id: &self.access.owner_id,
+        }
+        .emit();
+        Ok(())
+    }
+
+    pub fn has_role(&self, account_id: AccountId, role: Role) -> bool {
+        self.access.has_role(&account_id, role)
+    }
+
+    /// Get a page of accounts explicitly granted `role` (the owner is not listed)
+    pub fn get_role_members(&self, role: Role, from_index: Option<u32>, limit: Option<u32>) -> Page<AccountId> {
+        let members = self.access.members(role);
+        paginate(members.iter().cloned(), members.len(), from_index, limit)
+    }
+
+    #[handle_result]
+    pub fn grant_role(&mut self, account_id: AccountId, role: Role) -> Result<(), BookyError> {
+        let owner_id = self.access.require_owner()?;
+        if self.access.members_mut(role).insert(account_id.clone()) {
+            BookyEvent::RoleGranted { account_id: &account_id, role, granted_by: &owner_id }.emit();
+        }
+        Ok(())
+    }
+
+    #[handle_result]
+    pub fn revoke_role(&mut self, account_id: AccountId, role: Role) -> Result<(), BookyError> {
+        let owner_id = self.access.require_owner()?;
+        if self.access.members_mut(role).remove(&account_id) {
+            BookyEvent::RoleRevoked { account_id: &account_id, role, revoked_by: &owner_id }.emit();
+        }
+        Ok(())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.access.paused
+    }
+
+    /// Block all user writes; views and admin methods keep working
+    #[handle_result]
+    pub fn pause(&mut self) -> Result<(), BookyError> {
+        let account_id = self.access.require_role(Role::Pauser)?;
+        if !self.access.paused {
+            self.access.paused = true;
+            BookyEvent::Paused { account_id: &account_id }.emit();
+        }
+        Ok(())
+    }
+
+    #[handle_result]
+    pub fn unpause(&mut self) -> Result<(), BookyError> {
+        let account_id = self.access.require_role(Role::Pauser)?;
+        if self.access.paused {
+            self.access.paused = false;
+            BookyEvent::Unpaused { account_id: &account_id }.emit();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,220 @@
+// Per-account activity log, and a feed merging the logs of followed accounts
+use crate::privacy::Audience;
+use crate::query::{DEFAULT_PAGE_LIMIT, MAX_PAGE_LIMIT};
+use crate::{Contract, ContractExt, PrivacySettings, ReadingStatus, StorageKey};
+use near_sdk::json_types::U64;
+use near_sdk::store::Vector;
+use near_sdk::{env, near, AccountId};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Most activities kept per account; recording more overwrites the oldest
+pub const MAX_ACTIVITY_PER_ACCOUNT: u32 = 100;
+
+/// Something an account did with a book
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ActivityKind {
+    BookAdded,
+    StartedReading,
+    /// Chapters newly marked as read by one progress update
+    ChaptersCompleted { chapters: Vec<u32> },
+    Finished,
+    NoteAdded { chapter: u32 },
+}
+
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Activity {
+    /// Block timestamp in nanoseconds
+    pub timestamp: U64,
+    pub isbn: String,
+    pub kind: ActivityKind,
+}
+
+/// One entry of a feed
+#[near(serializers = [json])]
+pub struct FeedItem {
+    pub account_id: AccountId,
+    pub activity: Activity,
+}
+
+/// An account's most recent activities, kept as a ring buffer
+#[near(serializers = [borsh])]
+pub struct ActivityLog {
+    entries: Vector<Activity>,
+    /// Number of activities ever recorded; the newest is at `(recorded - 1) % MAX_ACTIVITY_PER_ACCOUNT`
+    recorded: u64,
+}
+
+impl ActivityLog {
+    fn new(account_id: &AccountId) -> Self {
+        Self {
+            entries: Vector::new(StorageKey::Activity {
+                account_hash: env::sha256_array(account_id.as_bytes()),
+            }),
+            recorded: 0,
+        }
+    }
+
+    fn push(&mut self, activity: Activity) {
+        if self.entries.len() < MAX_ACTIVITY_PER_ACCOUNT {
+            self.entries.push(activity);
+        } else {
+            self.entries.set((self.recorded % MAX_ACTIVITY_PER_ACCOUNT as u64) as u32, activity);
+        }
+        self.recorded += 1;
+    }
+
+    fn newest_first(&self) -> impl Iterator<Item = &Activity> {
+        let len = self.entries.len() as u64;
+        (1..=len).filter_map(move |age| {
+            let index = (self.recorded - age) % MAX_ACTIVITY_PER_ACCOUNT as u64;
+            self.entries.get(index as u32)
+        })
+    }
+
+    /// Replace the log with `f` applied to each activity, oldest first, keeping those it returns
+    fn rewrite(&mut self, f: impl FnMut(Activity) -> Option<Activity>) {
+        let mut activities: Vec<Activity> = self.newest_first().cloned().collect();
+        activities.reverse();
+        self.entries.clear();
+        self.recorded = 0;
+        for activity in activities.into_iter().filter_map(f) {
+            self.push(activity);
+        }
+    }
+
+    pub fn flush(&mut self) {
+        self.entries.flush();
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Activity recorded for a reading status change, if any
+pub fn status_activity(old: ReadingStatus, new: ReadingStatus) -> Option<ActivityKind> {
+    match new {
+        _ if old == new => None,
+        ReadingStatus::Reading => Some(ActivityKind::StartedReading),
+        ReadingStatus::Completed => Some(ActivityKind::Finished),
+        _ => None,
+    }
+}
+
+#[near]
+impl Contract {
+    /// Get an account's most recent activities that `viewer` may see, newest first,
+    /// optionally only those at or after `since` (nanoseconds)
+    pub fn get_activity(
+        &self,
+        account_id: AccountId,
+        since: Option<U64>,
+        limit: Option<u32>,
+        viewer: Option<AccountId>,
+    ) -> Vec<Activity> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+        self.visible_activity(&account_id, viewer.as_ref(), since.map_or(0, |since| since.0))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Get the most recent activity of the accounts `account_id` follows, newest
+    /// first, as `account_id` may see it. Muted accounts are left out.
+    pub fn get_feed(&self, account_id: AccountId, since: Option<U64>, limit: Option<u32>) -> Vec<FeedItem> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+        let since = since.map_or(0, |since| since.0);
+        let followed: Vec<&AccountId> = self.unmuted_followed(&account_id).collect();
+
+        // Merge the newest-first logs by repeatedly taking the newest of their heads
+        let mut logs: Vec<_> = followed
+            .iter()
+            .map(|followed_id| self.visible_activity(followed_id, Some(&account_id), since))
+            .collect();
+        let mut heads: Vec<Option<&Activity>> = logs.iter_mut().map(Iterator::next).collect();
+        let mut newest: BinaryHeap<(u64, Reverse<usize>)> = heads
+            .iter()
+            .enumerate()
+            .filter_map(|(i, head)| head.map(|activity| (activity.timestamp.0, Reverse(i))))
+            .collect();
+
+        let mut feed = Vec::new();
+        while feed.len() < limit {
+            let Some((_, Reverse(i))) = newest.pop() else { break };
+            if let Some(activity) = heads[i].take() {
+                feed.push(FeedItem { account_id: followed[i].clone(), activity: activity.clone() });
+            }
+            heads[i] = logs[i].next();
+            if let Some(next) = heads[i] {
+                newest.push((next.timestamp.0, Reverse(i)));
+            }
+        }
+        feed
+    }
+}
+
+impl Contract {
+    /// Append to an account's activity log
+    pub(crate) fn record_activity(&mut self, account_id: &AccountId, isbn: &str, kind: ActivityKind) {
+        self.activity
+            .entry(account_id.clone())
+            .or_insert_with(|| ActivityLog::new(account_id))
+            .push(Activity { timestamp: U64(env::block_timestamp()), isbn: isbn.to_string(), kind });
+    }
+
+    /// Rewrite an account's activity log with `f` if any activity matches `affected`,
+    /// dropping the log once it is empty
+    pub(crate) fn rewrite_activity(
+        &mut self,
+        account_id: &AccountId,
+        affected: impl Fn(&Activity) -> bool,
+        f: impl FnMut(Activity) -> Option<Activity>,
+    ) {
+        let log = match self.activity.get_mut(account_id) {
+            Some(log) if log.newest_first().any(affected) => log,
+            _ => return,
+        };
+        log.rewrite(f);
+        if log.entries.is_empty() {
+            self.activity.remove(account_id);
+        }
+    }
+
+    /// An account's activities at or after `since` that `viewer` may see, newest first.
+    /// Activities on books no longer in the library are skipped.
+    fn visible_activity<'a>(
+        &'a self,
+        account_id: &'a AccountId,
+        viewer: Option<&AccountId>,
+        since: u64,
+    ) -> impl Iterator<Item = &'a Activity> + 'a {
+        let audience = self.audience(account_id, viewer);
+        let privacy = self.library_privacy(account_id);
+        self.activity
+            .get(account_id)
+            .filter(|_| privacy.allows(audience))
+            .into_iter()
+            .flat_map(ActivityLog::newest_first)
+            .take_while(move |activity| activity.timestamp.0 >= since)
+            .filter(move |activity| self.activity_visible(account_id, activity, audience, privacy))
+    }
+
+    fn activity_visible(
+        &self,
+        account_id: &AccountId,
+        activity: &Activity,
+        audience: Audience,
+        library_privacy: PrivacySettings,
+    ) -> bool {
+        let book = match self.find_book(account_id, &activity.isbn) {
+            Some(book) => book,
+            None => return false,
+        };
+        let settings = library_privacy.restrict(book.privacy);
+        let hidden_note = matches!(activity.kind, ActivityKind::NoteAdded { .. }) && settings.hide_notes;
+        settings.allows(audience) && (audience == Audience::Owner || !hidden_note)
+    }
+}
@@ -0,0 +1,255 @@
+// Shared catalog of canonical book metadata keyed by ISBN-13. Library entries keep
+// only the fields that differ from it, and views fill in the rest.
+use crate::{
+    canonical_isbn, normalize_isbn, BookEntry, BookyError, BookyEvent, CatalogEntryV1, Contract, ContractExt, Role,
+    Section,
+};
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::{env, near};
+use std::borrow::Cow;
+use std::io::{self, Read};
+
+/// Canonical metadata for one edition
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CatalogEntry {
+    pub isbn: String,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub authors: Vec<String>,
+    pub publisher: Option<String>,
+    pub year: Option<u16>,
+    /// Language tag, e.g. "en" or "pt-BR"
+    pub language: Option<String>,
+    pub page_count: Option<u32>,
+    /// Table of contents; its section count is the book's chapter count
+    pub chapters: Vec<Section>,
+    /// Block timestamp of the last change, set by the contract
+    #[serde(default)]
+    pub updated_at: U64,
+}
+
+impl CatalogEntry {
+    /// The authors as one `BookEntry::author` string
+    pub fn author(&self) -> String {
+        self.authors.join(", ")
+    }
+
+    pub fn total_chapters(&self) -> Option<u32> {
+        (!self.chapters.is_empty()).then_some(self.chapters.len() as u32)
+    }
+
+    /// Fill in the fields a library entry leaves to the catalog, returning which ones
+    /// were filled. An entry with its own chapter count or table of contents keeps
+    /// it, and gets neither from the catalog.
+    fn fill(&self, book: &mut BookEntry) -> Inherited {
+        let inherited = Inherited::of(book);
+        if inherited.title {
+            book.title = self.title.clone();
+        }
+        if inherited.author {
+            book.author = self.author();
+        }
+        if inherited.chapters {
+            book.sections = self.chapters.clone();
+            book.total_chapters = self.total_chapters();
+        }
+        if inherited.total_pages {
+            book.total_pages = self.page_count;
+        }
+        book.catalog = Some(Box::new(self.clone()));
+        inherited
+    }
+
+    /// Clear the fields of an edited entry that `existing` left to the catalog and that
+    /// still hold what a view filled in, so writing back a book read from a view
+    /// doesn't turn them into overrides
+    fn unfill(&self, book: &mut BookEntry, existing: &BookEntry) {
+        let inherited = Inherited::of(existing);
+        if inherited.title && book.title == self.title {
+            book.title.clear();
+        }
+        if inherited.author && book.author == self.author() {
+            book.author.clear();
+        }
+        if inherited.chapters && book.sections == self.chapters && book.total_chapters == self.total_chapters() {
+            book.sections.clear();
+            book.total_chapters = None;
+        }
+        if inherited.total_pages && book.total_pages == self.page_count {
+            book.total_pages = None;
+        }
+    }
+}
+
+/// Which fields of a library entry come from the catalog
+#[derive(Clone, Copy)]
+struct Inherited {
+    title: bool,
+    author: bool,
+    /// Both the table of contents and the chapter count
+    chapters: bool,
+    total_pages: bool,
+}
+
+impl Inherited {
+    fn of(book: &BookEntry) -> Self {
+        Self {
+            title: book.title.is_empty(),
+            author: book.author.is_empty(),
+            chapters: book.sections.is_empty() && book.total_chapters.is_none(),
+            total_pages: book.total_pages.is_none(),
+        }
+    }
+
+    /// Clear the fields `CatalogEntry::fill` filled in, leaving them to the catalog again
+    fn strip(self, book: &mut BookEntry) {
+        if self.title {
+            book.title.clear();
+        }
+        if self.author {
+            book.author.clear();
+        }
+        if self.chapters {
+            book.sections.clear();
+            book.total_chapters = None;
+        }
+        if self.total_pages {
+            book.total_pages = None;
+        }
+        book.catalog = None;
+    }
+}
+
+/// First byte of an untagged `CatalogEntryV1`: the length prefix of its ISBN-13,
+/// which no variant tag reaches
+const UNTAGGED_ISBN_LEN: u8 = 13;
+
+/// A catalog entry as stored, tagged with the `CatalogEntry` layout it was written in.
+/// Entries from before the tag are read as `V1` and converted on read.
+#[derive(BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub enum VersionedCatalogEntry {
+    V1(CatalogEntryV1),
+    V2(CatalogEntry),
+}
+
+impl BorshDeserialize for VersionedCatalogEntry {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        match u8::deserialize_reader(reader)? {
+            0 => Ok(Self::V1(CatalogEntryV1::deserialize_reader(reader)?)),
+            1 => Ok(Self::V2(CatalogEntry::deserialize_reader(reader)?)),
+            UNTAGGED_ISBN_LEN => {
+                let mut untagged = [UNTAGGED_ISBN_LEN].chain(reader);
+                Ok(Self::V1(CatalogEntryV1::deserialize_reader(&mut untagged)?))
+            }
+            tag => Err(io::Error::new(io::ErrorKind::InvalidData, format!("Unknown catalog entry tag {}", tag))),
+        }
+    }
+}
+
+impl VersionedCatalogEntry {
+    /// The entry in the current layout
+    pub fn current(&self) -> Cow<'_, CatalogEntry> {
+        match self {
+            Self::V1(entry) => Cow::Owned(entry.clone().into()),
+            Self::V2(entry) => Cow::Borrowed(entry),
+        }
+    }
+}
+
+/// A library entry with the catalog's metadata filled in, if the catalog has its ISBN
+pub(crate) fn resolve<'a>(catalog: Option<&CatalogEntry>, book: Cow<'a, BookEntry>) -> Cow<'a, BookEntry> {
+    match catalog {
+        Some(catalog) => {
+            let mut book = book.into_owned();
+            catalog.fill(&mut book);
+            Cow::Owned(book)
+        }
+        None => book,
+    }
+}
+
+#[near]
+impl Contract {
+    /// Add an ISBN to the catalog. Any registered account can add an ISBN the catalog
+    /// doesn't have yet and pays for its storage; later changes go through moderators.
+    /// The entry outlives the account, so its bytes stay paid for if the account unregisters.
+    #[handle_result]
+    pub fn add_catalog_entry(&mut self, entry: CatalogEntry) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+
+        let entry = CatalogEntry { isbn: canonical_isbn(&entry.isbn)?, updated_at: U64(env::block_timestamp()), ..entry };
+        self.field_limits.check_catalog_entry(&entry)?;
+        if self.catalog.contains_key(&entry.isbn) {
+            return Err(BookyError::CatalogEntryExists(entry.isbn));
+        }
+        BookyEvent::CatalogEntryAdded { account_id: &account_id, isbn: &entry.isbn }.emit();
+        self.catalog.insert(entry.isbn.clone(), VersionedCatalogEntry::V2(entry));
+        self.catalog.flush();
+        let entry_bytes = env::storage_usage() - initial_storage;
+        *self.catalog_bytes.entry(account_id.clone()).or_default() += entry_bytes;
+        self.catalog_bytes.flush();
+
+        self.charge_storage(&account_id, initial_storage)
+    }
+
+    /// Replace the catalog's metadata for an ISBN (moderators only). Every library
+    /// holding the ISBN sees the correction, except in the fields it overrides.
+    #[handle_result]
+    pub fn correct_catalog_entry(&mut self, entry: CatalogEntry) -> Result<(), BookyError> {
+        let moderator_id = self.access.require_role(Role::Moderator)?;
+        let entry = CatalogEntry { isbn: canonical_isbn(&entry.isbn)?, updated_at: U64(env::block_timestamp()), ..entry };
+        self.field_limits.check_catalog_entry(&entry)?;
+        if !self.catalog.contains_key(&entry.isbn) {
+            return Err(BookyError::NotInCatalog(entry.isbn));
+        }
+        BookyEvent::CatalogEntryCorrected { isbn: &entry.isbn, corrected_by: &moderator_id }.emit();
+        self.catalog.insert(entry.isbn.clone(), VersionedCatalogEntry::V2(entry));
+        Ok(())
+    }
+
+    /// The catalog's metadata for an ISBN, in any form
+    pub fn get_catalog_entry(&self, isbn: String) -> Option<CatalogEntry> {
+        let isbn = normalize_isbn(&isbn).unwrap_or(isbn);
+        self.catalog.get(&isbn).map(|entry| entry.current().into_owned())
+    }
+}
+
+impl Contract {
+    /// A library entry with the catalog's metadata filled in
+    pub(crate) fn with_catalog<'a>(&self, book: Cow<'a, BookEntry>) -> Cow<'a, BookEntry> {
+        let catalog = self.catalog.get(&book.isbn).map(VersionedCatalogEntry::current);
+        resolve(catalog.as_deref(), book)
+    }
+
+    /// Check a book as views will show it, with the catalog's metadata filled in,
+    /// then keep only the fields that override the catalog. `existing` is the stored
+    /// entry an update replaces; fields it left to the catalog stay with it unless
+    /// the caller changed them.
+    pub(crate) fn check_against_catalog(
+        &self,
+        book: &mut BookEntry,
+        existing: Option<&BookEntry>,
+    ) -> Result<(), BookyError> {
+        let catalog = self.catalog.get(&book.isbn).map(VersionedCatalogEntry::current);
+        book.catalog = None;
+        let inherited = match (&catalog, existing) {
+            (Some(catalog), existing) => {
+                if let Some(existing) = existing {
+                    catalog.unfill(book, existing);
+                }
+                Some(catalog.fill(book))
+            }
+            (None, _) => None,
+        };
+        self.field_limits.check_book(book)?;
+        if let Some(inherited) = inherited {
+            inherited.strip(book);
+        }
+        Ok(())
+    }
+}
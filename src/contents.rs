@@ -0,0 +1,135 @@
+// Tables of contents: the sections that chapter numbers in progress and notes refer to
+use crate::{BookEntry, Contract, ContractExt};
+use near_sdk::{near, AccountId};
+use std::borrow::Cow;
+
+/// One entry in a book's table of contents, e.g. a prologue, a chapter or "Part II"
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Section {
+    /// The number progress and notes use for this section, in place of a chapter number
+    pub id: u32,
+    pub label: String,
+    /// Position in reading order
+    pub ordinal: u32,
+    pub start_page: Option<u32>,
+    pub end_page: Option<u32>,
+}
+
+/// A section with the reader's progress through it
+#[near(serializers = [json])]
+pub struct SectionProgress {
+    pub section: Section,
+    pub read: bool,
+    pub has_note: bool,
+}
+
+/// The chapter numbers a book accepts: the ids in its table of contents if it has
+/// one, or else 1 to its chapter count. Also carries its page count.
+pub struct Contents<'a> {
+    sections: Cow<'a, [Section]>,
+    total_chapters: Option<u32>,
+    total_pages: Option<u32>,
+}
+
+impl<'a> Contents<'a> {
+    /// Contents of a book as views resolve it, with the catalog filled in
+    pub fn of(book: &'a BookEntry) -> Self {
+        Self {
+            sections: Cow::Borrowed(&book.sections),
+            total_chapters: book.total_chapters,
+            total_pages: book.total_pages,
+        }
+    }
+
+    pub fn into_owned(self) -> Contents<'static> {
+        Contents { sections: Cow::Owned(self.sections.into_owned()), ..self }
+    }
+
+    pub fn has_sections(&self) -> bool {
+        !self.sections.is_empty()
+    }
+
+    pub fn total_chapters(&self) -> Option<u32> {
+        self.total_chapters
+    }
+
+    pub fn total_pages(&self) -> Option<u32> {
+        self.total_pages
+    }
+
+    pub fn contains(&self, chapter: u32) -> bool {
+        if self.has_sections() {
+            return self.sections.iter().any(|section| section.id == chapter);
+        }
+        self.total_chapters.is_some_and(|total| (1..=total).contains(&chapter))
+    }
+
+    /// Number of sections, if known
+    pub fn count(&self) -> Option<u32> {
+        if self.has_sections() {
+            return Some(self.sections.len() as u32);
+        }
+        self.total_chapters.filter(|total| *total > 0)
+    }
+
+    /// Every section in reading order. Books without a table of contents get
+    /// numbered chapters.
+    pub fn sections(&self) -> Vec<Section> {
+        if !self.has_sections() {
+            let numbered = 1..=self.total_chapters.unwrap_or(0);
+            let chapter = |id| Section { id, label: format!("Chapter {}", id), ordinal: id, start_page: None, end_page: None };
+            return numbered.map(chapter).collect();
+        }
+        let mut sections = self.sections.to_vec();
+        sections.sort_by_key(|section| (section.ordinal, section.id));
+        sections
+    }
+
+    /// The section reading starts from: the first in the table of contents, or chapter 1
+    pub fn first_chapter(&self) -> u32 {
+        let first = self.sections.iter().min_by_key(|section| (section.ordinal, section.id));
+        first.map_or(1, |section| section.id)
+    }
+
+    /// Every section id in reading order
+    pub fn chapter_ids(&self) -> Vec<u32> {
+        self.sections().into_iter().map(|section| section.id).collect()
+    }
+}
+
+#[near]
+impl Contract {
+    /// A book's table of contents in reading order, with which sections have been read
+    /// and have notes. Empty if the book has no chapter count or `viewer` may not see it.
+    pub fn get_table_of_contents(
+        &self,
+        account_id: AccountId,
+        isbn: String,
+        viewer: Option<AccountId>,
+    ) -> Vec<SectionProgress> {
+        let book = match self.visible_book(&account_id, &isbn, viewer.as_ref()) {
+            Some(book) => book,
+            None => return Vec::new(),
+        };
+        Contents::of(&book)
+            .sections()
+            .into_iter()
+            .map(|section| SectionProgress {
+                read: book.chapters_read.contains(&section.id),
+                has_note: book.chapter_notes.contains_key(&section.id),
+                section,
+            })
+            .collect()
+    }
+}
+
+impl Contract {
+    /// Contents of a book as views resolve them, read before taking the book for mutation
+    pub(crate) fn contents(&self, account_id: &AccountId, isbn: &str) -> Contents<'static> {
+        match self.find_book(account_id, isbn) {
+            Some(book) => Contents::of(&book).into_owned(),
+            None => Contents { sections: Cow::Owned(Vec::new()), total_chapters: None, total_pages: None },
+        }
+    }
+}
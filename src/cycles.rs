@@ -0,0 +1,173 @@
+// Re-reads: each time through a book is a reading cycle, and finished cycles are kept on the entry
+use crate::{
+    is_published, set_reading_status, BookEntry, BookyError, BookyEvent, Contract, ContractExt, ReadingStatus,
+};
+use near_sdk::json_types::U64;
+use near_sdk::{env, near, AccountId};
+use std::collections::HashSet;
+use std::mem;
+
+/// Most finished cycles kept on one book
+pub const MAX_READING_CYCLES: usize = 50;
+
+/// Highest rating; ratings count half-stars, so 10 is five stars
+pub const MAX_RATING: u8 = 10;
+
+/// How a reading cycle ended
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReadingOutcome {
+    Completed,
+    Abandoned,
+}
+
+/// One time through a book
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReadingCycle {
+    pub started_at: U64,
+    /// When it was completed, or last updated before being abandoned
+    pub finished_at: Option<U64>,
+    /// Null while the cycle is in progress
+    pub outcome: Option<ReadingOutcome>,
+    pub chapters_read: HashSet<u32>,
+    /// Half-stars from 1 to 10; the current cycle has the book's rating
+    pub rating: Option<u8>,
+}
+
+impl BookEntry {
+    /// The cycle held in the entry's own progress fields, if it has been started
+    pub fn current_cycle(&self) -> Option<ReadingCycle> {
+        let outcome = match self.reading_status {
+            ReadingStatus::Completed => Some(ReadingOutcome::Completed),
+            ReadingStatus::Abandoned => Some(ReadingOutcome::Abandoned),
+            _ => None,
+        };
+        let started_at = match (self.started_at, outcome) {
+            (Some(started_at), _) => started_at,
+            // Finished without being started through the contract
+            (None, Some(_)) => self.added_at,
+            (None, None) => return None,
+        };
+        let finished_at = match outcome {
+            Some(ReadingOutcome::Completed) => Some(self.completed_at.unwrap_or(self.updated_at)),
+            Some(ReadingOutcome::Abandoned) => Some(self.updated_at),
+            None => None,
+        };
+        Some(ReadingCycle {
+            started_at,
+            finished_at,
+            outcome,
+            chapters_read: self.chapters_read.clone(),
+            rating: self.rating,
+        })
+    }
+
+    /// Finished books only go back to an unfinished status through `start_reread`,
+    /// which keeps the finished read as a cycle
+    pub(crate) fn check_reopen(&self, status: ReadingStatus) -> Result<(), BookyError> {
+        let finished = |status| matches!(status, ReadingStatus::Completed | ReadingStatus::Abandoned);
+        if finished(self.reading_status) && !finished(status) {
+            return Err(BookyError::AlreadyFinished { status: self.reading_status });
+        }
+        Ok(())
+    }
+
+    /// Number of completed reads, the current one included
+    pub fn completed_reads(&self) -> u32 {
+        let finished = self.reading_cycles.iter().filter(|cycle| cycle.outcome == Some(ReadingOutcome::Completed));
+        finished.count() as u32 + (self.reading_status == ReadingStatus::Completed) as u32
+    }
+}
+
+pub(crate) fn check_rating(rating: u8) -> Result<(), BookyError> {
+    if rating == 0 || rating > MAX_RATING {
+        return Err(BookyError::InvalidRating { rating, max: MAX_RATING });
+    }
+    Ok(())
+}
+
+#[near]
+impl Contract {
+    /// Start reading a completed or abandoned book again. The finished cycle is
+    /// kept in `reading_cycles` and progress starts over from `starting_chapter`.
+    #[handle_result]
+    pub fn start_reread(&mut self, isbn: String, starting_chapter: Option<u32>) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let limits = self.field_limits;
+        let library_privacy = self.library_privacy(&account_id);
+        let contents = self.contents(&account_id, &isbn);
+        let book = self.book_mut(&account_id, &isbn)?;
+
+        let finished = match book.current_cycle() {
+            Some(cycle) if cycle.outcome.is_some() => cycle,
+            _ => return Err(BookyError::NotFinished { status: book.reading_status }),
+        };
+        if book.reading_cycles.len() >= MAX_READING_CYCLES {
+            return Err(BookyError::TooManyReadingCycles { max: MAX_READING_CYCLES as u32 });
+        }
+        let starting_chapter = starting_chapter.unwrap_or_else(|| contents.first_chapter());
+        limits.check_chapter(starting_chapter, &contents)?;
+
+        // The finished read keeps its rating; the new one starts unrated and from the top
+        book.reading_cycles.push(ReadingCycle { chapters_read: mem::take(&mut book.chapters_read), ..finished });
+        let previous_rating = book.rating.take();
+        book.current_chapter = starting_chapter;
+        book.last_read_position.clear();
+        book.position = None;
+        book.started_at = None;
+        book.completed_at = None;
+        if is_published(book, library_privacy) {
+            BookyEvent::RereadStarted {
+                account_id: &account_id,
+                isbn: &book.isbn,
+                cycle: book.reading_cycles.len() as u32,
+            }
+            .emit();
+            if previous_rating.is_some() {
+                BookyEvent::BookRated { account_id: &account_id, isbn: &book.isbn, rating: None }.emit();
+            }
+        }
+        let activity = set_reading_status(&account_id, book, ReadingStatus::Reading, library_privacy);
+
+        let isbn = book.isbn.clone();
+        if let Some(kind) = activity {
+            self.record_activity(&account_id, &isbn, kind);
+        }
+        let tally_bytes = self.tally_rating(&isbn, previous_rating, None);
+        self.charge_storage(&account_id, initial_storage.saturating_add_signed(tally_bytes))
+    }
+
+    /// Rate a finished cycle, by its index in `reading_cycles`, or clear its rating with null
+    #[handle_result]
+    pub fn rate_reading_cycle(&mut self, isbn: String, cycle: u32, rating: Option<u8>) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        if let Some(rating) = rating {
+            check_rating(rating)?;
+        }
+        let book = self.book_mut(&account_id, &isbn)?;
+
+        let finished = book.reading_cycles
+            .get_mut(cycle as usize)
+            .ok_or(BookyError::CycleNotFound { cycle })?;
+        finished.rating = rating;
+
+        self.charge_storage(&account_id, initial_storage)
+    }
+
+    /// Every reading cycle of a book, oldest first, ending with the current one if it
+    /// has been started. Empty if `viewer` may not see the book.
+    pub fn get_reading_cycles(&self, account_id: AccountId, isbn: String, viewer: Option<AccountId>) -> Vec<ReadingCycle> {
+        let book = match self.visible_book(&account_id, &isbn, viewer.as_ref()) {
+            Some(book) => book,
+            None => return Vec::new(),
+        };
+        let mut cycles = book.reading_cycles.clone();
+        cycles.extend(book.current_cycle());
+        cycles
+    }
+}
@@ -0,0 +1,237 @@
+// Typed errors with stable codes, surfaced to callers through `FunctionError`
+use crate::{ReadingStatus, Role};
+use near_sdk::json_types::U128;
+use near_sdk::{AccountId, FunctionError};
+use std::fmt;
+
+/// Every failure a contract method can report. The panic message is
+/// `"<Code>: <description>"`, where `<Code>` is the variant name and never changes.
+#[derive(FunctionError, Debug, Clone, PartialEq, Eq)]
+pub enum BookyError {
+    LibraryNotFound,
+    BookNotFound,
+    DuplicateIsbn,
+    InvalidIsbn(String),
+    ChapterOutOfRange { chapter: u32, total_chapters: Option<u32> },
+    TooManyChapters { total_chapters: u32, max: u32 },
+    FieldTooLong { field: &'static str, max_length: u32 },
+    InvalidDate { field: &'static str, value: String },
+    SelfFollow,
+    NotFollowing(AccountId),
+    NotRegistered(AccountId),
+    InsufficientDeposit { minimum: U128 },
+    InsufficientStorageBalance { required: U128 },
+    WithdrawExceedsAvailable { available: U128 },
+    StorageInUse,
+    OneYoctoRequired,
+    NotOwner(AccountId),
+    MissingRole { account_id: AccountId, role: Role },
+    NotPendingOwner(AccountId),
+    Paused,
+    NoCodeAttached,
+    InvalidEnvelope { field: &'static str, reason: &'static str },
+    TooManyRecipients { max: u32 },
+    InvalidPublicKey { max_length: u32 },
+    NoPublicKey(AccountId),
+    NoteNotFound { chapter: u32 },
+    NoteNotEncrypted { chapter: u32 },
+    FollowRequestNotFound(AccountId),
+    NotAFollower(AccountId),
+    SelfBlock,
+    BlockedBy(AccountId),
+    NotBlocked(AccountId),
+    SelfMute,
+    NotMuted(AccountId),
+    SessionTooLong { minutes: u32, max: u32 },
+    NotFinished { status: ReadingStatus },
+    AlreadyFinished { status: ReadingStatus },
+    TooManyReadingCycles { max: u32 },
+    CycleNotFound { cycle: u32 },
+    InvalidRating { rating: u8, max: u8 },
+    EmptyField { field: &'static str },
+    TooManyShelves { max: u32 },
+    ShelfNotFound { shelf_id: u32 },
+    ShelfFull { max: u32 },
+    NotOnShelf { shelf_id: u32, isbn: String },
+    InvalidShelfOrder { shelf_id: u32 },
+    TooManyTags { max: u32 },
+    CatalogEntryExists(String),
+    NotInCatalog(String),
+    SectionNotFound { section: u32 },
+    InvalidSection { section: u32, reason: &'static str },
+    InvalidPosition { reason: &'static str },
+    TooManyCopies { max: u32 },
+    InvalidCurrency(String),
+}
+
+impl BookyError {
+    /// Stable identifier for clients to match on
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::LibraryNotFound => "LibraryNotFound",
+            Self::BookNotFound => "BookNotFound",
+            Self::DuplicateIsbn => "DuplicateIsbn",
+            Self::InvalidIsbn(_) => "InvalidIsbn",
+            Self::ChapterOutOfRange { .. } => "ChapterOutOfRange",
+            Self::TooManyChapters { .. } => "TooManyChapters",
+            Self::FieldTooLong { .. } => "FieldTooLong",
+            Self::InvalidDate { .. } => "InvalidDate",
+            Self::SelfFollow => "SelfFollow",
+            Self::NotFollowing(_) => "NotFollowing",
+            Self::NotRegistered(_) => "NotRegistered",
+            Self::InsufficientDeposit { .. } => "InsufficientDeposit",
+            Self::InsufficientStorageBalance { .. } => "InsufficientStorageBalance",
+            Self::WithdrawExceedsAvailable { .. } => "WithdrawExceedsAvailable",
+            Self::StorageInUse => "StorageInUse",
+            Self::OneYoctoRequired => "OneYoctoRequired",
+            Self::NotOwner(_) => "NotOwner",
+            Self::MissingRole { .. } => "MissingRole",
+            Self::NotPendingOwner(_) => "NotPendingOwner",
+            Self::Paused => "Paused",
+            Self::NoCodeAttached => "NoCodeAttached",
+            Self::InvalidEnvelope { .. } => "InvalidEnvelope",
+            Self::TooManyRecipients { .. } => "TooManyRecipients",
+            Self::InvalidPublicKey { .. } => "InvalidPublicKey",
+            Self::NoPublicKey(_) => "NoPublicKey",
+            Self::NoteNotFound { .. } => "NoteNotFound",
+            Self::NoteNotEncrypted { .. } => "NoteNotEncrypted",
+            Self::FollowRequestNotFound(_) => "FollowRequestNotFound",
+            Self::NotAFollower(_) => "NotAFollower",
+            Self::SelfBlock => "SelfBlock",
+            Self::BlockedBy(_) => "BlockedBy",
+            Self::NotBlocked(_) => "NotBlocked",
+            Self::SelfMute => "SelfMute",
+            Self::NotMuted(_) => "NotMuted",
+            Self::SessionTooLong { .. } => "SessionTooLong",
+            Self::NotFinished { .. } => "NotFinished",
+            Self::AlreadyFinished { .. } => "AlreadyFinished",
+            Self::TooManyReadingCycles { .. } => "TooManyReadingCycles",
+            Self::CycleNotFound { .. } => "CycleNotFound",
+            Self::InvalidRating { .. } => "InvalidRating",
+            Self::EmptyField { .. } => "EmptyField",
+            Self::TooManyShelves { .. } => "TooManyShelves",
+            Self::ShelfNotFound { .. } => "ShelfNotFound",
+            Self::ShelfFull { .. } => "ShelfFull",
+            Self::NotOnShelf { .. } => "NotOnShelf",
+            Self::InvalidShelfOrder { .. } => "InvalidShelfOrder",
+            Self::TooManyTags { .. } => "TooManyTags",
+            Self::CatalogEntryExists(_) => "CatalogEntryExists",
+            Self::NotInCatalog(_) => "NotInCatalog",
+            Self::SectionNotFound { .. } => "SectionNotFound",
+            Self::InvalidSection { .. } => "InvalidSection",
+            Self::InvalidPosition { .. } => "InvalidPosition",
+            Self::TooManyCopies { .. } => "TooManyCopies",
+            Self::InvalidCurrency(_) => "InvalidCurrency",
+        }
+    }
+}
+
+impl fmt::Display for BookyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.code())?;
+        match self {
+            Self::LibraryNotFound => write!(f, "Library not found"),
+            Self::BookNotFound => write!(f, "Book not found in your library"),
+            Self::DuplicateIsbn => write!(f, "Book with this ISBN already exists in your library"),
+            Self::InvalidIsbn(reason) => write!(f, "{}", reason),
+            Self::ChapterOutOfRange { chapter, total_chapters: Some(total) } => {
+                write!(f, "Chapter number {} exceeds total chapters {}", chapter, total)
+            }
+            Self::ChapterOutOfRange { chapter: 0, .. } => write!(f, "Chapter number must be at least 1"),
+            Self::ChapterOutOfRange { chapter, .. } => {
+                write!(f, "Chapter number {} exceeds the maximum chapter count", chapter)
+            }
+            Self::TooManyChapters { total_chapters, max } => {
+                write!(f, "Total chapters {} exceeds the maximum of {}", total_chapters, max)
+            }
+            Self::FieldTooLong { field, max_length } => {
+                write!(f, "Field {} is longer than {} bytes", field, max_length)
+            }
+            Self::InvalidDate { field, value } => {
+                write!(f, "Field {} must be an ISO-8601 date (YYYY-MM-DD), got {:?}", field, value)
+            }
+            Self::SelfFollow => write!(f, "Cannot follow yourself"),
+            Self::NotFollowing(account_id) => write!(f, "Not following {}", account_id),
+            Self::NotRegistered(account_id) => {
+                write!(f, "Account {} is not registered; call storage_deposit first", account_id)
+            }
+            Self::InsufficientDeposit { minimum } => write!(
+                f,
+                "The attached deposit is less than the minimum storage balance of {} yoctoNEAR",
+                minimum.0
+            ),
+            Self::InsufficientStorageBalance { required } => write!(
+                f,
+                "Insufficient storage balance: {} yoctoNEAR more is required",
+                required.0
+            ),
+            Self::WithdrawExceedsAvailable { available } => write!(
+                f,
+                "The amount is greater than the available storage balance of {} yoctoNEAR",
+                available.0
+            ),
+            Self::StorageInUse => write!(f, "Can't unregister the account with stored data without `force`"),
+            Self::OneYoctoRequired => write!(f, "Requires attached deposit of exactly 1 yoctoNEAR"),
+            Self::NotOwner(account_id) => write!(f, "Account {} is not the contract owner", account_id),
+            Self::MissingRole { account_id, role } => {
+                write!(f, "Account {} does not have the {:?} role", account_id, role)
+            }
+            Self::NotPendingOwner(account_id) => {
+                write!(f, "Account {} has not been proposed as the new owner", account_id)
+            }
+            Self::Paused => write!(f, "The contract is paused"),
+            Self::NoCodeAttached => write!(f, "Pass the new contract code as the raw call input"),
+            Self::InvalidEnvelope { field, reason } => write!(f, "Encrypted {} is malformed: {}", field, reason),
+            Self::TooManyRecipients { max } => write!(f, "An encrypted note can have at most {} recipients", max),
+            Self::InvalidPublicKey { max_length } => {
+                write!(f, "Public keys must be between 1 and {} bytes", max_length)
+            }
+            Self::NoPublicKey(account_id) => {
+                write!(f, "Account {} has not published a note public key", account_id)
+            }
+            Self::NoteNotFound { chapter } => write!(f, "No note found for chapter {}", chapter),
+            Self::NoteNotEncrypted { chapter } => write!(f, "The note for chapter {} is not encrypted", chapter),
+            Self::FollowRequestNotFound(account_id) => {
+                write!(f, "No pending follow request from {}", account_id)
+            }
+            Self::NotAFollower(account_id) => write!(f, "{} is not following you", account_id),
+            Self::SelfBlock => write!(f, "Cannot block yourself"),
+            Self::BlockedBy(account_id) => write!(f, "Account {} has blocked you", account_id),
+            Self::NotBlocked(account_id) => write!(f, "Account {} is not blocked", account_id),
+            Self::SelfMute => write!(f, "Cannot mute yourself"),
+            Self::NotMuted(account_id) => write!(f, "Account {} is not muted", account_id),
+            Self::SessionTooLong { minutes, max } => {
+                write!(f, "A reading session of {} minutes exceeds the maximum of {}", minutes, max)
+            }
+            Self::NotFinished { status } => {
+                write!(f, "Only completed or abandoned books can be re-read, this one is {:?}", status)
+            }
+            Self::AlreadyFinished { status } => {
+                write!(f, "This book is {:?}; use start_reread to read it again", status)
+            }
+            Self::TooManyReadingCycles { max } => write!(f, "A book can keep at most {} finished reads", max),
+            Self::CycleNotFound { cycle } => write!(f, "No finished read at index {}", cycle),
+            Self::InvalidRating { rating, max } => write!(f, "Rating {} is not between 1 and {}", rating, max),
+            Self::EmptyField { field } => write!(f, "Field {} can't be empty", field),
+            Self::TooManyShelves { max } => write!(f, "An account can have at most {} shelves", max),
+            Self::ShelfNotFound { shelf_id } => write!(f, "Shelf {} not found", shelf_id),
+            Self::ShelfFull { max } => write!(f, "A shelf can hold at most {} books", max),
+            Self::NotOnShelf { shelf_id, isbn } => write!(f, "Book {} is not on shelf {}", isbn, shelf_id),
+            Self::InvalidShelfOrder { shelf_id } => {
+                write!(f, "The new order must list exactly the books on shelf {}", shelf_id)
+            }
+            Self::TooManyTags { max } => write!(f, "A book can have at most {} tags", max),
+            Self::CatalogEntryExists(isbn) => {
+                write!(f, "The catalog already has {}; ask a moderator to correct it", isbn)
+            }
+            Self::NotInCatalog(isbn) => write!(f, "The catalog has no entry for {}", isbn),
+            Self::SectionNotFound { section } => write!(f, "Section {} is not in the table of contents", section),
+            Self::InvalidSection { section, reason } => write!(f, "Invalid section {}: {}", section, reason),
+            Self::InvalidPosition { reason } => write!(f, "Invalid reading position: {}", reason),
+            Self::TooManyCopies { max } => write!(f, "A book can list at most {} owned copies", max),
+            Self::InvalidCurrency(currency) => {
+                write!(f, "Currency {:?} is not a three-letter ISO 4217 code", currency)
+            }
+        }
+    }
+}
@@ -0,0 +1,301 @@
+// NEP-297 events emitted for every state change, so indexers can rebuild libraries from receipts
+use crate::{
+    BookEntry, BookFormat, NoteContent, OwnedCopy, PrivacySettings, ReadingPosition, ReadingStatus, Review, Role,
+    Section,
+};
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::{near, AccountId};
+
+/// Bytes a `book_added` or `book_updated` event and the `note_added` and
+/// `note_deleted` events that go with it may log together, leaving room within
+/// the 16 KiB a call may log for the other events of the call
+pub const MAX_BOOK_EVENT_BYTES: usize = 12 * 1024;
+
+/// Events logged as `EVENT_JSON:{"standard":"booky","version":...,"event":...,"data":...}`
+///
+/// Logs are public, so events only carry what a viewer without an account may
+/// see: events about books that aren't public are skipped, and hidden fields are
+/// left out of the rest.
+#[near(event_json(standard = "booky"))]
+pub enum BookyEvent<'a> {
+    /// Followed by a `note_added` per public note
+    #[event_version("2.1.0")]
+    BookAdded {
+        account_id: &'a AccountId,
+        isbn: &'a str,
+        book: BookFields,
+    },
+
+    /// `book` only has the fields that changed, or every field when the book just
+    /// became public. `previous_isbn` is set when the update moved the book to a
+    /// new ISBN. A null `book` means the book is no longer public. Followed by a
+    /// `note_added` or `note_deleted` per note the public now sees differently.
+    #[event_version("2.1.0")]
+    BookUpdated {
+        account_id: &'a AccountId,
+        isbn: &'a str,
+        previous_isbn: Option<&'a str>,
+        book: Option<BookFields>,
+    },
+
+    #[event_version("1.0.0")]
+    BookDeleted { account_id: &'a AccountId, isbn: &'a str },
+
+    /// `migrate_isbns` moved the book from `old_isbn` to its canonical ISBN-13;
+    /// nothing else about it changed
+    #[event_version("1.0.0")]
+    IsbnRekeyed { account_id: &'a AccountId, old_isbn: &'a str, isbn: &'a str },
+
+    /// Carries the resulting progress fields, plus the chapters newly marked as read
+    #[event_version("1.1.0")]
+    ProgressUpdated {
+        account_id: &'a AccountId,
+        isbn: &'a str,
+        current_chapter: u32,
+        chapters_completed: Vec<u32>,
+        last_read_position: &'a str,
+        position: Option<&'a ReadingPosition>,
+        last_read_date: Option<&'a str>,
+    },
+
+    /// `note` is null when notes on the book are hidden from the public
+    #[event_version("1.1.0")]
+    NoteAdded {
+        account_id: &'a AccountId,
+        isbn: &'a str,
+        chapter: u32,
+        note: Option<&'a NoteContent>,
+    },
+
+    #[event_version("1.0.0")]
+    NoteDeleted { account_id: &'a AccountId, isbn: &'a str, chapter: u32 },
+
+    #[event_version("1.0.0")]
+    Followed { account_id: &'a AccountId, followed_account_id: &'a AccountId },
+
+    #[event_version("1.0.0")]
+    Unfollowed { account_id: &'a AccountId, unfollowed_account_id: &'a AccountId },
+
+    /// The followed account approves followers, so the follow is pending
+    #[event_version("1.0.0")]
+    FollowRequested { account_id: &'a AccountId, target_account_id: &'a AccountId },
+
+    /// `follower_id` now follows `account_id`
+    #[event_version("1.0.0")]
+    FollowApproved { account_id: &'a AccountId, follower_id: &'a AccountId },
+
+    #[event_version("1.0.0")]
+    FollowRejected { account_id: &'a AccountId, requester_id: &'a AccountId },
+
+    /// `follower_id` no longer follows `account_id`, which removed or blocked it
+    #[event_version("1.0.0")]
+    FollowerRemoved { account_id: &'a AccountId, follower_id: &'a AccountId },
+
+    #[event_version("1.0.0")]
+    AccountBlocked { account_id: &'a AccountId, blocked_account_id: &'a AccountId },
+
+    #[event_version("1.0.0")]
+    AccountUnblocked { account_id: &'a AccountId, unblocked_account_id: &'a AccountId },
+
+    #[event_version("1.0.0")]
+    AccountMuted { account_id: &'a AccountId, muted_account_id: &'a AccountId },
+
+    #[event_version("1.0.0")]
+    AccountUnmuted { account_id: &'a AccountId, unmuted_account_id: &'a AccountId },
+
+    #[event_version("1.0.0")]
+    StatusChanged {
+        account_id: &'a AccountId,
+        isbn: &'a str,
+        old_status: ReadingStatus,
+        new_status: ReadingStatus,
+    },
+
+    /// `rating` is null when the rating was cleared
+    #[event_version("1.0.0")]
+    BookRated { account_id: &'a AccountId, isbn: &'a str, rating: Option<u8> },
+
+    /// `review` is null when the review was deleted
+    #[event_version("1.0.0")]
+    ReviewWritten { account_id: &'a AccountId, isbn: &'a str, review: Option<&'a Review> },
+
+    /// Shelf events are only emitted for public libraries
+    #[event_version("1.0.0")]
+    ShelfCreated { account_id: &'a AccountId, shelf_id: u32 },
+
+    #[event_version("1.0.0")]
+    ShelfDeleted { account_id: &'a AccountId, shelf_id: u32 },
+
+    #[event_version("1.0.0")]
+    ShelfReordered { account_id: &'a AccountId, shelf_id: u32 },
+
+    #[event_version("1.0.0")]
+    BookShelved { account_id: &'a AccountId, shelf_id: u32, isbn: &'a str, position: u32 },
+
+    #[event_version("1.0.0")]
+    BookUnshelved { account_id: &'a AccountId, shelf_id: u32, isbn: &'a str },
+
+    /// Carries the book's full tag list after the change
+    #[event_version("1.0.0")]
+    BookTagged { account_id: &'a AccountId, isbn: &'a str, tags: &'a [String] },
+
+    /// A finished book was started again; `cycle` is the number of earlier reads
+    #[event_version("1.0.0")]
+    RereadStarted { account_id: &'a AccountId, isbn: &'a str, cycle: u32 },
+
+    #[event_version("1.0.0")]
+    CatalogEntryAdded { account_id: &'a AccountId, isbn: &'a str },
+
+    /// Libraries holding the ISBN show the corrected metadata from now on
+    #[event_version("1.0.0")]
+    CatalogEntryCorrected { isbn: &'a str, corrected_by: &'a AccountId },
+
+    #[event_version("1.0.0")]
+    NoteKeyShared {
+        account_id: &'a AccountId,
+        isbn: &'a str,
+        chapter: u32,
+        recipient: &'a AccountId,
+    },
+
+    /// `public_key` is null when the key was removed
+    #[event_version("1.0.0")]
+    NotePublicKeyChanged { account_id: &'a AccountId, public_key: Option<&'a Base64VecU8> },
+
+    /// Library-wide settings changed; re-read the library with no viewer to resync
+    #[event_version("1.0.0")]
+    LibraryPrivacyChanged { account_id: &'a AccountId, settings: PrivacySettings },
+
+    /// The account closed its storage account with `force`, deleting its library,
+    /// follows, follow requests and everything else it stored
+    #[event_version("1.0.0")]
+    AccountUnregistered { account_id: &'a AccountId },
+
+    #[event_version("1.0.0")]
+    OwnershipProposed { owner_id: &'a AccountId, pending_owner_id: &'a AccountId },
+
+    #[event_version("1.0.0")]
+    OwnershipTransferred { previous_owner_id: &'a AccountId, owner_id: &'a AccountId },
+
+    #[event_version("1.0.0")]
+    RoleGranted { account_id: &'a AccountId, role: Role, granted_by: &'a AccountId },
+
+    #[event_version("1.0.0")]
+    RoleRevoked { account_id: &'a AccountId, role: Role, revoked_by: &'a AccountId },
+
+    #[event_version("1.0.0")]
+    Paused { account_id: &'a AccountId },
+
+    #[event_version("1.0.0")]
+    Unpaused { account_id: &'a AccountId },
+}
+
+/// A book as `BookAdded` and `BookUpdated` carry it, with counts of its
+/// collections. Notes travel in their own events. Fields that didn't change are
+/// omitted. When the content would take the events past `MAX_BOOK_EVENT_BYTES`,
+/// comments, copies, sections, read chapters and notes are left out and
+/// `content_omitted` is set; read the book with `get_book` instead.
+#[near(serializers = [json])]
+pub struct BookFields {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acquisition_date: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<BookFormat>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub copies: Option<Vec<OwnedCopy>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub personal_comments: Option<NoteContent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_hash: Option<Option<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reading_status: Option<ReadingStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_chapter: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_chapters: Option<Option<u32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sections: Option<Vec<Section>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_pages: Option<Option<u32>>,
+    /// In ascending order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chapters_read: Option<Vec<u32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_read_position: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<Option<ReadingPosition>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_read_date: Option<Option<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub privacy: Option<Option<PrivacySettings>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chapters_read_count: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note_count: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub section_count: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub copy_count: Option<u32>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub content_omitted: bool,
+}
+
+impl BookFields {
+    /// The fields of `book` that differ from `old`, or all of them without `old`
+    pub fn changed(old: Option<&BookEntry>, book: &BookEntry) -> Self {
+        fn field<T: PartialEq>(old: Option<&BookEntry>, book: &BookEntry, get: impl Fn(&BookEntry) -> T) -> Option<T> {
+            let value = get(book);
+            match old {
+                Some(old) if get(old) == value => None,
+                _ => Some(value),
+            }
+        }
+        Self {
+            title: field(old, book, |b| b.title.clone()),
+            author: field(old, book, |b| b.author.clone()),
+            acquisition_date: field(old, book, |b| b.acquisition_date.clone()),
+            condition: field(old, book, |b| b.condition.clone()),
+            format: field(old, book, |b| b.format),
+            copies: field(old, book, |b| b.copies.clone()),
+            personal_comments: field(old, book, |b| b.personal_comments.clone()),
+            media_hash: field(old, book, |b| b.media_hash.clone()),
+            reading_status: field(old, book, |b| b.reading_status),
+            current_chapter: field(old, book, |b| b.current_chapter),
+            total_chapters: field(old, book, |b| b.total_chapters),
+            sections: field(old, book, |b| b.sections.clone()),
+            total_pages: field(old, book, |b| b.total_pages),
+            chapters_read: field(old, book, |b| {
+                let mut chapters: Vec<u32> = b.chapters_read.iter().copied().collect();
+                chapters.sort_unstable();
+                chapters
+            }),
+            last_read_position: field(old, book, |b| b.last_read_position.clone()),
+            position: field(old, book, |b| b.position),
+            last_read_date: field(old, book, |b| b.last_read_date.clone()),
+            privacy: field(old, book, |b| b.privacy),
+            chapters_read_count: field(old, book, |b| b.chapters_read.len() as u32),
+            note_count: field(old, book, |b| b.chapter_notes.len() as u32),
+            section_count: field(old, book, |b| b.sections.len() as u32),
+            copy_count: field(old, book, |b| b.copies.len() as u32),
+            content_omitted: false,
+        }
+    }
+
+    /// These fields with the bodies left out, for books too large to log
+    pub fn without_content(self) -> Self {
+        Self {
+            copies: None,
+            personal_comments: None,
+            sections: None,
+            chapters_read: None,
+            content_omitted: true,
+            ..self
+        }
+    }
+}
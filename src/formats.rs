@@ -0,0 +1,76 @@
+// Book formats and the copies a reader owns, with library stats broken down by format
+use crate::{BookEntry, ReadingStatus};
+use near_sdk::json_types::U64;
+use near_sdk::near;
+
+/// Physical or digital form of a book
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub enum BookFormat {
+    Hardcover,
+    Paperback,
+    Ebook,
+    Audiobook,
+    #[default]
+    Other,
+}
+
+impl BookFormat {
+    pub const ALL: [BookFormat; 5] = [Self::Hardcover, Self::Paperback, Self::Ebook, Self::Audiobook, Self::Other];
+}
+
+/// An amount in a currency's minor unit, e.g. 1999 for 19.99 USD
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Price {
+    pub amount: U64,
+    /// ISO 4217 code, e.g. "USD"
+    pub currency: String,
+}
+
+/// One copy of a book the reader owns
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OwnedCopy {
+    pub format: BookFormat,
+    pub condition: String,
+    pub acquisition_date: String, // ISO format: YYYY-MM-DD
+    pub price_paid: Option<Price>,
+    /// Where the copy is kept, e.g. "Office shelf" or "Kindle"
+    pub location: Option<String>,
+}
+
+/// Books and owned copies of one format
+#[near(serializers = [json, borsh])]
+#[derive(Debug, PartialEq)]
+pub struct FormatStats {
+    pub format: BookFormat,
+    /// Books whose `format` is this one
+    pub books: u32,
+    pub completed: u32,
+    /// Owned copies in this format, across all books
+    pub copies: u32,
+}
+
+/// Per-format counts over a library, in `BookFormat::ALL` order
+pub(crate) struct FormatTally([FormatStats; 5]);
+
+impl FormatTally {
+    pub fn new() -> Self {
+        Self(BookFormat::ALL.map(|format| FormatStats { format, books: 0, completed: 0, copies: 0 }))
+    }
+
+    pub fn add(&mut self, book: &BookEntry) {
+        let stats = &mut self.0[book.format as usize];
+        stats.books += 1;
+        stats.completed += (book.reading_status == ReadingStatus::Completed) as u32;
+        for copy in &book.copies {
+            self.0[copy.format as usize].copies += 1;
+        }
+    }
+
+    /// The formats that have any books or copies
+    pub fn into_stats(self) -> Vec<FormatStats> {
+        self.0.into_iter().filter(|stats| stats.books > 0 || stats.copies > 0).collect()
+    }
+}
@@ -0,0 +1,99 @@
+// ISBN validation and normalization to a canonical ISBN-13
+//
+// Books are keyed by the canonical form: 13 digits with no separators, so
+// "978-0451524935", "978 0 451 52493 5" and "0451524935" all name the same book.
+
+/// Validate an ISBN-10 or ISBN-13 and return its canonical ISBN-13 form
+pub fn normalize_isbn(isbn: &str) -> Result<String, String> {
+    let compact: String = isbn
+        .chars()
+        .filter(|c| !matches!(c, '-' | ' '))
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    if !compact.is_ascii() {
+        return Err(format!("Invalid ISBN: {}", isbn));
+    }
+
+    match compact.len() {
+        10 => isbn10_to_isbn13(&compact).ok_or_else(|| format!("Invalid ISBN-10: {}", isbn)),
+        13 if is_valid_isbn13(&compact) => Ok(compact),
+        13 => Err(format!("Invalid ISBN-13: {}", isbn)),
+        _ => Err(format!("ISBN must have 10 or 13 digits: {}", isbn)),
+    }
+}
+
+fn digits(s: &str) -> Option<Vec<u32>> {
+    s.chars().map(|c| c.to_digit(10)).collect()
+}
+
+fn isbn13_check_digit(first_twelve: &[u32]) -> u32 {
+    let sum: u32 = first_twelve
+        .iter()
+        .enumerate()
+        .map(|(i, d)| if i % 2 == 0 { *d } else { d * 3 })
+        .sum();
+    (10 - sum % 10) % 10
+}
+
+fn is_valid_isbn13(isbn: &str) -> bool {
+    if !(isbn.starts_with("978") || isbn.starts_with("979")) {
+        return false;
+    }
+    match digits(isbn) {
+        Some(d) => isbn13_check_digit(&d[..12]) == d[12],
+        None => false,
+    }
+}
+
+/// Check an ISBN-10 (whose check digit may be 'X') and convert it to ISBN-13
+fn isbn10_to_isbn13(isbn: &str) -> Option<String> {
+    let body = digits(&isbn[..9])?;
+    let check = match isbn.as_bytes()[9] {
+        b'X' => 10,
+        c @ b'0'..=b'9' => (c - b'0') as u32,
+        _ => return None,
+    };
+
+    let sum: u32 = body.iter().enumerate().map(|(i, d)| d * (10 - i as u32)).sum::<u32>() + check;
+    if sum % 11 != 0 {
+        return None;
+    }
+
+    let mut isbn13: Vec<u32> = vec![9, 7, 8];
+    isbn13.extend(body);
+    let check13 = isbn13_check_digit(&isbn13);
+    isbn13.push(check13);
+    Some(isbn13.iter().map(|d| char::from_digit(*d, 10).unwrap()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hyphenated_and_compact_isbn13_match() {
+        assert_eq!(normalize_isbn("978-0451524935").unwrap(), "9780451524935");
+        assert_eq!(normalize_isbn("978 0 451 52493 5").unwrap(), "9780451524935");
+        assert_eq!(normalize_isbn("9780451524935").unwrap(), "9780451524935");
+    }
+
+    #[test]
+    fn isbn10_converts_to_isbn13() {
+        assert_eq!(normalize_isbn("0-451-52493-4").unwrap(), "9780451524935");
+        // 'X' check digit
+        assert_eq!(normalize_isbn("0-8044-2957-X").unwrap(), "9780804429573");
+        assert_eq!(normalize_isbn("0-8044-2957-x").unwrap(), "9780804429573");
+    }
+
+    #[test]
+    fn rejects_bad_checksums_and_garbage() {
+        assert!(normalize_isbn("978-0451524936").is_err());
+        assert!(normalize_isbn("0-451-52493-5").is_err());
+        assert!(normalize_isbn("not an isbn").is_err());
+        assert!(normalize_isbn("123").is_err());
+        assert!(normalize_isbn("97804515249X5").is_err());
+        // Valid checksum but not a Bookland prefix
+        assert!(normalize_isbn("1234567890128").is_err());
+    }
+}
@@ -1,29 +1,191 @@
 // Book Library Storage - Simple On-Chain Book Management with Reading Progress & Chapter Notes
-use near_sdk::{env, log, near, AccountId};
+use near_sdk::store::{IterableMap, IterableSet, LookupMap, LookupSet};
+use near_sdk::json_types::{Base64VecU8, U64};
+use near_sdk::{env, log, near, AccountId, BorshStorageKey};
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 
+mod access;
+mod activity;
+mod catalog;
+mod contents;
+mod cycles;
+mod errors;
+mod events;
+mod formats;
+mod isbn;
+mod migrate;
+mod notes;
+mod position;
+mod privacy;
+mod query;
+mod ratings;
+mod sessions;
+mod shelves;
+mod social;
+mod storage;
+mod validation;
+
+pub use access::Role;
+use access::AccessControl;
+pub use activity::{Activity, ActivityKind, FeedItem};
+use activity::{status_activity, ActivityLog};
+pub use catalog::{CatalogEntry, VersionedCatalogEntry};
+pub use contents::{Section, SectionProgress};
+pub use cycles::{ReadingCycle, ReadingOutcome};
+pub use errors::BookyError;
+pub use events::{BookFields, BookyEvent, MAX_BOOK_EVENT_BYTES};
+pub use formats::{BookFormat, FormatStats, OwnedCopy, Price};
+use formats::FormatTally;
+pub use isbn::normalize_isbn;
+pub use migrate::{
+    BookEntryV1, BookEntryV2, BookEntryV3, BookEntryV4, BookEntryV5, BookEntryV6, BookEntryV7, CatalogEntryV1,
+    IsbnCollision, IsbnMigrationReport, StateVersion, VersionedContract, CURRENT_STATE_VERSION,
+};
+pub use notes::{EncryptedEnvelope, NoteContent, RecipientKey};
+pub use position::ReadingPosition;
+pub use privacy::{PrivacySettings, Visibility};
+use privacy::Audience;
+pub use query::{LibraryQuery, Page, SortBy};
+use query::paginate;
+pub use ratings::{RatingSummary, Review};
+use ratings::{tally_key, RatingTally};
+pub use sessions::{ReadingPace, ReadingSession, TimeRange};
+pub use shelves::{ShelfInfo, ShelfPage, TagCount};
+use shelves::AccountShelves;
+
+pub use storage::{StorageBalance, StorageBalanceBounds};
+use storage::AccountStorage;
+pub use validation::FieldLimits;
+use validation::iso_date_from_timestamp;
+
 /// Individual book entry stored on-chain with reading progress tracking and chapter notes
 #[near(serializers = [json, borsh])]
 #[derive(Clone)]
 pub struct BookEntry {
     pub isbn: String,
-    pub title: String,
-    pub author: String,
+    pub title: String,                  // Empty to use the catalog's
+    pub author: String,                 // Empty to use the catalog's
     pub acquisition_date: String, // ISO format: YYYY-MM-DD
     pub condition: String,         // e.g., "Like New", "Good", "Fair"
-    pub personal_comments: String,
+    #[serde(default)]
+    pub format: BookFormat,        // The format you read
+    #[serde(default)]
+    pub copies: Vec<OwnedCopy>,    // Every copy you own, in any format
+    pub personal_comments: NoteContent, // Plain text or an encrypted envelope
     pub media_hash: Option<String>, // IPFS/Arweave hash for photos
 
+    // Set with rate_book and write_review; values sent with the book are ignored
+    #[serde(default)]
+    pub rating: Option<u8>,         // Half-stars, 1 to 10
+    #[serde(default)]
+    pub review: Option<Review>,
+    #[serde(default)]
+    pub tags: Vec<String>,          // Set with tag_book; lowercase
+
     // Reading Progress Tracking
     pub reading_status: ReadingStatus,
     pub current_chapter: u32,     // Chapter number currently reading
-    pub total_chapters: Option<u32>, // Total chapters in book
+    pub total_chapters: Option<u32>, // Total chapters in book; null to use the catalog's
+    #[serde(default)]
+    pub sections: Vec<Section>,     // Table of contents; chapter numbers are section ids when set
+    #[serde(default)]
+    pub total_pages: Option<u32>,   // null to use the catalog's page count
     pub chapters_read: HashSet<u32>, // Set of completed chapter numbers
     pub last_read_position: String, // e.g., "page 45", "23%", "chapter 3 paragraph 2"
-    pub last_read_date: Option<String>, // ISO format: YYYY-MM-DD
+    #[serde(default)]
+    pub position: Option<ReadingPosition>, // Structured position, which progress is computed from
+    pub last_read_date: Option<String>, // ISO format: YYYY-MM-DD, for display only
+
+    // Block timestamps in nanoseconds, set by the contract. Values sent by callers are ignored.
+    #[serde(default)]
+    pub added_at: U64,
+    #[serde(default)]
+    pub started_at: Option<U64>,
+    #[serde(default)]
+    pub completed_at: Option<U64>,
+    #[serde(default)]
+    pub updated_at: U64,
+
+    // Finished earlier reads, oldest first; the current read is in the fields above.
+    // Kept by the contract: values sent by callers are ignored.
+    #[serde(default)]
+    pub reading_cycles: Vec<ReadingCycle>,
+
+    // Chapter Notes - Maps chapter number to personal note, plain or encrypted
+    pub chapter_notes: HashMap<u32, NoteContent>,
+
+    // Privacy - combined with the library-wide settings, the stricter one wins
+    pub privacy: Option<PrivacySettings>,
+
+    // The catalog's metadata for this ISBN, filled in by views and never stored
+    #[borsh(skip)]
+    #[serde(default)]
+    pub catalog: Option<Box<CatalogEntry>>,
+}
+
+/// A library entry as stored, tagged with the `BookEntry` layout it was written in.
+/// When `BookEntry` gains fields, the old layout moves to a new variant here and
+/// is converted on read, so existing records keep deserializing.
+///
+/// Variants stay unboxed: old layouts are rewritten as the current one on their
+/// next write, and boxing the current one would add an allocation to every read.
+#[allow(clippy::large_enum_variant)]
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub enum VersionedBookEntry {
+    V1(BookEntryV1),
+    V2(BookEntryV2),
+    V3(BookEntryV3),
+    V4(BookEntryV4),
+    V5(BookEntryV5),
+    V6(BookEntryV6),
+    V7(BookEntryV7),
+    V8(BookEntry),
+}
+
+impl VersionedBookEntry {
+    /// The entry in the current layout
+    pub fn current(&self) -> Cow<'_, BookEntry> {
+        match self {
+            Self::V8(book) => Cow::Borrowed(book),
+            old => Cow::Owned(old.clone().into_current()),
+        }
+    }
+
+    /// The entry in the current layout for mutation, upgrading it in place
+    pub fn current_mut(&mut self) -> &mut BookEntry {
+        if !matches!(self, Self::V8(_)) {
+            *self = Self::V8(self.clone().into_current());
+        }
+        match self {
+            Self::V8(book) => book,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Convert an old layout one version at a time up to the current one
+    pub(crate) fn into_current(self) -> BookEntry {
+        let mut entry = self;
+        loop {
+            entry = match entry {
+                Self::V1(book) => Self::V2(book.into()),
+                Self::V2(book) => Self::V3(book.into()),
+                Self::V3(book) => Self::V4(book.into()),
+                Self::V4(book) => Self::V5(book.into()),
+                Self::V5(book) => Self::V6(book.into()),
+                Self::V6(book) => Self::V7(book.into()),
+                Self::V7(book) => Self::V8(book.into()),
+                Self::V8(book) => return book,
+            };
+        }
+    }
+}
 
-    // Chapter Notes - Maps chapter number to personal note
-    pub chapter_notes: HashMap<u32, String>,
+impl From<BookEntry> for VersionedBookEntry {
+    fn from(book: BookEntry) -> Self {
+        Self::V8(book)
+    }
 }
 
 /// Reading status of a book
@@ -43,119 +205,336 @@ pub struct ProgressUpdate {
     pub current_chapter: Option<u32>,
     pub chapters_completed: Vec<u32>,     // Chapters just completed
     pub last_read_position: Option<String>,
+    #[serde(default)]
+    pub position: Option<ReadingPosition>, // Also sets last_read_position, unless that is given too
     pub last_read_date: Option<String>,
     pub reading_status: Option<ReadingStatus>,
+    pub minutes_read: Option<u32>,        // Length of the session, logged with it
+}
+
+/// Storage prefixes for the contract's persistent collections
+#[near(serializers = [borsh])]
+#[derive(BorshStorageKey)]
+enum StorageKey {
+    Libraries,
+    Library { account_hash: [u8; 32] },
+    FollowedAccounts,
+    Followed { account_hash: [u8; 32] },
+    Followers,
+    Follower { account_hash: [u8; 32] },
+    StorageAccounts,
+    Moderators,
+    Pausers,
+    PrivacySettings,
+    NotePublicKeys,
+    FollowApproval,
+    FollowRequests,
+    FollowRequest { account_hash: [u8; 32] },
+    BlockedAccounts,
+    Blocked { account_hash: [u8; 32] },
+    MutedAccounts,
+    Muted { account_hash: [u8; 32] },
+    ActivityLogs,
+    Activity { account_hash: [u8; 32] },
+    ReadingSessions,
+    Sessions { account_hash: [u8; 32] },
+    RatingTallies,
+    Shelves,
+    Shelf { account_hash: [u8; 32] },
+    TagIndexes,
+    Tags { account_hash: [u8; 32] },
+    Catalog,
+    SentFollowRequests,
+    SentFollowRequest { account_hash: [u8; 32] },
+    CatalogBytes,
 }
 
 /// Library contract storing book collections by account
 #[near(contract_state)]
 pub struct Contract {
-    /// Maps account_id to their library of books
-    /// Using HashMap for simplicity, LookupMap for production if > 1000 books
-    libraries: HashMap<AccountId, Vec<BookEntry>>,
+    /// Layout marker, always stored first so `migrate` can identify the layout
+    state_version: StateVersion,
+
+    /// Maps account_id to their library of books, keyed by ISBN.
+    /// Each library lives under its own storage prefix, so a call only
+    /// loads the entries it touches instead of every user's data.
+    libraries: LookupMap<AccountId, IterableMap<String, VersionedBookEntry>>,
+
+    /// Maps account_id to the set of accounts they follow
+    followed_accounts: LookupMap<AccountId, IterableSet<AccountId>>,
+
+    /// Reverse of `followed_accounts`: maps account_id to the accounts following it
+    followers: LookupMap<AccountId, IterableSet<AccountId>>,
+
+    /// Accounts whose new followers need approval
+    follow_approval: LookupSet<AccountId>,
+
+    /// Maps account_id to the accounts waiting for it to approve their follow
+    follow_requests: LookupMap<AccountId, IterableSet<AccountId>>,
+
+    /// Reverse of `follow_requests`: maps account_id to the accounts it asked to follow
+    sent_follow_requests: LookupMap<AccountId, IterableSet<AccountId>>,
+
+    /// Maps account_id to the accounts it has blocked
+    blocked_accounts: LookupMap<AccountId, IterableSet<AccountId>>,
+
+    /// Maps account_id to the followed accounts it has hidden from its feed views
+    muted_accounts: LookupMap<AccountId, IterableSet<AccountId>>,
+
+    /// Each account's most recent activity, for feeds
+    activity: LookupMap<AccountId, ActivityLog>,
+
+    /// Each account's reading session log per book, keyed by ISBN
+    reading_sessions: LookupMap<AccountId, IterableMap<String, Vec<ReadingSession>>>,
 
-    /// Maps account_id to list of accounts they follow
-    followed_accounts: HashMap<AccountId, Vec<AccountId>>,
+    /// Maps ISBN to the ratings every library has given it
+    rating_tallies: LookupMap<String, RatingTally>,
+
+    /// Each account's shelves
+    shelves: LookupMap<AccountId, AccountShelves>,
+
+    /// Maps account_id to the ISBNs in their library carrying each tag
+    tag_index: LookupMap<AccountId, IterableMap<String, Vec<String>>>,
+
+    /// Canonical metadata by ISBN-13, shared by every library
+    catalog: LookupMap<String, VersionedCatalogEntry>,
+
+    /// Bytes of the catalog entries each account added and paid for
+    catalog_bytes: LookupMap<AccountId, u64>,
+
+    /// Library-wide privacy, for accounts that changed it from public
+    privacy_settings: LookupMap<AccountId, PrivacySettings>,
+
+    /// Public keys readers publish so others can share encrypted notes with them
+    note_public_keys: LookupMap<AccountId, Base64VecU8>,
+
+    /// Running count of books across all libraries
+    total_books: u64,
+
+    /// NEP-145 storage deposits and bytes used per account
+    storage_accounts: LookupMap<AccountId, AccountStorage>,
+
+    /// Size limits checked on every write
+    field_limits: FieldLimits,
+
+    /// Owner, admin roles and pause flag
+    access: AccessControl,
 }
 
+/// Without an explicit `new`, the contract account is the owner
 impl Default for Contract {
     fn default() -> Self {
         Self {
-            libraries: HashMap::new(),
-            followed_accounts: HashMap::new(),
+            state_version: StateVersion::current(),
+            libraries: LookupMap::new(StorageKey::Libraries),
+            followed_accounts: LookupMap::new(StorageKey::FollowedAccounts),
+            followers: LookupMap::new(StorageKey::Followers),
+            follow_approval: LookupSet::new(StorageKey::FollowApproval),
+            follow_requests: LookupMap::new(StorageKey::FollowRequests),
+            sent_follow_requests: LookupMap::new(StorageKey::SentFollowRequests),
+            blocked_accounts: LookupMap::new(StorageKey::BlockedAccounts),
+            muted_accounts: LookupMap::new(StorageKey::MutedAccounts),
+            activity: LookupMap::new(StorageKey::ActivityLogs),
+            reading_sessions: LookupMap::new(StorageKey::ReadingSessions),
+            rating_tallies: LookupMap::new(StorageKey::RatingTallies),
+            shelves: LookupMap::new(StorageKey::Shelves),
+            tag_index: LookupMap::new(StorageKey::TagIndexes),
+            catalog: LookupMap::new(StorageKey::Catalog),
+            catalog_bytes: LookupMap::new(StorageKey::CatalogBytes),
+            privacy_settings: LookupMap::new(StorageKey::PrivacySettings),
+            note_public_keys: LookupMap::new(StorageKey::NotePublicKeys),
+            total_books: 0,
+            storage_accounts: LookupMap::new(StorageKey::StorageAccounts),
+            field_limits: FieldLimits::default(),
+            access: AccessControl::new(env::current_account_id()),
         }
     }
 }
 
 #[near]
 impl Contract {
-    /// Add a new book to the caller's library
-    pub fn add_book(&mut self, book: BookEntry) {
+    /// Add a new book to the caller's library. The ISBN is validated and stored as ISBN-13.
+    #[handle_result]
+    pub fn add_book(&mut self, book: BookEntry) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
         let account_id = env::predecessor_account_id();
-
-        // Validate ISBN not already in user's library
-        if let Some(library) = self.libraries.get(&account_id) {
-            if library.iter().any(|b| b.isbn == book.isbn) {
-                env::panic_str("Book with this ISBN already exists in your library");
-            }
+        let initial_storage = env::storage_usage();
+
+        let now = U64(env::block_timestamp());
+        let mut book = BookEntry {
+            isbn: canonical_isbn(&book.isbn)?,
+            added_at: now,
+            started_at: None,
+            completed_at: None,
+            updated_at: now,
+            reading_cycles: Vec::new(),
+            rating: None,
+            review: None,
+            tags: Vec::new(),
+            ..book
+        };
+        book.stamp_status(book.reading_status, now);
+        self.check_against_catalog(&mut book, None)?;
+        // Events carry the stored entry; indexers look up the catalog themselves
+        let added = book.clone();
+        if !self.insert_book(account_id.clone(), book) {
+            return Err(BookyError::DuplicateIsbn);
         }
+        self.record_activity(&account_id, &added.isbn, ActivityKind::BookAdded);
+        emit_book_written(&account_id, None, None, &added, self.library_privacy(&account_id));
 
-        log!("Adding book: {} by {}", book.title, book.author);
-        self.libraries.entry(account_id).or_insert_with(Vec::new).push(book);
+        self.charge_storage(&account_id, initial_storage)
     }
 
-    /// Get all books for a specific account (public read)
-    pub fn get_library(&self, account_id: AccountId) -> Vec<BookEntry> {
-        self.libraries.get(&account_id).cloned().unwrap_or_default()
+    /// Get a page of the books in an account's library that `viewer` may see,
+    /// optionally filtered and sorted
+    pub fn get_library(
+        &self,
+        account_id: AccountId,
+        query: Option<LibraryQuery>,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+        viewer: Option<AccountId>,
+    ) -> Page<BookEntry> {
+        let query = query.unwrap_or_default();
+        self.library_page(&account_id, viewer.as_ref(), &query, from_index, limit, BookEntry::clone)
     }
 
     /// Get a specific book by ISBN from an account's library
-    pub fn get_book(&self, account_id: AccountId, isbn: String) -> Option<BookEntry> {
-        self.libraries
-            .get(&account_id)
-            .and_then(|library| library.iter().find(|b| b.isbn == isbn).cloned())
+    pub fn get_book(&self, account_id: AccountId, isbn: String, viewer: Option<AccountId>) -> Option<BookEntry> {
+        self.visible_book(&account_id, &isbn, viewer.as_ref()).map(Cow::into_owned)
     }
 
     /// Update book details (only owner can modify)
-    pub fn update_book(&mut self, isbn: String, updated_book: BookEntry) {
+    #[handle_result]
+    pub fn update_book(&mut self, isbn: String, updated_book: BookEntry) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
         let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+
+        let mut updated_book = BookEntry { isbn: canonical_isbn(&updated_book.isbn)?, ..updated_book };
+        let library_privacy = self.library_privacy(&account_id);
+        let library = self.libraries.get(&account_id)
+            .ok_or(BookyError::LibraryNotFound)?;
+
+        let isbn = resolve_isbn(library, &isbn)
+            .ok_or(BookyError::BookNotFound)?;
+
+        // Timestamps are the contract's to keep, whatever the caller sent
+        let existing = library.get(&isbn).unwrap().current().into_owned();
+        self.check_against_catalog(&mut updated_book, Some(&existing))?;
+        existing.check_reopen(updated_book.reading_status)?;
+        let now = U64(env::block_timestamp());
+        let mut updated_book = BookEntry {
+            added_at: existing.added_at,
+            started_at: existing.started_at,
+            completed_at: existing.completed_at,
+            updated_at: now,
+            reading_cycles: existing.reading_cycles.clone(),
+            rating: existing.rating,
+            review: existing.review.clone(),
+            tags: existing.tags.clone(),
+            ..updated_book
+        };
+        if updated_book.reading_status != existing.reading_status {
+            updated_book.stamp_status(updated_book.reading_status, now);
+        }
 
-        let library = self.libraries.get_mut(&account_id)
-            .expect("Library not found");
+        // The ISBN is the library key, so a changed ISBN moves the entry
+        let library = self.libraries.get_mut(&account_id).unwrap();
+        if updated_book.isbn != isbn {
+            if library.contains_key(&updated_book.isbn) {
+                return Err(BookyError::DuplicateIsbn);
+            }
+            library.remove(&isbn);
+        }
 
-        let book_index = library.iter().position(|b| b.isbn == isbn)
-            .expect("Book not found in your library");
+        let previous_isbn = Some(isbn.as_str()).filter(|old| *old != updated_book.isbn);
+        emit_book_written(&account_id, previous_isbn, Some(&existing), &updated_book, library_privacy);
+        let (rating, tags) = (updated_book.rating, updated_book.tags.clone());
+        let new_isbn = updated_book.isbn.clone();
+        library.insert(new_isbn.clone(), updated_book.into());
+
+        let mut tally_bytes = 0;
+        if new_isbn != isbn {
+            tally_bytes = self.move_book_records(&account_id, &isbn, Some(&new_isbn), rating, &tags);
+        }
 
-        log!("Updating book: {}", updated_book.title);
-        library[book_index] = updated_book;
+        self.charge_storage(&account_id, initial_storage.saturating_add_signed(tally_bytes))
     }
 
     /// Delete a book from library (only owner can delete)
-    pub fn delete_book(&mut self, isbn: String) {
+    #[handle_result]
+    pub fn delete_book(&mut self, isbn: String) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
         let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let library_privacy = self.library_privacy(&account_id);
 
         let library = self.libraries.get_mut(&account_id)
-            .expect("Library not found");
+            .ok_or(BookyError::LibraryNotFound)?;
+
+        let isbn = resolve_isbn(library, &isbn)
+            .ok_or(BookyError::BookNotFound)?;
+        let entry = library.remove(&isbn).unwrap();
+        let book = entry.current();
+        let (rating, tags) = (book.rating, book.tags.clone());
+        let published = is_published(&book, library_privacy);
+
+        // Drop empty libraries so their storage is refunded too
+        if library.is_empty() {
+            self.libraries.remove(&account_id);
+        }
 
-        let book_index = library.iter().position(|b| b.isbn == isbn)
-            .expect("Book not found in your library");
+        self.total_books -= 1;
+        if published {
+            BookyEvent::BookDeleted { account_id: &account_id, isbn: &isbn }.emit();
+        }
+        let tally_bytes = self.move_book_records(&account_id, &isbn, None, rating, &tags);
 
-        let removed_book = library.remove(book_index);
-        log!("Deleted book: {}", removed_book.title);
+        self.charge_storage(&account_id, initial_storage.saturating_add_signed(tally_bytes))
     }
 
     /// Get total number of books across all libraries
-    pub fn get_total_books(&self) -> u32 {
-        self.libraries.values().map(|lib| lib.len() as u32).sum()
+    pub fn get_total_books(&self) -> U64 {
+        U64(self.total_books)
     }
 
     /// Update reading progress for a book
-    pub fn update_reading_progress(&mut self, isbn: String, progress: ProgressUpdate) {
+    #[handle_result]
+    pub fn update_reading_progress(&mut self, isbn: String, progress: ProgressUpdate) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
         let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let limits = self.field_limits;
+        let library_privacy = self.library_privacy(&account_id);
+        let contents = self.contents(&account_id, &isbn);
+        let book = self.book_mut(&account_id, &isbn)?;
+        limits.check_progress(&progress, &contents)?;
+        if let Some(status) = progress.reading_status {
+            book.check_reopen(status)?;
+        }
+        let from_chapter = book.current_chapter;
 
-        let library = self.libraries.get_mut(&account_id)
-            .expect("Library not found");
-
-        let book = library.iter_mut().find(|b| b.isbn == isbn)
-            .expect("Book not found in your library");
-
-        log!("Updating reading progress for: {}", book.title);
-
-        // Update current chapter if provided
-        if let Some(chapter) = progress.current_chapter {
+        // Update current chapter if provided, or if the new position names one
+        if let Some(chapter) = progress.current_chapter.or(progress.position.and_then(|p| p.chapter())) {
             book.current_chapter = chapter;
-            log!("Current chapter: {}", chapter);
         }
 
         // Add completed chapters to set
-        for chapter_num in progress.chapters_completed {
-            book.chapters_read.insert(chapter_num);
-            log!("Completed chapter: {}", chapter_num);
-        }
+        let newly_read: Vec<u32> = progress
+            .chapters_completed
+            .iter()
+            .copied()
+            .filter(|chapter| book.chapters_read.insert(*chapter))
+            .collect();
 
         // Update last read position
+        if let Some(position) = progress.position {
+            book.position = Some(position);
+            book.last_read_position = position.to_string();
+        }
         if let Some(position) = progress.last_read_position {
-            log!("Last read position: {}", position);
             book.last_read_position = position;
         }
 
@@ -164,218 +543,590 @@ impl Contract {
             book.last_read_date = Some(date);
         }
 
+        emit_progress_updated(&account_id, book, progress.chapters_completed, library_privacy);
+
+        let mut activities = Vec::new();
+        if !newly_read.is_empty() {
+            activities.push(ActivityKind::ChaptersCompleted { chapters: newly_read });
+        }
+
         // Update reading status
         if let Some(status) = progress.reading_status {
-            book.reading_status = status;
-            log!("Reading status changed to: {:?}", status);
+            activities.extend(set_reading_status(&account_id, book, status, library_privacy));
         }
-    }
-
-    /// Add or update a note for a specific chapter
-    pub fn add_chapter_note(&mut self, isbn: String, chapter: u32, note: String) {
-        let account_id = env::predecessor_account_id();
 
-        let library = self.libraries.get_mut(&account_id)
-            .expect("Library not found");
+        let isbn = book.isbn.clone();
+        let session = ReadingSession {
+            timestamp: env::block_timestamp().into(),
+            from_chapter,
+            to_chapter: book.current_chapter,
+            position: book.last_read_position.clone(),
+            minutes: progress.minutes_read,
+            updates: 1,
+        };
+        self.record_session(&account_id, &isbn, session);
+        for kind in activities {
+            self.record_activity(&account_id, &isbn, kind);
+        }
 
-        let book = library.iter_mut().find(|b| b.isbn == isbn)
-            .expect("Book not found in your library");
+        self.charge_storage(&account_id, initial_storage)
+    }
 
-        // Validate chapter number
-        if let Some(total) = book.total_chapters {
-            if chapter > total {
-                env::panic_str(&format!("Chapter number {} exceeds total chapters {}", chapter, total));
+    /// Add or update a note for a specific chapter, as plain text or an encrypted envelope
+    #[handle_result]
+    pub fn add_chapter_note(&mut self, isbn: String, chapter: u32, note: NoteContent) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let limits = self.field_limits;
+        let library_privacy = self.library_privacy(&account_id);
+        let contents = self.contents(&account_id, &isbn);
+        let book = self.book_mut(&account_id, &isbn)?;
+
+        limits.check_chapter(chapter, &contents)?;
+        limits.check_note(&note)?;
+
+        let settings = library_privacy.restrict(book.privacy);
+        if settings.allows(Audience::Public) {
+            BookyEvent::NoteAdded {
+                account_id: &account_id,
+                isbn: &book.isbn,
+                chapter,
+                note: Some(&note).filter(|_| !settings.hide_notes),
             }
-        } else if chapter == 0 {
-            env::panic_str("Chapter number must be at least 1");
+            .emit();
         }
-
         book.chapter_notes.insert(chapter, note);
-        log!("Added/updated note for chapter {} of {}", chapter, book.title);
+        let isbn = book.isbn.clone();
+        self.record_activity(&account_id, &isbn, ActivityKind::NoteAdded { chapter });
+
+        self.charge_storage(&account_id, initial_storage)
     }
 
     /// Get a note for a specific chapter
-    pub fn get_chapter_note(&self, account_id: AccountId, isbn: String, chapter: u32) -> Option<String> {
-        self.libraries
-            .get(&account_id)
-            .and_then(|library| {
-                library.iter()
-                    .find(|b| b.isbn == isbn)
-                    .and_then(|book| book.chapter_notes.get(&chapter).cloned())
-            })
+    pub fn get_chapter_note(
+        &self,
+        account_id: AccountId,
+        isbn: String,
+        chapter: u32,
+        viewer: Option<AccountId>,
+    ) -> Option<NoteContent> {
+        self.visible_book(&account_id, &isbn, viewer.as_ref())
+            .and_then(|book| book.chapter_notes.get(&chapter).cloned())
     }
 
     /// Get all chapter notes for a book
-    pub fn get_all_chapter_notes(&self, account_id: AccountId, isbn: String) -> HashMap<u32, String> {
-        self.libraries
-            .get(&account_id)
-            .and_then(|library| {
-                library.iter()
-                    .find(|b| b.isbn == isbn)
-                    .map(|book| book.chapter_notes.clone())
-            })
+    pub fn get_all_chapter_notes(
+        &self,
+        account_id: AccountId,
+        isbn: String,
+        viewer: Option<AccountId>,
+    ) -> HashMap<u32, NoteContent> {
+        self.visible_book(&account_id, &isbn, viewer.as_ref())
+            .map(|book| book.into_owned().chapter_notes)
             .unwrap_or_default()
     }
 
     /// Delete a note for a specific chapter
-    pub fn delete_chapter_note(&mut self, isbn: String, chapter: u32) {
+    #[handle_result]
+    pub fn delete_chapter_note(&mut self, isbn: String, chapter: u32) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
         let account_id = env::predecessor_account_id();
-
-        let library = self.libraries.get_mut(&account_id)
-            .expect("Library not found");
-
-        let book = library.iter_mut().find(|b| b.isbn == isbn)
-            .expect("Book not found in your library");
+        let initial_storage = env::storage_usage();
+        let library_privacy = self.library_privacy(&account_id);
+        let book = self.book_mut(&account_id, &isbn)?;
 
         if book.chapter_notes.remove(&chapter).is_some() {
-            log!("Deleted note for chapter {} of {}", chapter, book.title);
+            if is_published(book, library_privacy) {
+                BookyEvent::NoteDeleted { account_id: &account_id, isbn: &book.isbn, chapter }.emit();
+            }
+            let isbn = book.isbn.clone();
+            let is_deleted_note =
+                |activity: &Activity| activity.isbn == isbn && activity.kind == ActivityKind::NoteAdded { chapter };
+            self.rewrite_activity(&account_id, is_deleted_note, |activity| {
+                Some(activity).filter(|activity| !is_deleted_note(activity))
+            });
         } else {
             log!("No note found for chapter {} - nothing to delete", chapter);
         }
-    }
 
-    /// Get reading statistics for an account's library
-    pub fn get_reading_stats(&self, account_id: AccountId) -> ReadingStats {
-        let library = self.libraries.get(&account_id).cloned().unwrap_or_default();
-
-        let total_books = library.len();
-        let reading = library.iter().filter(|b| b.reading_status == ReadingStatus::Reading).count();
-        let completed = library.iter().filter(|b| b.reading_status == ReadingStatus::Completed).count();
-        let to_read = library.iter().filter(|b| b.reading_status == ReadingStatus::ToRead).count();
+        self.charge_storage(&account_id, initial_storage)
+    }
 
-        ReadingStats {
-            total_books: total_books as u32,
-            currently_reading: reading as u32,
-            completed: completed as u32,
-            to_read: to_read as u32,
-            on_hold: (total_books - reading - completed - to_read) as u32,
-        }
+    /// Get reading statistics over the books in an account's library that `viewer` may see
+    pub fn get_reading_stats(&self, account_id: AccountId, viewer: Option<AccountId>) -> ReadingStats {
+        self.compute_stats(&account_id, viewer.as_ref())
     }
 
-    /// Get books currently being read
-    pub fn get_currently_reading(&self, account_id: AccountId) -> Vec<BookEntry> {
-        self.get_library(account_id)
-            .into_iter()
-            .filter(|b| b.reading_status == ReadingStatus::Reading)
-            .collect()
+    /// Get a page of books currently being read
+    pub fn get_currently_reading(
+        &self,
+        account_id: AccountId,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+        viewer: Option<AccountId>,
+    ) -> Page<BookEntry> {
+        let query = LibraryQuery {
+            reading_status: Some(ReadingStatus::Reading),
+            ..Default::default()
+        };
+        self.library_page(&account_id, viewer.as_ref(), &query, from_index, limit, BookEntry::clone)
     }
 
     /// Mark book as completed
-    pub fn mark_completed(&mut self, isbn: String) {
+    #[handle_result]
+    pub fn mark_completed(&mut self, isbn: String) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
         let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let library_privacy = self.library_privacy(&account_id);
+        let contents = self.contents(&account_id, &isbn);
+        let book = self.book_mut(&account_id, &isbn)?;
 
-        let library = self.libraries.get_mut(&account_id)
-            .expect("Library not found");
-
-        let book = library.iter_mut().find(|b| b.isbn == isbn)
-            .expect("Book not found in your library");
+        book.last_read_date = Some(iso_date_from_timestamp(env::block_timestamp()));
 
-        book.reading_status = ReadingStatus::Completed;
-        book.last_read_date = Some(env::block_timestamp().to_string()); // Use current date in real implementation
-        log!("Marked {} as completed", book.title);
+        // Mark every section as read if the table of contents or chapter count is known
+        let chapters_completed: Vec<u32> =
+            contents.chapter_ids().into_iter().filter(|chapter| book.chapters_read.insert(*chapter)).collect();
 
-        // Mark all chapters as read if total_chapters is set
-        if let Some(total) = book.total_chapters {
-            for i in 1..=total {
-                book.chapters_read.insert(i);
-            }
-            log!("Marked all {} chapters as completed", total);
+        emit_progress_updated(&account_id, book, chapters_completed, library_privacy);
+        if let Some(kind) = set_reading_status(&account_id, book, ReadingStatus::Completed, library_privacy) {
+            let isbn = book.isbn.clone();
+            self.record_activity(&account_id, &isbn, kind);
         }
+
+        self.charge_storage(&account_id, initial_storage)
     }
 
     /// Mark book as currently reading
-    pub fn start_reading(&mut self, isbn: String, starting_chapter: Option<u32>) {
+    #[handle_result]
+    pub fn start_reading(&mut self, isbn: String, starting_chapter: Option<u32>) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
         let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let limits = self.field_limits;
+        let library_privacy = self.library_privacy(&account_id);
+        let contents = self.contents(&account_id, &isbn);
+        let book = self.book_mut(&account_id, &isbn)?;
+        book.check_reopen(ReadingStatus::Reading)?;
+
+        let starting_chapter = starting_chapter.unwrap_or_else(|| contents.first_chapter());
+        limits.check_chapter(starting_chapter, &contents)?;
+        book.current_chapter = starting_chapter;
+
+        emit_progress_updated(&account_id, book, Vec::new(), library_privacy);
+        if let Some(kind) = set_reading_status(&account_id, book, ReadingStatus::Reading, library_privacy) {
+            let isbn = book.isbn.clone();
+            self.record_activity(&account_id, &isbn, kind);
+        }
 
-        let library = self.libraries.get_mut(&account_id)
-            .expect("Library not found");
-
-        let book = library.iter_mut().find(|b| b.isbn == isbn)
-            .expect("Book not found in your library");
-
-        book.reading_status = ReadingStatus::Reading;
-        book.current_chapter = starting_chapter.unwrap_or(1);
-        log!("Started reading {} from chapter {}", book.title, book.current_chapter);
+        self.charge_storage(&account_id, initial_storage)
     }
 
-    /// Follow another account to track their library
-    pub fn follow_account(&mut self, account_id_to_follow: AccountId) {
+    /// Follow another account to track their library. If the account approves
+    /// its followers, this sends a follow request instead.
+    #[handle_result]
+    pub fn follow_account(&mut self, account_id_to_follow: AccountId) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
         let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
 
         // Prevent self-follow
         if account_id == account_id_to_follow {
-            panic!("Cannot follow yourself");
+            return Err(BookyError::SelfFollow);
+        }
+        if self.has_blocked(&account_id_to_follow, &account_id) {
+            return Err(BookyError::BlockedBy(account_id_to_follow));
         }
 
-        // Get or create followed list for this account
-        let followed = self.followed_accounts.entry(account_id.clone()).or_default();
+        if self.follow_approval.contains(&account_id_to_follow) && !self.is_following_account(&account_id, &account_id_to_follow) {
+            if !self.insert_follow_request(account_id.clone(), &account_id_to_follow) {
+                log!("Already requested to follow {}", account_id_to_follow);
+                return Ok(());
+            }
+            BookyEvent::FollowRequested {
+                account_id: &account_id,
+                target_account_id: &account_id_to_follow,
+            }
+            .emit();
+            return self.charge_storage(&account_id, initial_storage);
+        }
 
         // Check if already following
-        if followed.contains(&account_id_to_follow) {
+        if !self.insert_follow(account_id.clone(), account_id_to_follow.clone()) {
             log!("Already following {}", account_id_to_follow);
-            return;
+            return Ok(());
         }
 
-        followed.push(account_id_to_follow.clone());
-        log!("Now following {}", account_id_to_follow);
+        BookyEvent::Followed {
+            account_id: &account_id,
+            followed_account_id: &account_id_to_follow,
+        }
+        .emit();
+
+        self.charge_storage(&account_id, initial_storage)
     }
 
     /// Unfollow an account
-    pub fn unfollow_account(&mut self, account_id_to_unfollow: AccountId) {
+    #[handle_result]
+    pub fn unfollow_account(&mut self, account_id_to_unfollow: AccountId) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
         let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
 
-        let followed = self.followed_accounts.get_mut(&account_id)
-            .expect("You don't have any followed accounts");
-
-        // Remove account_id_to_unfollow if present
-        let original_len = followed.len();
-        followed.retain(|id| id != &account_id_to_unfollow);
+        if !self.remove_follow(&account_id, &account_id_to_unfollow) {
+            return Err(BookyError::NotFollowing(account_id_to_unfollow));
+        }
 
-        if followed.len() < original_len {
-            log!("Unfollowed {}", account_id_to_unfollow);
-        } else {
-            log!("Not following {}", account_id_to_unfollow);
+        BookyEvent::Unfollowed {
+            account_id: &account_id,
+            unfollowed_account_id: &account_id_to_unfollow,
         }
+        .emit();
+
+        self.charge_storage(&account_id, initial_storage)
     }
 
-    /// Get list of accounts that a user follows
-    pub fn get_followed_accounts(&self, account_id: AccountId) -> Vec<AccountId> {
+    /// Get a page of accounts that a user follows
+    pub fn get_followed_accounts(
+        &self,
+        account_id: AccountId,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+    ) -> Page<AccountId> {
         match self.followed_accounts.get(&account_id) {
-            Some(followed) => followed.clone(),
-            None => Vec::new(),
+            Some(followed) => paginate(followed.iter().cloned(), followed.len(), from_index, limit),
+            None => paginate(std::iter::empty(), 0, from_index, limit),
         }
     }
 
-    /// Get another user's library (view-only access)
-    pub fn get_user_library(&self, account_id: AccountId) -> Vec<BookEntry> {
-        match self.libraries.get(&account_id) {
-            Some(library) => library.clone(),
-            None => Vec::new(),
+    /// Get a page of accounts that follow a user
+    pub fn get_followers(
+        &self,
+        account_id: AccountId,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+    ) -> Page<AccountId> {
+        match self.followers.get(&account_id) {
+            Some(followers) => paginate(followers.iter().cloned(), followers.len(), from_index, limit),
+            None => paginate(std::iter::empty(), 0, from_index, limit),
         }
     }
 
+    /// Number of accounts following a user
+    pub fn get_follower_count(&self, account_id: AccountId) -> u32 {
+        self.followers.get(&account_id).map_or(0, |followers| followers.len())
+    }
+
+    /// Whether `account_id` follows `followed_account_id`
+    pub fn is_following(&self, account_id: AccountId, followed_account_id: AccountId) -> bool {
+        self.is_following_account(&account_id, &followed_account_id)
+    }
+
+    /// Get a page of another user's library (view-only access)
+    pub fn get_user_library(
+        &self,
+        account_id: AccountId,
+        query: Option<LibraryQuery>,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+        viewer: Option<AccountId>,
+    ) -> Page<BookEntry> {
+        self.get_library(account_id, query, from_index, limit, viewer)
+    }
+
     /// Get another user's reading stats
-    pub fn get_user_stats(&self, account_id: AccountId) -> ReadingStats {
-        let library = match self.libraries.get(&account_id) {
-            Some(lib) => lib,
-            None => return ReadingStats {
-                total_books: 0,
-                currently_reading: 0,
-                completed: 0,
-                to_read: 0,
-                on_hold: 0,
+    pub fn get_user_stats(&self, account_id: AccountId, viewer: Option<AccountId>) -> ReadingStats {
+        self.compute_stats(&account_id, viewer.as_ref())
+    }
+
+    /// Get a page of followed accounts with their libraries and stats in a single call
+    /// This is more efficient than calling get_followed_accounts + get_user_library + get_user_stats separately.
+    /// Muted accounts are left out.
+    /// Each library holds its first `books_limit` books; fetch the rest with get_user_library.
+    pub fn get_followed_accounts_with_details(
+        &self,
+        account_id: AccountId,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+        books_limit: Option<u32>,
+        viewer: Option<AccountId>,
+    ) -> Page<FollowedAccountDetails> {
+        let followed = self.feed_accounts(&account_id, from_index, limit);
+        let query = LibraryQuery::default();
+
+        Page {
+            items: followed
+                .items
+                .into_iter()
+                .map(|followed_id| FollowedAccountDetails {
+                    library: self.library_page(&followed_id, viewer.as_ref(), &query, None, books_limit, BookEntry::clone),
+                    stats: self.compute_stats(&followed_id, viewer.as_ref()),
+                    account_id: followed_id,
+                })
+                .collect(),
+            total: followed.total,
+            next_index: followed.next_index,
+        }
+    }
+
+    /// Get a page of lightweight book summaries, without progress sets or notes
+    pub fn get_library_summaries(
+        &self,
+        account_id: AccountId,
+        query: Option<LibraryQuery>,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+        viewer: Option<AccountId>,
+    ) -> Page<BookSummary> {
+        let query = query.unwrap_or_default();
+        self.library_page(&account_id, viewer.as_ref(), &query, from_index, limit, BookEntry::summary)
+    }
+
+    /// Get a page of summaries of books currently being read
+    pub fn get_currently_reading_summaries(
+        &self,
+        account_id: AccountId,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+        viewer: Option<AccountId>,
+    ) -> Page<BookSummary> {
+        let query = LibraryQuery {
+            reading_status: Some(ReadingStatus::Reading),
+            ..Default::default()
+        };
+        self.library_page(&account_id, viewer.as_ref(), &query, from_index, limit, BookEntry::summary)
+    }
+
+    /// Same as get_followed_accounts_with_details, with book summaries instead of full entries
+    pub fn get_followed_accounts_with_summaries(
+        &self,
+        account_id: AccountId,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+        books_limit: Option<u32>,
+        viewer: Option<AccountId>,
+    ) -> Page<FollowedAccountSummary> {
+        let followed = self.feed_accounts(&account_id, from_index, limit);
+        let query = LibraryQuery::default();
+
+        Page {
+            items: followed
+                .items
+                .into_iter()
+                .map(|followed_id| FollowedAccountSummary {
+                    books: self.library_page(&followed_id, viewer.as_ref(), &query, None, books_limit, BookEntry::summary),
+                    stats: self.compute_stats(&followed_id, viewer.as_ref()),
+                    account_id: followed_id,
+                })
+                .collect(),
+            total: followed.total,
+            next_index: followed.next_index,
+        }
+    }
+}
+
+impl Contract {
+    /// Insert a book into an account's library, creating the library on first use.
+    /// Returns false if the library already holds a book with this ISBN.
+    fn insert_book(&mut self, account_id: AccountId, book: BookEntry) -> bool {
+        let library = self.libraries.entry(account_id.clone()).or_insert_with(|| {
+            IterableMap::new(StorageKey::Library {
+                account_hash: env::sha256_array(account_id.as_bytes()),
+            })
+        });
+
+        if resolve_isbn(library, &book.isbn).is_some() {
+            return false;
+        }
+
+        library.insert(book.isbn.clone(), book.into());
+        self.total_books += 1;
+        true
+    }
+
+    /// Add an account to another account's followed set, and the reverse entry to its followers.
+    /// Returns false if it was already being followed.
+    fn insert_follow(&mut self, account_id: AccountId, account_id_to_follow: AccountId) -> bool {
+        let inserted = self.followed_accounts
+            .entry(account_id.clone())
+            .or_insert_with(|| {
+                IterableSet::new(StorageKey::Followed {
+                    account_hash: env::sha256_array(account_id.as_bytes()),
+                })
+            })
+            .insert(account_id_to_follow.clone());
+        if !inserted {
+            return false;
+        }
+
+        let followers = self.followers.entry(account_id_to_follow.clone()).or_insert_with(|| {
+            IterableSet::new(StorageKey::Follower {
+                account_hash: env::sha256_array(account_id_to_follow.as_bytes()),
+            })
+        });
+        followers.insert(account_id);
+        // Write it now so the storage delta charged to the follower includes it
+        followers.flush();
+        true
+    }
+
+    /// Remove a follow in both directions, dropping sets that become empty.
+    /// Returns false if `account_id` wasn't following `followed_account_id`.
+    fn remove_follow(&mut self, account_id: &AccountId, followed_account_id: &AccountId) -> bool {
+        let followed = match self.followed_accounts.get_mut(account_id) {
+            Some(followed) => followed,
+            None => return false,
+        };
+        if !followed.remove(followed_account_id) {
+            return false;
+        }
+        if followed.is_empty() {
+            self.followed_accounts.remove(account_id);
+        }
+
+        if let Some(followers) = self.followers.get_mut(followed_account_id) {
+            followers.remove(account_id);
+            if followers.is_empty() {
+                self.followers.remove(followed_account_id);
+            } else {
+                followers.flush();
+            }
+        }
+        true
+    }
+
+    fn is_following_account(&self, account_id: &AccountId, followed_account_id: &AccountId) -> bool {
+        self.followed_accounts
+            .get(account_id)
+            .is_some_and(|followed| followed.contains(followed_account_id))
+    }
+
+    /// Look up a book in an account's library by any form of its ISBN, with the
+    /// catalog's metadata filled in
+    fn find_book(&self, account_id: &AccountId, isbn: &str) -> Option<Cow<'_, BookEntry>> {
+        let library = self.libraries.get(account_id)?;
+        let entry = library.get(&resolve_isbn(library, isbn)?)?;
+        Some(self.with_catalog(entry.current()))
+    }
+
+    /// Move what is kept about a book outside its entry (activity, sessions, its
+    /// rating in the tallies, tags and shelf places) to `new_isbn`, or drop it all.
+    /// Returns the bytes the rating tallies grew by, which the contract pays for.
+    pub(crate) fn move_book_records(
+        &mut self,
+        account_id: &AccountId,
+        isbn: &str,
+        new_isbn: Option<&str>,
+        rating: Option<u8>,
+        tags: &[String],
+    ) -> i64 {
+        self.rewrite_activity(
+            account_id,
+            |activity| activity.isbn == isbn,
+            |activity| match new_isbn {
+                _ if activity.isbn != isbn => Some(activity),
+                Some(new_isbn) => Some(Activity { isbn: new_isbn.to_string(), ..activity }),
+                None => None,
             },
+        );
+        self.move_sessions(account_id, isbn, new_isbn);
+        self.reshelve(account_id, isbn, new_isbn);
+        // Tallies are keyed by canonical ISBN, so re-keying a legacy entry leaves its rating in place
+        let moves_rating = new_isbn.map(tally_key) != Some(tally_key(isbn));
+        let mut tally_bytes = 0;
+        if moves_rating {
+            tally_bytes += self.tally_rating(isbn, rating, None);
+        }
+        self.index_tags(account_id, isbn, tags, &[]);
+        if let Some(new_isbn) = new_isbn {
+            if moves_rating {
+                tally_bytes += self.tally_rating(new_isbn, None, rating);
+            }
+            self.index_tags(account_id, new_isbn, &[], tags);
+        }
+        tally_bytes
+    }
+
+    /// Look up a book as `viewer` may see it
+    fn visible_book(&self, account_id: &AccountId, isbn: &str, viewer: Option<&AccountId>) -> Option<Cow<'_, BookEntry>> {
+        let book = self.find_book(account_id, isbn)?;
+        self.library_privacy(account_id).view(book, self.audience(account_id, viewer))
+    }
+
+    /// Look up a book in an account's library for mutation, stamping it as updated
+    fn book_mut(&mut self, account_id: &AccountId, isbn: &str) -> Result<&mut BookEntry, BookyError> {
+        let library = self.libraries
+            .get_mut(account_id)
+            .ok_or(BookyError::LibraryNotFound)?;
+        let isbn = resolve_isbn(library, isbn).ok_or(BookyError::BookNotFound)?;
+        let book = library.get_mut(&isbn).unwrap().current_mut();
+        book.updated_at = U64(env::block_timestamp());
+        Ok(book)
+    }
+
+    /// Apply a library query to the books in an account's library that `viewer`
+    /// may see and return the requested page.
+    /// Each book on the page is converted with `project`, so callers can
+    /// return full entries or lighter projections without cloning the rest
+    fn library_page<T>(
+        &self,
+        account_id: &AccountId,
+        viewer: Option<&AccountId>,
+        query: &LibraryQuery,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+        project: fn(&BookEntry) -> T,
+    ) -> Page<T> {
+        let audience = self.audience(account_id, viewer);
+        let privacy = self.library_privacy(account_id);
+        let library = match self.libraries.get(account_id) {
+            Some(library) if privacy.allows(audience) => library,
+            _ => return paginate(std::iter::empty(), 0, from_index, limit),
         };
 
+        // The owner sees every book, so without filters or sorting page straight through storage order
+        if audience == Audience::Owner && query.is_unfiltered() {
+            let books = library.values().map(|entry| project(&self.with_catalog(entry.current())));
+            return paginate(books, library.len(), from_index, limit);
+        }
+
+        let matching: Vec<Cow<BookEntry>> = library
+            .values()
+            .filter_map(|entry| privacy.view(self.with_catalog(entry.current()), audience))
+            .filter(|b| query.matches(b))
+            .collect();
+        let mut books: Vec<&BookEntry> = matching.iter().map(|b| b.as_ref()).collect();
+        query.sort(&mut books);
+        let total = books.len() as u32;
+        paginate(books.into_iter().map(project), total, from_index, limit)
+    }
+
+    /// Tally reading statuses over the books in an account's library that `viewer` may see
+    fn compute_stats(&self, account_id: &AccountId, viewer: Option<&AccountId>) -> ReadingStats {
         let mut stats = ReadingStats {
-            total_books: library.len() as u32,
+            total_books: 0,
             currently_reading: 0,
             completed: 0,
             to_read: 0,
             on_hold: 0,
+            total_reads: 0,
+            unique_books_read: 0,
+            average_progress_percent: None,
+            by_format: Vec::new(),
+        };
+        let mut formats = FormatTally::new();
+        let mut progress = (0u64, 0u64);
+
+        let audience = self.audience(account_id, viewer);
+        let privacy = self.library_privacy(account_id);
+        let library = match self.libraries.get(account_id) {
+            Some(lib) if privacy.allows(audience) => lib,
+            _ => return stats,
         };
 
-        for book in library {
+        for book in library.values().filter_map(|entry| privacy.view(self.with_catalog(entry.current()), audience)) {
+            stats.total_books += 1;
             match book.reading_status {
                 ReadingStatus::Reading => stats.currently_reading += 1,
                 ReadingStatus::Completed => stats.completed += 1,
@@ -383,54 +1134,176 @@ impl Contract {
                 ReadingStatus::OnHold => stats.on_hold += 1,
                 ReadingStatus::Abandoned => {}
             }
+            let reads = book.completed_reads();
+            stats.total_reads += reads;
+            stats.unique_books_read += (reads > 0) as u32;
+            formats.add(&book);
+            if let Some(percent) = book.progress_percent().filter(|_| book.reading_status == ReadingStatus::Reading) {
+                progress = (progress.0 + percent as u64, progress.1 + 1);
+            }
         }
+        stats.average_progress_percent = (progress.1 > 0).then(|| (progress.0 / progress.1) as u8);
+        stats.by_format = formats.into_stats();
 
         stats
     }
+}
 
-    /// Get followed accounts with their libraries and stats in a single call
-    /// This is more efficient than calling get_followed_accounts + get_user_library + get_user_stats separately
-    pub fn get_followed_accounts_with_details(&self, account_id: AccountId) -> Vec<FollowedAccountDetails> {
-        let followed_ids = match self.followed_accounts.get(&account_id) {
-            Some(followed) => followed.clone(),
-            None => return Vec::new(),
-        };
+impl BookEntry {
+    /// Record when the book entered `status`. Resuming keeps the original start.
+    fn stamp_status(&mut self, status: ReadingStatus, now: U64) {
+        match status {
+            ReadingStatus::Reading => {
+                self.started_at.get_or_insert(now);
+            }
+            ReadingStatus::Completed => self.completed_at = Some(now),
+            _ => {}
+        }
+    }
+
+    /// Project the entry down to the fields list views need
+    pub fn summary(&self) -> BookSummary {
+        BookSummary {
+            isbn: self.isbn.clone(),
+            title: self.title.clone(),
+            author: self.author.clone(),
+            reading_status: self.reading_status,
+            progress_percent: self.progress_percent(),
+            last_read_date: self.last_read_date.clone(),
+            note_count: self.chapter_notes.len() as u32,
+            rating: self.rating,
+            format: self.format,
+        }
+    }
+}
+
+/// Validate an ISBN argument and return its canonical ISBN-13
+fn canonical_isbn(isbn: &str) -> Result<String, BookyError> {
+    normalize_isbn(isbn).map_err(BookyError::InvalidIsbn)
+}
+
+/// Find the key a book is stored under: the exact string given, which matches
+/// entries stored before ISBNs were normalized, or else its canonical ISBN-13
+fn resolve_isbn(library: &IterableMap<String, VersionedBookEntry>, isbn: &str) -> Option<String> {
+    if library.contains_key(isbn) {
+        return Some(isbn.to_string());
+    }
+    normalize_isbn(isbn).ok().filter(|canonical| library.contains_key(canonical))
+}
+
+/// Emit `book_added`, or `book_updated` when there was an `old` entry, with the fields
+/// that changed, then a `note_added` or `note_deleted` per note the public now sees
+/// differently. Whatever the public may not see is withheld, and nothing is emitted
+/// about a book that was public neither before nor after.
+fn emit_book_written(
+    account_id: &AccountId,
+    previous_isbn: Option<&str>,
+    old: Option<&BookEntry>,
+    book: &BookEntry,
+    library_privacy: PrivacySettings,
+) {
+    let previously = old.and_then(|old| library_privacy.view(Cow::Borrowed(old), Audience::Public));
+    let published = library_privacy.view(Cow::Borrowed(book), Audience::Public);
+    if previously.is_none() && published.is_none() {
+        return;
+    }
 
-        followed_ids
+    let mut notes = Vec::new();
+    if let Some(published) = &published {
+        let old_notes = previously.as_ref().map(|old| &old.chapter_notes);
+        let mut written: Vec<_> = published
+            .chapter_notes
+            .iter()
+            .filter(|(chapter, note)| old_notes.and_then(|notes| notes.get(chapter)) != Some(*note))
+            .collect();
+        written.sort_unstable_by_key(|(chapter, _)| **chapter);
+        notes.extend(written.into_iter().map(|(&chapter, note)| BookyEvent::NoteAdded {
+            account_id,
+            isbn: &book.isbn,
+            chapter,
+            note: Some(note),
+        }));
+        let mut deleted: Vec<u32> = old_notes
             .into_iter()
-            .map(|followed_id| {
-                let library = self
-                    .libraries
-                    .get(&followed_id)
-                    .cloned()
-                    .unwrap_or_default();
-
-                let mut stats = ReadingStats {
-                    total_books: library.len() as u32,
-                    currently_reading: 0,
-                    completed: 0,
-                    to_read: 0,
-                    on_hold: 0,
-                };
-
-                for book in &library {
-                    match book.reading_status {
-                        ReadingStatus::Reading => stats.currently_reading += 1,
-                        ReadingStatus::Completed => stats.completed += 1,
-                        ReadingStatus::ToRead => stats.to_read += 1,
-                        ReadingStatus::OnHold => stats.on_hold += 1,
-                        ReadingStatus::Abandoned => {}
-                    }
-                }
+            .flat_map(|notes| notes.keys())
+            .filter(|chapter| !published.chapter_notes.contains_key(chapter))
+            .copied()
+            .collect();
+        deleted.sort_unstable();
+        notes.extend(deleted.into_iter().map(|chapter| BookyEvent::NoteDeleted {
+            account_id,
+            isbn: &book.isbn,
+            chapter,
+        }));
+    }
 
-                FollowedAccountDetails {
-                    account_id: followed_id,
-                    library,
-                    stats,
-                }
-            })
-            .collect()
+    let book_event = |fields: Option<BookFields>| match (old, fields) {
+        (None, Some(fields)) => BookyEvent::BookAdded { account_id, isbn: &book.isbn, book: fields },
+        (_, fields) => BookyEvent::BookUpdated { account_id, isbn: &book.isbn, previous_isbn, book: fields },
+    };
+    let fields = || published.as_deref().map(|book| BookFields::changed(previously.as_deref(), book));
+    let mut event = book_event(fields());
+    let size = |event: &BookyEvent| event.to_json().to_string().len();
+    if size(&event) + notes.iter().map(size).sum::<usize>() > MAX_BOOK_EVENT_BYTES {
+        event = book_event(fields().map(BookFields::without_content));
+        notes.clear();
     }
+    event.emit();
+    notes.iter().for_each(BookyEvent::emit);
+}
+
+/// Whether events about `book` may be published
+pub(crate) fn is_published(book: &BookEntry, library_privacy: PrivacySettings) -> bool {
+    library_privacy.restrict(book.privacy).allows(Audience::Public)
+}
+
+/// Emit the progress fields of a book after an update
+fn emit_progress_updated(
+    account_id: &AccountId,
+    book: &BookEntry,
+    chapters_completed: Vec<u32>,
+    library_privacy: PrivacySettings,
+) {
+    if !is_published(book, library_privacy) {
+        return;
+    }
+    BookyEvent::ProgressUpdated {
+        account_id,
+        isbn: &book.isbn,
+        current_chapter: book.current_chapter,
+        chapters_completed,
+        last_read_position: &book.last_read_position,
+        position: book.position.as_ref(),
+        last_read_date: book.last_read_date.as_deref(),
+    }
+    .emit();
+}
+
+/// Change a book's reading status, emitting an event if it actually changed.
+/// Returns the activity to record for the change, if any.
+pub(crate) fn set_reading_status(
+    account_id: &AccountId,
+    book: &mut BookEntry,
+    status: ReadingStatus,
+    library_privacy: PrivacySettings,
+) -> Option<ActivityKind> {
+    if book.reading_status == status {
+        return None;
+    }
+
+    if is_published(book, library_privacy) {
+        BookyEvent::StatusChanged {
+            account_id,
+            isbn: &book.isbn,
+            old_status: book.reading_status,
+            new_status: status,
+        }
+        .emit();
+    }
+    let activity = status_activity(book.reading_status, status);
+    book.reading_status = status;
+    book.stamp_status(status, U64(env::block_timestamp()));
+    activity
 }
 
 /// Reading statistics for an account's library
@@ -441,13 +1314,43 @@ pub struct ReadingStats {
     pub completed: u32,
     pub to_read: u32,
     pub on_hold: u32,
+    /// Completed reads, counting each re-read
+    pub total_reads: u32,
+    /// Books completed at least once
+    pub unique_books_read: u32,
+    /// Average progress of the books being read whose progress is known
+    pub average_progress_percent: Option<u8>,
+    /// Books and owned copies per format, for formats that have any
+    pub by_format: Vec<FormatStats>,
 }
 
 /// Detailed information about a followed account including their library and stats
 #[near(serializers = [json, borsh])]
 pub struct FollowedAccountDetails {
     pub account_id: AccountId,
-    pub library: Vec<BookEntry>,
+    pub library: Page<BookEntry>,
+    pub stats: ReadingStats,
+}
+
+/// Compact view of a book for list and grid views
+#[near(serializers = [json, borsh])]
+pub struct BookSummary {
+    pub isbn: String,
+    pub title: String,
+    pub author: String,
+    pub reading_status: ReadingStatus,
+    pub progress_percent: Option<u8>,
+    pub last_read_date: Option<String>,
+    pub note_count: u32,
+    pub rating: Option<u8>,
+    pub format: BookFormat,
+}
+
+/// A followed account with summaries of their books and their stats
+#[near(serializers = [json, borsh])]
+pub struct FollowedAccountSummary {
+    pub account_id: AccountId,
+    pub books: Page<BookSummary>,
     pub stats: ReadingStats,
 }
 
@@ -458,16 +1361,33 @@ pub struct FollowedAccountDetails {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use near_sdk::testing_env;
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::mock::MockAction;
+    use near_sdk::{testing_env, NearToken};
 
     fn get_context(predecessor: AccountId) -> near_sdk::test_utils::VMContextBuilder {
         let mut builder = near_sdk::test_utils::VMContextBuilder::new();
         builder
             .predecessor_account_id(predecessor)
-            .attached_deposit(near_sdk::NearToken::from_yoctonear(0));
+            .attached_deposit(NearToken::from_yoctonear(0));
         builder
     }
 
+    /// Fresh contract owned by owner.testnet with alice and bob registered for storage,
+    /// keeping the current caller
+    fn setup_contract() -> Contract {
+        let caller = env::predecessor_account_id();
+        let mut contract = Contract::new("owner.testnet".parse().unwrap());
+        for account in ["alice.testnet", "bob.testnet"] {
+            let mut context = get_context(account.parse().unwrap());
+            context.attached_deposit(NearToken::from_near(1));
+            testing_env!(context.build());
+            contract.storage_deposit(None, None).unwrap();
+        }
+        testing_env!(get_context(caller).build());
+        contract
+    }
+
     fn create_sample_book() -> BookEntry {
         BookEntry {
             isbn: "978-0451524935".to_string(),
@@ -475,15 +1395,30 @@ mod tests {
             author: "George Orwell".to_string(),
             acquisition_date: "2024-01-15".to_string(),
             condition: "Good".to_string(),
-            personal_comments: "Still relevant today".to_string(),
+            format: BookFormat::Paperback,
+            copies: Vec::new(),
+            personal_comments: "Still relevant today".into(),
             media_hash: None,
+            rating: None,
+            review: None,
+            tags: Vec::new(),
             reading_status: ReadingStatus::ToRead,
             current_chapter: 0,
             total_chapters: Some(10),
+            sections: Vec::new(),
+            total_pages: None,
             chapters_read: HashSet::new(),
             last_read_position: "Not started".to_string(),
+            position: None,
             last_read_date: None,
+            added_at: U64(0),
+            started_at: None,
+            completed_at: None,
+            updated_at: U64(0),
+            reading_cycles: Vec::new(),
             chapter_notes: HashMap::new(),
+            privacy: None,
+            catalog: None,
         }
     }
 
@@ -492,10 +1427,10 @@ mod tests {
         let context = get_context("alice.testnet".parse().unwrap());
         testing_env!(context.build());
 
-        let mut contract = Contract::default();
-        contract.add_book(create_sample_book());
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
 
-        let library = contract.get_library("alice.testnet".parse().unwrap());
+        let library = contract.get_library("alice.testnet".parse().unwrap(), None, None, None, None).items;
         assert_eq!(library.len(), 1);
         assert_eq!(library[0].title, "1984");
         assert_eq!(library[0].reading_status, ReadingStatus::ToRead);
@@ -506,14 +1441,10 @@ mod tests {
         let context = get_context("alice.testnet".parse().unwrap());
         testing_env!(context.build());
 
-        let mut contract = Contract::default();
-        contract.add_book(create_sample_book());
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
 
-        // Should panic on duplicate
-        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-            contract.add_book(create_sample_book());
-        }));
-        assert!(result.is_err());
+        assert_eq!(contract.add_book(create_sample_book()), Err(BookyError::DuplicateIsbn));
     }
 
     #[test]
@@ -521,11 +1452,11 @@ mod tests {
         let context = get_context("alice.testnet".parse().unwrap());
         testing_env!(context.build());
 
-        let mut contract = Contract::default();
-        contract.add_book(create_sample_book());
-        contract.start_reading("978-0451524935".to_string(), Some(1));
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
+        contract.start_reading("978-0451524935".to_string(), Some(1)).unwrap();
 
-        let book = contract.get_book("alice.testnet".parse().unwrap(), "978-0451524935".to_string()).unwrap();
+        let book = contract.get_book("alice.testnet".parse().unwrap(), "978-0451524935".to_string(), None).unwrap();
         assert_eq!(book.reading_status, ReadingStatus::Reading);
         assert_eq!(book.current_chapter, 1);
     }
@@ -535,9 +1466,9 @@ mod tests {
         let context = get_context("alice.testnet".parse().unwrap());
         testing_env!(context.build());
 
-        let mut contract = Contract::default();
-        contract.add_book(create_sample_book());
-        contract.start_reading("978-0451524935".to_string(), Some(1));
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
+        contract.start_reading("978-0451524935".to_string(), Some(1)).unwrap();
 
         let progress = ProgressUpdate {
             current_chapter: Some(3),
@@ -545,11 +1476,13 @@ mod tests {
             last_read_position: Some("page 45".to_string()),
             last_read_date: Some("2024-12-22".to_string()),
             reading_status: None,
+            minutes_read: None,
+            position: None,
         };
 
-        contract.update_reading_progress("978-0451524935".to_string(), progress);
+        contract.update_reading_progress("978-0451524935".to_string(), progress).unwrap();
 
-        let book = contract.get_book("alice.testnet".parse().unwrap(), "978-0451524935".to_string()).unwrap();
+        let book = contract.get_book("alice.testnet".parse().unwrap(), "978-0451524935".to_string(), None).unwrap();
         assert_eq!(book.current_chapter, 3);
         assert!(book.chapters_read.contains(&1));
         assert!(book.chapters_read.contains(&2));
@@ -562,13 +1495,13 @@ mod tests {
         let context = get_context("alice.testnet".parse().unwrap());
         testing_env!(context.build());
 
-        let mut contract = Contract::default();
-        contract.add_book(create_sample_book());
-        contract.start_reading("978-0451524935".to_string(), Some(1));
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
+        contract.start_reading("978-0451524935".to_string(), Some(1)).unwrap();
 
-        contract.mark_completed("978-0451524935".to_string());
+        contract.mark_completed("978-0451524935".to_string()).unwrap();
 
-        let book = contract.get_book("alice.testnet".parse().unwrap(), "978-0451524935".to_string()).unwrap();
+        let book = contract.get_book("alice.testnet".parse().unwrap(), "978-0451524935".to_string(), None).unwrap();
         assert_eq!(book.reading_status, ReadingStatus::Completed);
         // All chapters should be marked as read
         assert_eq!(book.chapters_read.len(), 10);
@@ -579,14 +1512,14 @@ mod tests {
         let context = get_context("alice.testnet".parse().unwrap());
         testing_env!(context.build());
 
-        let mut contract = Contract::default();
-        contract.add_book(create_sample_book());
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
 
-        contract.add_chapter_note("978-0451524935".to_string(), 3, "Great quote on page 45 about freedom".to_string());
+        contract.add_chapter_note("978-0451524935".to_string(), 3, "Great quote on page 45 about freedom".into()).unwrap();
 
-        let note = contract.get_chapter_note("alice.testnet".parse().unwrap(), "978-0451524935".to_string(), 3);
+        let note = contract.get_chapter_note("alice.testnet".parse().unwrap(), "978-0451524935".to_string(), 3, None);
         assert!(note.is_some());
-        assert_eq!(note.unwrap(), "Great quote on page 45 about freedom");
+        assert_eq!(note.unwrap(), "Great quote on page 45 about freedom".into());
     }
 
     #[test]
@@ -594,17 +1527,17 @@ mod tests {
         let context = get_context("alice.testnet".parse().unwrap());
         testing_env!(context.build());
 
-        let mut contract = Contract::default();
-        contract.add_book(create_sample_book());
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
 
         // Add initial note
-        contract.add_chapter_note("978-0451524935".to_string(), 3, "Initial note".to_string());
+        contract.add_chapter_note("978-0451524935".to_string(), 3, "Initial note".into()).unwrap();
 
         // Update note
-        contract.add_chapter_note("978-0451524935".to_string(), 3, "Updated note with more detail".to_string());
+        contract.add_chapter_note("978-0451524935".to_string(), 3, "Updated note with more detail".into()).unwrap();
 
-        let note = contract.get_chapter_note("alice.testnet".parse().unwrap(), "978-0451524935".to_string(), 3);
-        assert_eq!(note.unwrap(), "Updated note with more detail");
+        let note = contract.get_chapter_note("alice.testnet".parse().unwrap(), "978-0451524935".to_string(), 3, None);
+        assert_eq!(note.unwrap(), "Updated note with more detail".into());
     }
 
     #[test]
@@ -612,19 +1545,19 @@ mod tests {
         let context = get_context("alice.testnet".parse().unwrap());
         testing_env!(context.build());
 
-        let mut contract = Contract::default();
-        contract.add_book(create_sample_book());
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
 
         // Add notes for multiple chapters
-        contract.add_chapter_note("978-0451524935".to_string(), 3, "Great quote on page 45".to_string());
-        contract.add_chapter_note("978-0451524935".to_string(), 5, "This was confusing".to_string());
-        contract.add_chapter_note("978-0451524935".to_string(), 7, "Key insight about Winston".to_string());
+        contract.add_chapter_note("978-0451524935".to_string(), 3, "Great quote on page 45".into()).unwrap();
+        contract.add_chapter_note("978-0451524935".to_string(), 5, "This was confusing".into()).unwrap();
+        contract.add_chapter_note("978-0451524935".to_string(), 7, "Key insight about Winston".into()).unwrap();
 
-        let notes = contract.get_all_chapter_notes("alice.testnet".parse().unwrap(), "978-0451524935".to_string());
+        let notes = contract.get_all_chapter_notes("alice.testnet".parse().unwrap(), "978-0451524935".to_string(), None);
         assert_eq!(notes.len(), 3);
-        assert_eq!(notes.get(&3).unwrap(), "Great quote on page 45");
-        assert_eq!(notes.get(&5).unwrap(), "This was confusing");
-        assert_eq!(notes.get(&7).unwrap(), "Key insight about Winston");
+        assert_eq!(notes[&3], "Great quote on page 45".into());
+        assert_eq!(notes[&5], "This was confusing".into());
+        assert_eq!(notes[&7], "Key insight about Winston".into());
     }
 
     #[test]
@@ -632,21 +1565,21 @@ mod tests {
         let context = get_context("alice.testnet".parse().unwrap());
         testing_env!(context.build());
 
-        let mut contract = Contract::default();
-        contract.add_book(create_sample_book());
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
 
         // Add note
-        contract.add_chapter_note("978-0451524935".to_string(), 3, "Note to delete".to_string());
+        contract.add_chapter_note("978-0451524935".to_string(), 3, "Note to delete".into()).unwrap();
 
         // Verify note exists
-        let note = contract.get_chapter_note("alice.testnet".parse().unwrap(), "978-0451524935".to_string(), 3);
+        let note = contract.get_chapter_note("alice.testnet".parse().unwrap(), "978-0451524935".to_string(), 3, None);
         assert!(note.is_some());
 
         // Delete note
-        contract.delete_chapter_note("978-0451524935".to_string(), 3);
+        contract.delete_chapter_note("978-0451524935".to_string(), 3).unwrap();
 
         // Verify note is gone
-        let note = contract.get_chapter_note("alice.testnet".parse().unwrap(), "978-0451524935".to_string(), 3);
+        let note = contract.get_chapter_note("alice.testnet".parse().unwrap(), "978-0451524935".to_string(), 3, None);
         assert!(note.is_none());
     }
 
@@ -655,27 +1588,27 @@ mod tests {
         let context = get_context("alice.testnet".parse().unwrap());
         testing_env!(context.build());
 
-        let mut contract = Contract::default();
+        let mut contract = setup_contract();
 
         // Add multiple books with different statuses
         let mut book1 = create_sample_book();
         book1.isbn = "978-0451524935".to_string();
         book1.reading_status = ReadingStatus::Reading;
-        contract.add_book(book1);
+        contract.add_book(book1).unwrap();
 
         let mut book2 = create_sample_book();
         book2.isbn = "978-0061120084".to_string();
         book2.title = "To Kill a Mockingbird".to_string();
         book2.reading_status = ReadingStatus::Completed;
-        contract.add_book(book2);
+        contract.add_book(book2).unwrap();
 
         let mut book3 = create_sample_book();
         book3.isbn = "978-0743273565".to_string();
         book3.title = "The Great Gatsby".to_string();
         book3.reading_status = ReadingStatus::ToRead;
-        contract.add_book(book3);
+        contract.add_book(book3).unwrap();
 
-        let stats = contract.get_reading_stats("alice.testnet".parse().unwrap());
+        let stats = contract.get_reading_stats("alice.testnet".parse().unwrap(), None);
         assert_eq!(stats.total_books, 3);
         assert_eq!(stats.currently_reading, 1);
         assert_eq!(stats.completed, 1);
@@ -687,21 +1620,21 @@ mod tests {
         let context = get_context("alice.testnet".parse().unwrap());
         testing_env!(context.build());
 
-        let mut contract = Contract::default();
+        let mut contract = setup_contract();
 
         // Add books
         let mut book1 = create_sample_book();
         book1.isbn = "978-0451524935".to_string();
         book1.reading_status = ReadingStatus::Reading;
-        contract.add_book(book1);
+        contract.add_book(book1).unwrap();
 
         let mut book2 = create_sample_book();
         book2.isbn = "978-0061120084".to_string();
         book2.title = "To Kill a Mockingbird".to_string();
         book2.reading_status = ReadingStatus::Completed;
-        contract.add_book(book2);
+        contract.add_book(book2).unwrap();
 
-        let reading = contract.get_currently_reading("alice.testnet".parse().unwrap());
+        let reading = contract.get_currently_reading("alice.testnet".parse().unwrap(), None, None, None).items;
         assert_eq!(reading.len(), 1);
         assert_eq!(reading[0].title, "1984");
     }
@@ -711,16 +1644,16 @@ mod tests {
         let context = get_context("alice.testnet".parse().unwrap());
         testing_env!(context.build());
 
-        let mut contract = Contract::default();
-        contract.add_book(create_sample_book());
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
 
         let mut updated_book = create_sample_book();
-        updated_book.personal_comments = "Re-read in 2025 - still terrifying".to_string();
+        updated_book.personal_comments = "Re-read in 2025 - still terrifying".into();
         updated_book.total_chapters = Some(12);
-        contract.update_book("978-0451524935".to_string(), updated_book);
+        contract.update_book("978-0451524935".to_string(), updated_book).unwrap();
 
-        let updated = contract.get_book("alice.testnet".parse().unwrap(), "978-0451524935".to_string()).unwrap();
-        assert_eq!(updated.personal_comments, "Re-read in 2025 - still terrifying");
+        let updated = contract.get_book("alice.testnet".parse().unwrap(), "978-0451524935".to_string(), None).unwrap();
+        assert_eq!(updated.personal_comments, "Re-read in 2025 - still terrifying".into());
         assert_eq!(updated.total_chapters, Some(12));
     }
 
@@ -729,11 +1662,11 @@ mod tests {
         let context = get_context("alice.testnet".parse().unwrap());
         testing_env!(context.build());
 
-        let mut contract = Contract::default();
-        contract.add_book(create_sample_book());
-        contract.delete_book("978-0451524935".to_string());
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
+        contract.delete_book("978-0451524935".to_string()).unwrap();
 
-        let library = contract.get_library("alice.testnet".parse().unwrap());
+        let library = contract.get_library("alice.testnet".parse().unwrap(), None, None, None, None).items;
         assert_eq!(library.len(), 0);
     }
 
@@ -742,13 +1675,13 @@ mod tests {
         let context = get_context("alice.testnet".parse().unwrap());
         testing_env!(context.build());
 
-        let mut contract = Contract::default();
-        contract.add_book(create_sample_book());
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
 
         let mut book2 = create_sample_book();
         book2.isbn = "978-0061120084".to_string();
         book2.title = "To Kill a Mockingbird".to_string();
-        contract.add_book(book2);
+        contract.add_book(book2).unwrap();
 
         // Bob adds 1 book
         let context = get_context("bob.testnet".parse().unwrap());
@@ -757,8 +1690,2421 @@ mod tests {
         let mut book3 = create_sample_book();
         book3.isbn = "978-0743273565".to_string();
         book3.title = "The Great Gatsby".to_string();
-        contract.add_book(book3);
+        contract.add_book(book3).unwrap();
+
+        assert_eq!(contract.get_total_books(), U64(3));
+    }
+
+    #[test]
+    fn migrate_from_hashmap_state() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut book2 = create_sample_book();
+        book2.isbn = "978-0061120084".to_string();
+        book2.title = "To Kill a Mockingbird".to_string();
+
+        let v0 = |book: BookEntry| migrate::BookEntryV0 {
+            isbn: book.isbn,
+            title: book.title,
+            author: book.author,
+            acquisition_date: book.acquisition_date,
+            condition: book.condition,
+            personal_comments: "Still relevant today".to_string(),
+            media_hash: book.media_hash,
+            reading_status: book.reading_status,
+            current_chapter: book.current_chapter,
+            total_chapters: book.total_chapters,
+            chapters_read: book.chapters_read,
+            last_read_position: book.last_read_position,
+            last_read_date: book.last_read_date,
+            chapter_notes: HashMap::new(),
+        };
+
+        // The old update_book could give two entries the same ISBN, here in another spelling
+        let mut duplicate = v0(create_sample_book());
+        duplicate.isbn = "0-451-52493-4".to_string();
+        duplicate.current_chapter = 4;
+        duplicate.chapter_notes.insert(3, "Room 101".to_string());
+
+        let mut libraries = HashMap::new();
+        libraries.insert("alice.testnet".parse().unwrap(), vec![v0(create_sample_book()), v0(book2), duplicate]);
+        libraries.insert("bob.testnet".parse().unwrap(), vec![v0(create_sample_book())]);
+        let mut followed_accounts = HashMap::new();
+        followed_accounts.insert("alice.testnet".parse().unwrap(), vec!["bob.testnet".parse().unwrap()]);
+        env::state_write(&migrate::ContractV0 { libraries, followed_accounts });
+
+        let mut contract = Contract::migrate();
+
+        assert_eq!(contract.get_total_books(), U64(4));
+        assert_eq!(contract.get_library("alice.testnet".parse().unwrap(), None, None, None, None).items.len(), 3);
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        let kept = contract.get_book(alice.clone(), "978-0451524935".to_string(), None).unwrap();
+        assert_eq!((kept.isbn.as_str(), kept.current_chapter), ("9780451524935", 0));
+        let duplicate = contract.get_book(alice.clone(), "9780451524935#2".to_string(), None).unwrap();
+        assert_eq!((duplicate.current_chapter, duplicate.chapter_notes.get(&3)), (4, Some(&"Room 101".into())));
+        let collision = "Kept 0-451-52493-4 of alice.testnet under 9780451524935#2".to_string();
+        assert!(near_sdk::test_utils::get_logs().contains(&collision));
+
+        // Migrated keys are canonical, so no spelling of a migrated ISBN can be added again
+        let book = BookEntry { isbn: "9780061120084".to_string(), ..create_sample_book() };
+        assert_eq!(contract.add_book(book), Err(BookyError::DuplicateIsbn));
+        let book = contract.get_book("bob.testnet".parse().unwrap(), "978-0451524935".to_string(), None).unwrap();
+        assert_eq!(book.title, "1984");
+        assert_eq!(
+            contract.get_followed_accounts("alice.testnet".parse().unwrap(), None, None).items,
+            vec!["bob.testnet".parse::<AccountId>().unwrap()]
+        );
+    }
+
+    /// Run add_book the way a real function call would: load state, mutate, write it back
+    fn add_book_gas(contract: Contract, account: &str, book: BookEntry) -> u64 {
+        env::state_write(&contract);
+        drop(contract);
+        testing_env!(get_context(account.parse().unwrap()).build());
+
+        let mut contract: Contract = env::state_read().unwrap();
+        contract.add_book(book).unwrap();
+        env::state_write(&contract);
+        drop(contract);
+        env::used_gas().as_gas()
+    }
+
+    #[test]
+    fn add_book_gas_independent_of_other_libraries() {
+        testing_env!(get_context("alice.testnet".parse().unwrap()).build());
+        let empty_gas = add_book_gas(setup_contract(), "alice.testnet", create_sample_book());
+
+        let mut context = get_context("carol.testnet".parse().unwrap());
+        context.attached_deposit(NearToken::from_near(1));
+        testing_env!(context.build());
+        let mut contract = setup_contract();
+        testing_env!(context.build());
+        contract.storage_deposit(None, None).unwrap();
+        for i in 0..200 {
+            let mut book = create_sample_book();
+            book.isbn = format!("isbn-{}", i);
+            book.personal_comments = "x".repeat(500).into();
+            contract.insert_book("bob.testnet".parse().unwrap(), book);
+        }
+        contract.insert_follow("bob.testnet".parse().unwrap(), "carol.testnet".parse().unwrap());
+        let busy_gas = add_book_gas(contract, "carol.testnet", create_sample_book());
+
+        assert_eq!(empty_gas, busy_gas);
+    }
+
+    #[test]
+    fn storage_deposit_and_balance() {
+        let mut context = get_context("alice.testnet".parse().unwrap());
+        context.attached_deposit(NearToken::from_near(1));
+        testing_env!(context.build());
+
+        let mut contract = Contract::default();
+        let bounds = contract.storage_balance_bounds();
+        let balance = contract.storage_deposit(None, None).unwrap();
+        assert_eq!(balance.total.0, NearToken::from_near(1).as_yoctonear());
+        assert_eq!(balance.available.0, balance.total.0 - bounds.min.0);
+
+        // Registration-only deposits keep just the minimum
+        let balance = contract.storage_deposit(Some("bob.testnet".parse().unwrap()), Some(true)).unwrap();
+        assert_eq!(balance.total.0, bounds.min.0);
+        assert_eq!(balance.available.0, 0);
+
+        assert!(contract.storage_balance_of("carol.testnet".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn unregistered_account_cannot_add_book() {
+        let context = get_context("carol.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        assert_eq!(
+            contract.add_book(create_sample_book()),
+            Err(BookyError::NotRegistered("carol.testnet".parse().unwrap()))
+        );
+    }
+
+    #[test]
+    fn insufficient_storage_balance_fails() {
+        let mut context = get_context("alice.testnet".parse().unwrap());
+        context.attached_deposit(NearToken::from_near(1));
+        testing_env!(context.build());
+
+        let mut contract = Contract::default();
+        contract.storage_deposit(None, Some(true)).unwrap();
+
+        let result = contract.add_book(create_sample_book());
+        assert!(matches!(result, Err(BookyError::InsufficientStorageBalance { .. })));
+    }
+
+    #[test]
+    fn storage_charged_and_refunded() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        let initial = contract.storage_balance_of(alice.clone()).unwrap().available.0;
+
+        contract.add_book(create_sample_book()).unwrap();
+        let after_add = contract.storage_balance_of(alice.clone()).unwrap().available.0;
+        assert!(after_add < initial);
+
+        contract.add_chapter_note("978-0451524935".to_string(), 3, "A long note".repeat(20).into()).unwrap();
+        let after_note = contract.storage_balance_of(alice.clone()).unwrap().available.0;
+        assert!(after_note < after_add);
+
+        contract.delete_chapter_note("978-0451524935".to_string(), 3).unwrap();
+        assert_eq!(contract.storage_balance_of(alice.clone()).unwrap().available.0, after_add);
+
+        contract.delete_book("978-0451524935".to_string()).unwrap();
+        assert_eq!(contract.storage_balance_of(alice).unwrap().available.0, initial);
+    }
+
+    #[test]
+    fn storage_withdraw_and_unregister() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
+
+        // Follow requests both ways: alice asks to follow bob, and bob pays for asking to follow alice
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        let bob: AccountId = "bob.testnet".parse().unwrap();
+        contract.set_follow_approval(true).unwrap();
+        testing_env!(get_context(bob.clone()).build());
+        contract.set_follow_approval(true).unwrap();
+        let bob_balance = contract.storage_balance_of(bob.clone()).unwrap();
+        contract.follow_account(alice.clone()).unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        contract.follow_account(bob.clone()).unwrap();
+
+        let mut context = get_context("alice.testnet".parse().unwrap());
+        context.attached_deposit(NearToken::from_yoctonear(1));
+        testing_env!(context.build());
+
+        let balance = contract.storage_withdraw(None).unwrap();
+        assert_eq!(balance.available.0, 0);
+
+        // Stored data blocks unregistering unless forced
+        assert_eq!(contract.storage_unregister(None), Err(BookyError::StorageInUse));
+
+        assert_eq!(contract.storage_unregister(Some(true)), Ok(true));
+        assert!(contract.storage_balance_of("alice.testnet".parse().unwrap()).is_none());
+        assert_eq!(contract.get_library("alice.testnet".parse().unwrap(), None, None, None, None).items.len(), 0);
+        assert_eq!(contract.get_total_books(), U64(0));
+        let events = event_logs();
+        assert_eq!(events.last().unwrap()["event"], "account_unregistered");
+
+        // No requests are left behind, and bob got back what his request cost
+        assert_eq!(contract.get_follow_requests(alice.clone(), None, None).total, 0);
+        assert_eq!(contract.get_follow_requests(bob.clone(), None, None).total, 0);
+        assert!(contract.sent_follow_requests.get(&alice).is_none());
+        assert!(contract.sent_follow_requests.get(&bob).is_none());
+        assert_eq!(contract.storage_balance_of(bob).unwrap().available, bob_balance.available);
+    }
+
+    fn event_logs() -> Vec<serde_json::Value> {
+        near_sdk::test_utils::get_logs()
+            .iter()
+            .filter_map(|log| log.strip_prefix("EVENT_JSON:"))
+            .map(|json| serde_json::from_str(json).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn book_added_event() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
+
+        let events = event_logs();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["standard"], "booky");
+        assert_eq!(events[0]["version"], "2.1.0");
+        assert_eq!(events[0]["event"], "book_added");
+        assert_eq!(events[0]["data"]["account_id"], "alice.testnet");
+        assert_eq!(events[0]["data"]["isbn"], "9780451524935");
+        assert_eq!(events[0]["data"]["book"]["title"], "1984");
+        assert_eq!(events[0]["data"]["book"]["note_count"], 0);
+        assert_eq!(events[0]["data"]["book"]["personal_comments"], "Still relevant today");
+        assert!(events[0]["data"]["book"].get("content_omitted").is_none());
+    }
+
+    #[test]
+    fn progress_and_status_events() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
+        contract.start_reading("978-0451524935".to_string(), Some(2)).unwrap();
+        contract.mark_completed("978-0451524935".to_string()).unwrap();
+
+        let events = event_logs();
+        let names: Vec<&str> = events.iter().map(|e| e["event"].as_str().unwrap()).collect();
+        assert_eq!(
+            names,
+            vec!["book_added", "progress_updated", "status_changed", "progress_updated", "status_changed"]
+        );
+        assert_eq!(events[1]["data"]["current_chapter"], 2);
+        assert_eq!(events[2]["data"]["old_status"], "ToRead");
+        assert_eq!(events[2]["data"]["new_status"], "Reading");
+        assert_eq!(events[3]["data"]["chapters_completed"].as_array().unwrap().len(), 10);
+        assert_eq!(events[4]["data"]["new_status"], "Completed");
+    }
+
+    #[test]
+    fn note_and_follow_events() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
+        contract.add_chapter_note("978-0451524935".to_string(), 3, "Big Brother".into()).unwrap();
+        contract.delete_chapter_note("978-0451524935".to_string(), 3).unwrap();
+        contract.follow_account("bob.testnet".parse().unwrap()).unwrap();
+        contract.unfollow_account("bob.testnet".parse().unwrap()).unwrap();
+        contract.delete_book("978-0451524935".to_string()).unwrap();
+
+        let events = event_logs();
+        let names: Vec<&str> = events.iter().map(|e| e["event"].as_str().unwrap()).collect();
+        assert_eq!(
+            names,
+            vec!["book_added", "note_added", "note_deleted", "followed", "unfollowed", "book_deleted"]
+        );
+        assert_eq!(events[1]["data"]["chapter"], 3);
+        assert_eq!(events[1]["data"]["note"], "Big Brother");
+        assert_eq!(events[3]["data"]["followed_account_id"], "bob.testnet");
+        assert_eq!(events[5]["data"]["isbn"], "9780451524935");
+    }
+
+    #[test]
+    fn book_updated_event_records_isbn_change() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
+
+        let mut updated_book = create_sample_book();
+        updated_book.isbn = "978-0141036144".to_string();
+        updated_book.condition = "Fair".to_string();
+        contract.update_book("978-0451524935".to_string(), updated_book).unwrap();
+
+        // Only the fields that changed are carried
+        let events = event_logs();
+        assert_eq!(events[1]["event"], "book_updated");
+        assert_eq!(events[1]["data"]["previous_isbn"], "9780451524935");
+        assert_eq!(events[1]["data"]["isbn"], "9780141036144");
+        assert_eq!(events[1]["data"]["book"], serde_json::json!({ "condition": "Fair" }));
+        assert!(contract.get_book("alice.testnet".parse().unwrap(), "978-0451524935".to_string(), None).is_none());
+    }
+
+    #[test]
+    fn book_rebuilds_from_its_events() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = setup_contract();
+        let section = |id| Section { id, label: format!("Part {}", id), ordinal: id, start_page: None, end_page: None };
+        let copy = OwnedCopy {
+            format: BookFormat::Hardcover,
+            condition: "Good".to_string(),
+            acquisition_date: "2024-01-15".to_string(),
+            price_paid: Some(Price { amount: U64(2499), currency: "USD".to_string() }),
+            location: Some("Study".to_string()),
+        };
+        let book = BookEntry {
+            reading_status: ReadingStatus::Reading,
+            current_chapter: 3,
+            total_chapters: None,
+            sections: (1..=3).map(section).collect(),
+            total_pages: Some(300),
+            chapters_read: HashSet::from([1, 2]),
+            position: Some(ReadingPosition::Page { page: 40 }),
+            copies: vec![copy],
+            chapter_notes: HashMap::from([(1, "Big Brother".into()), (2, "Room 101".into())]),
+            ..create_sample_book()
+        };
+        contract.add_book(book.clone()).unwrap();
+        let updated = BookEntry {
+            personal_comments: "Even more relevant".into(),
+            chapters_read: HashSet::from([1, 2, 3]),
+            chapter_notes: HashMap::from([(2, "Room 101, again".into()), (3, "Doublethink".into())]),
+            ..book
+        };
+        contract.update_book("9780451524935".to_string(), updated).unwrap();
+
+        // Replay the events the way an indexer would
+        let mut fields = serde_json::Map::new();
+        let mut notes = serde_json::Map::new();
+        for event in event_logs() {
+            let data = &event["data"];
+            match event["event"].as_str().unwrap() {
+                "book_added" | "book_updated" => fields.extend(data["book"].as_object().unwrap().clone()),
+                "note_added" => {
+                    notes.insert(data["chapter"].to_string(), data["note"].clone());
+                }
+                "note_deleted" => {
+                    notes.remove(&data["chapter"].to_string());
+                }
+                _ => {}
+            }
+        }
+        assert!(!fields.contains_key("content_omitted"));
+        fields.insert("isbn".to_string(), "9780451524935".into());
+        fields.insert("chapter_notes".to_string(), notes.into());
+        let rebuilt: BookEntry = serde_json::from_value(fields.into()).unwrap();
+
+        let stored = contract.get_book(alice, "9780451524935".to_string(), None).unwrap();
+        let content = |book: &BookEntry| {
+            (
+                book.personal_comments.clone(),
+                book.copies.clone(),
+                book.sections.clone(),
+                book.chapters_read.clone(),
+                book.position,
+                book.chapter_notes.clone(),
+            )
+        };
+        assert_eq!(content(&rebuilt), content(&stored));
+        assert_eq!(rebuilt.chapter_notes.len(), 2);
+        assert_eq!((rebuilt.title, rebuilt.condition, rebuilt.current_chapter), (stored.title, stored.condition, 3));
+    }
+
+    #[test]
+    fn largest_books_stay_editable() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = setup_contract();
+        let mut context = get_context(alice.clone());
+        testing_env!(context.attached_deposit(NearToken::from_near(100)).build());
+        contract.storage_deposit(None, None).unwrap();
+
+        // Every field at its size limit, and far more in notes than one 16 KB log could carry
+        let limits = contract.get_field_limits();
+        let text = |len: u32| "x".repeat(len as usize);
+        let chapters = 1..=limits.max_total_chapters;
+        let section = |id| Section {
+            id,
+            label: text(limits.max_title_len),
+            ordinal: id,
+            start_page: None,
+            end_page: None,
+        };
+        let copy = OwnedCopy {
+            format: BookFormat::Hardcover,
+            condition: text(limits.max_condition_len),
+            acquisition_date: "2024-01-15".to_string(),
+            price_paid: Some(Price { amount: U64(2499), currency: "USD".to_string() }),
+            location: Some(text(limits.max_location_len)),
+        };
+        let book = BookEntry {
+            title: text(limits.max_title_len),
+            author: text(limits.max_author_len),
+            condition: text(limits.max_condition_len),
+            personal_comments: text(limits.max_comments_len).into(),
+            media_hash: Some(text(limits.max_media_hash_len)),
+            last_read_position: text(limits.max_position_len),
+            total_chapters: None,
+            sections: chapters.clone().map(section).collect(),
+            chapters_read: chapters.clone().collect(),
+            copies: vec![copy; limits.max_copies_per_book as usize],
+            chapter_notes: chapters.take(100).map(|chapter| (chapter, text(limits.max_note_len).into())).collect(),
+            ..create_sample_book()
+        };
+        contract.add_book(book.clone()).unwrap();
+        let added = event_logs().into_iter().find(|event| event["event"] == "book_added").unwrap();
+        assert_eq!(added["data"]["book"]["content_omitted"], true);
+        assert!(added["data"]["book"].get("sections").is_none());
+        assert!(event_logs().iter().all(|event| event["event"] != "note_added"));
+
+        // A fresh context per call, as each would be its own transaction
+        let next_call = || testing_env!(get_context(alice.clone()).storage_usage(env::storage_usage()).build());
+        next_call();
+        let updated = BookEntry { condition: "Fair".to_string(), ..book };
+        contract.update_book("9780451524935".to_string(), updated).unwrap();
+        let events = event_logs();
+        assert_eq!(events[0]["data"]["book"], serde_json::json!({ "condition": "Fair" }));
+
+        next_call();
+        contract.set_book_privacy("9780451524935".to_string(), Some(PrivacySettings::default())).unwrap();
+        let book = contract.get_book(alice.clone(), "9780451524935".to_string(), None).unwrap();
+        assert_eq!((book.condition.as_str(), book.chapter_notes.len()), ("Fair", 100));
+    }
+
+    fn add_sample_library(contract: &mut Contract) {
+        let mut book1 = create_sample_book();
+        book1.reading_status = ReadingStatus::Reading;
+        book1.last_read_date = Some("2024-12-01".to_string());
+        contract.add_book(book1).unwrap();
+
+        let mut book2 = create_sample_book();
+        book2.isbn = "978-0061120084".to_string();
+        book2.title = "To Kill a Mockingbird".to_string();
+        book2.author = "Harper Lee".to_string();
+        book2.acquisition_date = "2023-06-01".to_string();
+        book2.reading_status = ReadingStatus::Completed;
+        book2.last_read_date = Some("2024-03-10".to_string());
+        contract.add_book(book2).unwrap();
+
+        let mut book3 = create_sample_book();
+        book3.isbn = "978-0452284234".to_string();
+        book3.title = "Animal Farm".to_string();
+        book3.acquisition_date = "2024-08-20".to_string();
+        contract.add_book(book3).unwrap();
+    }
+
+    #[test]
+    fn get_library_paginates() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        add_sample_library(&mut contract);
+
+        let page = contract.get_library("alice.testnet".parse().unwrap(), None, None, Some(2), None);
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(page.total, 3);
+        assert_eq!(page.next_index, Some(2));
+
+        let page = contract.get_library("alice.testnet".parse().unwrap(), None, page.next_index, Some(2), None);
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.next_index, None);
+
+        // A zero limit still moves forward
+        let page = contract.get_library("alice.testnet".parse().unwrap(), None, Some(1), Some(0), None);
+        assert_eq!((page.items.len(), page.next_index), (1, Some(2)));
+
+        let page = contract.get_library("nobody.testnet".parse().unwrap(), None, None, None, None);
+        assert_eq!(page.total, 0);
+        assert!(page.items.is_empty());
+    }
+
+    #[test]
+    fn get_library_filters() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        add_sample_library(&mut contract);
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+
+        let query = LibraryQuery { author: Some("orwell".to_string()), ..Default::default() };
+        let page = contract.get_library(alice.clone(), Some(query), None, None, None);
+        assert_eq!(page.total, 2);
+
+        let query = LibraryQuery { reading_status: Some(ReadingStatus::Completed), ..Default::default() };
+        let page = contract.get_library(alice.clone(), Some(query), None, None, None);
+        assert_eq!(page.total, 1);
+        assert_eq!(page.items[0].title, "To Kill a Mockingbird");
+
+        let query = LibraryQuery {
+            acquired_from: Some("2024-01-01".to_string()),
+            acquired_to: Some("2024-06-30".to_string()),
+            ..Default::default()
+        };
+        let page = contract.get_library(alice, Some(query), None, None, None);
+        assert_eq!(page.total, 1);
+        assert_eq!(page.items[0].title, "1984");
+    }
+
+    #[test]
+    fn get_library_sorts() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        add_sample_library(&mut contract);
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+
+        let query = LibraryQuery { sort_by: Some(SortBy::Title), ..Default::default() };
+        let titles: Vec<String> = contract
+            .get_library(alice.clone(), Some(query), None, None, None)
+            .items
+            .into_iter()
+            .map(|b| b.title)
+            .collect();
+        assert_eq!(titles, vec!["1984", "Animal Farm", "To Kill a Mockingbird"]);
+
+        let query = LibraryQuery {
+            sort_by: Some(SortBy::AcquisitionDate),
+            descending: true,
+            ..Default::default()
+        };
+        let page = contract.get_library(alice.clone(), Some(query), Some(1), Some(1), None);
+        assert_eq!(page.items[0].title, "1984");
+        assert_eq!(page.next_index, Some(2));
+
+        // Books never read sort after those with a last read date
+        let query = LibraryQuery { sort_by: Some(SortBy::LastReadDate), ..Default::default() };
+        let titles: Vec<String> = contract
+            .get_library(alice, Some(query), None, None, None)
+            .items
+            .into_iter()
+            .map(|b| b.title)
+            .collect();
+        assert_eq!(titles, vec!["To Kill a Mockingbird", "1984", "Animal Farm"]);
+    }
+
+    #[test]
+    fn get_followed_accounts_with_details_paginates() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        add_sample_library(&mut contract);
+
+        testing_env!(get_context("bob.testnet".parse().unwrap()).build());
+        contract.follow_account("alice.testnet".parse().unwrap()).unwrap();
+        contract.follow_account("carol.testnet".parse().unwrap()).unwrap();
+
+        let page = contract.get_followed_accounts_with_details("bob.testnet".parse().unwrap(), None, Some(1), Some(2), None);
+        assert_eq!(page.total, 2);
+        assert_eq!(page.next_index, Some(1));
+        assert_eq!(page.items[0].account_id, "alice.testnet".parse::<AccountId>().unwrap());
+        assert_eq!(page.items[0].library.items.len(), 2);
+        assert_eq!(page.items[0].library.total, 3);
+        assert_eq!(page.items[0].stats.total_books, 3);
+    }
+
+    #[test]
+    fn library_summaries() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        add_sample_library(&mut contract);
+        contract.update_reading_progress(
+            "978-0451524935".to_string(),
+            ProgressUpdate {
+                current_chapter: Some(4),
+                chapters_completed: vec![1, 2, 3],
+                last_read_position: None,
+                last_read_date: None,
+                reading_status: None,
+                minutes_read: None,
+                position: None,
+            },
+        ).unwrap();
+        contract.add_chapter_note("978-0451524935".to_string(), 2, "Doublethink".into()).unwrap();
+
+        let page = contract.get_library_summaries("alice.testnet".parse().unwrap(), None, None, None, None);
+        assert_eq!(page.total, 3);
+        let summary = page.items.iter().find(|b| b.isbn == "9780451524935").unwrap();
+        assert_eq!(summary.title, "1984");
+        assert_eq!(summary.progress_percent, Some(30));
+        assert_eq!(summary.note_count, 1);
+        assert_eq!(summary.last_read_date, Some("2024-12-01".to_string()));
+
+        // Completed books count as fully read
+        let completed = page.items.iter().find(|b| b.isbn == "9780061120084").unwrap();
+        assert_eq!(completed.progress_percent, Some(100));
+
+        let reading = contract.get_currently_reading_summaries("alice.testnet".parse().unwrap(), None, None, None);
+        assert_eq!(reading.total, 1);
+        assert_eq!(reading.items[0].reading_status, ReadingStatus::Reading);
+    }
+
+    #[test]
+    fn followed_accounts_with_summaries() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        add_sample_library(&mut contract);
+
+        testing_env!(get_context("bob.testnet".parse().unwrap()).build());
+        contract.follow_account("alice.testnet".parse().unwrap()).unwrap();
+
+        let page = contract.get_followed_accounts_with_summaries("bob.testnet".parse().unwrap(), None, None, None, None);
+        assert_eq!(page.total, 1);
+        assert_eq!(page.items[0].books.total, 3);
+        assert_eq!(page.items[0].stats.completed, 1);
+    }
+
+    #[test]
+    fn invalid_isbn_rejected() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        let mut book = create_sample_book();
+        book.isbn = "978-0451524936".to_string();
+
+        assert_eq!(
+            contract.add_book(book),
+            Err(BookyError::InvalidIsbn("Invalid ISBN-13: 978-0451524936".to_string()))
+        );
+    }
+
+    #[test]
+    fn isbn_forms_resolve_to_same_book() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
+
+        // Same book as ISBN-10 is a duplicate
+        let mut book = create_sample_book();
+        book.isbn = "0451524934".to_string();
+        assert_eq!(contract.add_book(book), Err(BookyError::DuplicateIsbn));
+
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        let book = contract.get_book(alice.clone(), "9780451524935".to_string(), None).unwrap();
+        assert_eq!(book.isbn, "9780451524935");
+        assert!(contract.get_book(alice.clone(), "0-451-52493-4".to_string(), None).is_some());
+        assert!(contract.get_book(alice.clone(), "garbage".to_string(), None).is_none());
+
+        contract.add_chapter_note("0451524934".to_string(), 1, "War is peace".into()).unwrap();
+        assert_eq!(
+            contract.get_chapter_note(alice.clone(), "978 0 451 52493 5".to_string(), 1, None),
+            Some("War is peace".into())
+        );
+
+        contract.delete_book("0-451-52493-4".to_string()).unwrap();
+        assert_eq!(contract.get_total_books(), U64(0));
+    }
+
+    #[test]
+    fn migrate_isbns_rekeys_and_reports_collisions() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        // Entries as stored before normalization
+        for isbn in ["978-0451524935", "0451524934", "not-an-isbn", "9780061120084"] {
+            let mut book = create_sample_book();
+            book.isbn = isbn.to_string();
+            contract.insert_book(alice.clone(), book);
+        }
+        // Ratings of legacy entries are tallied under the canonical ISBN, and stay there
+        contract.rate_book("978-0451524935".to_string(), Some(8)).unwrap();
+        assert!(contract.rating_tallies.get("9780451524935").is_some());
+
+        let result = contract.migrate_isbns(vec![alice.clone()]);
+        assert!(matches!(result, Err(BookyError::NotOwner(account_id)) if account_id == alice));
+        testing_env!(get_context("owner.testnet".parse().unwrap()).build());
+        let reports = contract.migrate_isbns(vec![alice.clone(), "nobody.testnet".parse().unwrap()]).unwrap();
+        assert_eq!(reports.len(), 2);
+        let report = &reports[0];
+        assert_eq!(report.rekeyed, 1);
+        assert_eq!(report.collisions.len(), 1);
+        assert_eq!(report.collisions[0].canonical_isbn, "9780451524935");
+        assert_eq!(report.invalid, vec!["not-an-isbn".to_string()]);
+        assert_eq!(reports[1].rekeyed, 0);
+        let events = event_logs();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["event"], "isbn_rekeyed");
+        assert_eq!(events[0]["data"], serde_json::json!({
+            "account_id": "alice.testnet",
+            "old_isbn": "978-0451524935",
+            "isbn": "9780451524935",
+        }));
+
+        assert_eq!(contract.get_total_books(), U64(4));
+        let rekeyed = contract.find_book(&alice, "9780451524935").unwrap();
+        assert_eq!((rekeyed.isbn.as_str(), rekeyed.rating), ("9780451524935", Some(8)));
+        assert!(contract.rating_tallies.get("9780451524935").is_some());
+        assert!(contract.rating_tallies.get("978-0451524935").is_none());
+        // Colliding and invalid entries stay reachable by their old key
+        assert_eq!(contract.get_book(alice.clone(), "0451524934".to_string(), None).unwrap().isbn, "0451524934");
+        assert!(contract.get_book(alice, "not-an-isbn".to_string(), None).is_some());
+    }
+
+    #[test]
+    fn book_error_codes() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        let isbn = "978-0451524935".to_string();
+
+        assert_eq!(contract.delete_book(isbn.clone()), Err(BookyError::LibraryNotFound));
+        assert_eq!(contract.update_book(isbn.clone(), create_sample_book()), Err(BookyError::LibraryNotFound));
+        assert_eq!(contract.start_reading(isbn.clone(), None), Err(BookyError::LibraryNotFound));
+
+        contract.add_book(create_sample_book()).unwrap();
+        let missing = "978-0061120084".to_string();
+        assert_eq!(contract.delete_book(missing.clone()), Err(BookyError::BookNotFound));
+        assert_eq!(contract.update_book(missing.clone(), create_sample_book()), Err(BookyError::BookNotFound));
+        assert_eq!(contract.mark_completed(missing.clone()), Err(BookyError::BookNotFound));
+        assert_eq!(contract.delete_chapter_note(missing.clone(), 1), Err(BookyError::BookNotFound));
+        assert_eq!(contract.add_chapter_note(missing, 1, "Note".into()), Err(BookyError::BookNotFound));
+
+        // Moving a book onto an ISBN already in the library
+        let mut book2 = create_sample_book();
+        book2.isbn = "978-0061120084".to_string();
+        contract.add_book(book2.clone()).unwrap();
+        assert_eq!(contract.update_book(isbn.clone(), book2), Err(BookyError::DuplicateIsbn));
+
+        let mut bad = create_sample_book();
+        bad.isbn = "123".to_string();
+        let err = contract.update_book(isbn.clone(), bad).unwrap_err();
+        assert_eq!(err.code(), "InvalidIsbn");
+
+        assert_eq!(
+            contract.add_chapter_note(isbn.clone(), 11, "Note".into()),
+            Err(BookyError::ChapterOutOfRange { chapter: 11, total_chapters: Some(10) })
+        );
+        let mut open_ended = create_sample_book();
+        open_ended.total_chapters = None;
+        contract.update_book(isbn.clone(), open_ended).unwrap();
+        assert_eq!(
+            contract.add_chapter_note(isbn, 0, "Note".into()),
+            Err(BookyError::ChapterOutOfRange { chapter: 0, total_chapters: None })
+        );
+    }
+
+    #[test]
+    fn follow_error_codes() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        let bob: AccountId = "bob.testnet".parse().unwrap();
+
+        assert_eq!(contract.follow_account("alice.testnet".parse().unwrap()), Err(BookyError::SelfFollow));
+        assert_eq!(contract.unfollow_account(bob.clone()), Err(BookyError::NotFollowing(bob.clone())));
+
+        contract.follow_account(bob.clone()).unwrap();
+        // Following twice is a no-op rather than an error
+        contract.follow_account(bob).unwrap();
+        let carol: AccountId = "carol.testnet".parse().unwrap();
+        assert_eq!(contract.unfollow_account(carol.clone()), Err(BookyError::NotFollowing(carol)));
+    }
+
+    #[test]
+    fn storage_error_codes() {
+        let mut context = get_context("alice.testnet".parse().unwrap());
+        context.attached_deposit(NearToken::from_yoctonear(1));
+        testing_env!(context.build());
+
+        let mut contract = Contract::default();
+        let min = contract.storage_balance_bounds().min;
+        assert_eq!(contract.storage_deposit(None, None), Err(BookyError::InsufficientDeposit { minimum: min }));
+        assert_eq!(
+            contract.storage_withdraw(None),
+            Err(BookyError::NotRegistered("alice.testnet".parse().unwrap()))
+        );
+
+        let mut context = get_context("alice.testnet".parse().unwrap());
+        context.attached_deposit(NearToken::from_near(1));
+        testing_env!(context.build());
+        contract.storage_deposit(None, None).unwrap();
+        // Anything other than exactly one yoctoNEAR is rejected
+        assert_eq!(contract.storage_withdraw(None), Err(BookyError::OneYoctoRequired));
+        assert_eq!(contract.storage_unregister(None), Err(BookyError::OneYoctoRequired));
+
+        let mut context = get_context("alice.testnet".parse().unwrap());
+        context.attached_deposit(NearToken::from_yoctonear(1));
+        testing_env!(context.build());
+        let available = contract.storage_balance_of("alice.testnet".parse().unwrap()).unwrap().available;
+        assert_eq!(
+            contract.storage_withdraw(Some(U128(available.0 + 1))),
+            Err(BookyError::WithdrawExceedsAvailable { available })
+        );
+    }
+
+    #[test]
+    fn error_message_starts_with_code() {
+        let err = BookyError::ChapterOutOfRange { chapter: 12, total_chapters: Some(10) };
+        assert_eq!(err.code(), "ChapterOutOfRange");
+        assert_eq!(err.to_string(), "ChapterOutOfRange: Chapter number 12 exceeds total chapters 10");
+        assert_eq!(
+            BookyError::NotFollowing("bob.testnet".parse().unwrap()).to_string(),
+            "NotFollowing: Not following bob.testnet"
+        );
+    }
+
+    #[test]
+    fn oversized_and_malformed_fields_rejected() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        let limits = contract.get_field_limits();
+
+        let mut book = create_sample_book();
+        book.title = "x".repeat(limits.max_title_len as usize + 1);
+        assert_eq!(
+            contract.add_book(book),
+            Err(BookyError::FieldTooLong { field: "title", max_length: limits.max_title_len })
+        );
+
+        let mut book = create_sample_book();
+        book.acquisition_date = "15/01/2024".to_string();
+        assert_eq!(
+            contract.add_book(book),
+            Err(BookyError::InvalidDate { field: "acquisition_date", value: "15/01/2024".to_string() })
+        );
+
+        let mut book = create_sample_book();
+        book.total_chapters = Some(limits.max_total_chapters + 1);
+        assert_eq!(contract.add_book(book).unwrap_err().code(), "TooManyChapters");
+
+        contract.add_book(create_sample_book()).unwrap();
+        let isbn = "978-0451524935".to_string();
+        let long_note = "x".repeat(limits.max_note_len as usize + 1);
+        assert_eq!(
+            contract.add_chapter_note(isbn.clone(), 1, long_note.into()),
+            Err(BookyError::FieldTooLong { field: "note", max_length: limits.max_note_len })
+        );
+        assert_eq!(
+            contract.start_reading(isbn, Some(11)),
+            Err(BookyError::ChapterOutOfRange { chapter: 11, total_chapters: Some(10) })
+        );
+    }
+
+    #[test]
+    fn progress_update_validated_against_book() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
+        let isbn = "978-0451524935".to_string();
+        let progress = |chapters_completed: Vec<u32>, last_read_date: &str| ProgressUpdate {
+            current_chapter: None,
+            chapters_completed,
+            last_read_position: None,
+            last_read_date: Some(last_read_date.to_string()),
+            reading_status: None,
+            minutes_read: None,
+            position: None,
+        };
+
+        assert_eq!(
+            contract.update_reading_progress(isbn.clone(), progress(vec![9, 10, 11], "2024-12-22")),
+            Err(BookyError::ChapterOutOfRange { chapter: 11, total_chapters: Some(10) })
+        );
+        assert_eq!(
+            contract.update_reading_progress(isbn.clone(), progress(vec![1], "yesterday")),
+            Err(BookyError::InvalidDate { field: "last_read_date", value: "yesterday".to_string() })
+        );
+        contract.update_reading_progress(isbn.clone(), progress(vec![9, 10], "2024-12-22T21:15:00Z")).unwrap();
+
+        // Completing a book records the block date in ISO form
+        contract.mark_completed(isbn.clone()).unwrap();
+        let book = contract.get_book("alice.testnet".parse().unwrap(), isbn, None).unwrap();
+        assert!(validation::is_iso_date(book.last_read_date.as_deref().unwrap()));
+    }
+
+    #[test]
+    fn field_limits_configurable() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        let limits = FieldLimits { max_comments_len: 10, ..FieldLimits::default() };
+        assert_eq!(contract.set_field_limits(limits), Err(BookyError::NotOwner("alice.testnet".parse().unwrap())));
+
+        testing_env!(get_context("owner.testnet".parse().unwrap()).build());
+        contract.set_field_limits(limits).unwrap();
+        assert_eq!(contract.get_field_limits().max_comments_len, 10);
+
+        testing_env!(get_context("alice.testnet".parse().unwrap()).build());
+
+        assert_eq!(
+            contract.add_book(create_sample_book()),
+            Err(BookyError::FieldTooLong { field: "personal_comments", max_length: 10 })
+        );
+        let mut book = create_sample_book();
+        book.personal_comments = "Relevant".into();
+        contract.add_book(book).unwrap();
+    }
+
+    #[test]
+    fn ownership_transfer_takes_two_steps() {
+        let context = get_context("owner.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        let owner: AccountId = "owner.testnet".parse().unwrap();
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        assert_eq!(contract.get_owner(), owner);
+
+        contract.propose_owner(alice.clone()).unwrap();
+        assert_eq!(contract.get_pending_owner(), Some(alice.clone()));
+        // Proposing alone does not hand over control
+        assert_eq!(contract.get_owner(), owner);
+
+        testing_env!(get_context("bob.testnet".parse().unwrap()).build());
+        assert_eq!(contract.accept_ownership(), Err(BookyError::NotPendingOwner("bob.testnet".parse().unwrap())));
+        assert_eq!(
+            contract.propose_owner("bob.testnet".parse().unwrap()),
+            Err(BookyError::NotOwner("bob.testnet".parse().unwrap()))
+        );
+
+        testing_env!(get_context(alice.clone()).build());
+        contract.accept_ownership().unwrap();
+        assert_eq!(contract.get_owner(), alice);
+        assert_eq!(contract.get_pending_owner(), None);
+
+        let events = event_logs();
+        assert_eq!(events[0]["event"], "ownership_transferred");
+        assert_eq!(events[0]["data"]["previous_owner_id"], "owner.testnet");
+        assert_eq!(events[0]["data"]["owner_id"], "alice.testnet");
+    }
+
+    #[test]
+    fn roles_granted_and_revoked_by_owner() {
+        let context = get_context("owner.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+
+        contract.grant_role(alice.clone(), Role::Moderator).unwrap();
+        assert!(contract.has_role(alice.clone(), Role::Moderator));
+        assert!(!contract.has_role(alice.clone(), Role::Pauser));
+        // The owner holds every role without being listed
+        assert!(contract.has_role("owner.testnet".parse().unwrap(), Role::Pauser));
+        assert_eq!(contract.get_role_members(Role::Moderator, None, None).items, vec![alice.clone()]);
+        assert_eq!(event_logs()[0]["event"], "role_granted");
+
+        testing_env!(get_context(alice.clone()).build());
+        assert_eq!(
+            contract.grant_role(alice.clone(), Role::Pauser),
+            Err(BookyError::NotOwner(alice.clone()))
+        );
+        assert_eq!(
+            contract.pause(),
+            Err(BookyError::MissingRole { account_id: alice.clone(), role: Role::Pauser })
+        );
+
+        testing_env!(get_context("owner.testnet".parse().unwrap()).build());
+        contract.revoke_role(alice.clone(), Role::Moderator).unwrap();
+        assert!(!contract.has_role(alice, Role::Moderator));
+        assert_eq!(contract.get_role_members(Role::Moderator, None, None).total, 0);
+    }
+
+    #[test]
+    fn pause_blocks_writes_but_not_views() {
+        let context = get_context("owner.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        contract.grant_role("bob.testnet".parse().unwrap(), Role::Pauser).unwrap();
+
+        testing_env!(get_context("alice.testnet".parse().unwrap()).build());
+        contract.add_book(create_sample_book()).unwrap();
+
+        testing_env!(get_context("bob.testnet".parse().unwrap()).build());
+        contract.pause().unwrap();
+        assert!(contract.is_paused());
+
+        testing_env!(get_context("alice.testnet".parse().unwrap()).build());
+        let isbn = "978-0451524935".to_string();
+        assert_eq!(contract.start_reading(isbn.clone(), None), Err(BookyError::Paused));
+        assert_eq!(contract.delete_book(isbn.clone()), Err(BookyError::Paused));
+        assert_eq!(contract.follow_account("bob.testnet".parse().unwrap()), Err(BookyError::Paused));
+        assert_eq!(contract.storage_deposit(None, None), Err(BookyError::Paused));
+        assert!(contract.get_book("alice.testnet".parse().unwrap(), isbn.clone(), None).is_some());
+        assert_eq!(contract.get_total_books(), U64(1));
+
+        testing_env!(get_context("bob.testnet".parse().unwrap()).build());
+        contract.unpause().unwrap();
+        testing_env!(get_context("alice.testnet".parse().unwrap()).build());
+        contract.start_reading(isbn, None).unwrap();
+    }
+
+    #[test]
+    fn migrate_keeps_versioned_state() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
+        env::state_write(&contract);
+        drop(contract);
+
+        let state = env::storage_read(b"STATE").unwrap();
+        assert!(state.starts_with(b"BKY\0"));
+
+        let contract = Contract::migrate();
+        assert_eq!(contract.get_state_version(), CURRENT_STATE_VERSION);
+        assert_eq!(contract.get_owner(), "owner.testnet".parse::<AccountId>().unwrap());
+        assert_eq!(contract.get_total_books(), U64(1));
+        assert!(contract.get_book("alice.testnet".parse().unwrap(), "978-0451524935".to_string(), None).is_some());
+    }
+
+    #[test]
+    fn migrate_from_version_1_state() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let bob: AccountId = "bob.testnet".parse().unwrap();
+        testing_env!(get_context(bob.clone()).build());
+        let mut contract = setup_contract();
+        contract.set_follow_approval(true).unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        contract.follow_account(bob.clone()).unwrap();
+        contract.add_book(create_sample_book()).unwrap();
+
+        // Store the book, and leave the request unindexed, the way version 1 did
+        contract.sent_follow_requests.remove(&alice).unwrap().clear();
+        contract.sent_follow_requests.flush();
+        let sample = create_sample_book();
+        let v2 = migrate::BookEntryV2 {
+            isbn: "9780451524935".to_string(),
+            title: sample.title,
+            author: sample.author,
+            acquisition_date: sample.acquisition_date,
+            condition: sample.condition,
+            personal_comments: sample.personal_comments,
+            media_hash: None,
+            reading_status: ReadingStatus::Completed,
+            current_chapter: 3,
+            total_chapters: Some(10),
+            chapters_read: HashSet::new(),
+            last_read_position: String::new(),
+            last_read_date: None,
+            added_at: U64(100),
+            started_at: Some(U64(200)),
+            completed_at: Some(U64(300)),
+            updated_at: U64(300),
+            chapter_notes: HashMap::new(),
+            privacy: None,
+        };
+        let library = contract.libraries.get_mut(&alice).unwrap();
+        library.insert("9780451524935".to_string(), VersionedBookEntry::V2(v2));
+
+        let limits = FieldLimits::default();
+        let mut state_version = StateVersion::current();
+        state_version.version = 1;
+        env::state_write(&migrate::ContractV1 {
+            state_version,
+            libraries: contract.libraries,
+            followed_accounts: contract.followed_accounts,
+            followers: contract.followers,
+            follow_approval: contract.follow_approval,
+            follow_requests: contract.follow_requests,
+            blocked_accounts: contract.blocked_accounts,
+            muted_accounts: contract.muted_accounts,
+            activity: contract.activity,
+            reading_sessions: contract.reading_sessions,
+            privacy_settings: contract.privacy_settings,
+            note_public_keys: contract.note_public_keys,
+            total_books: contract.total_books,
+            storage_accounts: contract.storage_accounts,
+            field_limits: migrate::FieldLimitsV1 {
+                max_title_len: 100,
+                max_author_len: limits.max_author_len,
+                max_condition_len: limits.max_condition_len,
+                max_comments_len: limits.max_comments_len,
+                max_media_hash_len: limits.max_media_hash_len,
+                max_position_len: limits.max_position_len,
+                max_note_len: limits.max_note_len,
+                max_total_chapters: limits.max_total_chapters,
+                max_note_recipients: limits.max_note_recipients,
+                max_session_minutes: limits.max_session_minutes,
+            },
+            access: contract.access,
+        });
+
+        let mut contract = Contract::migrate();
+        assert_eq!(contract.get_state_version(), CURRENT_STATE_VERSION);
+        assert_eq!(contract.get_owner(), "owner.testnet".parse::<AccountId>().unwrap());
+        assert_eq!(contract.get_field_limits(), FieldLimits { max_title_len: 100, ..limits });
+        assert_eq!(contract.get_total_books(), U64(1));
+        let book = contract.get_book(alice.clone(), "9780451524935".to_string(), None).unwrap();
+        assert_eq!((book.current_chapter, book.completed_at, book.rating), (3, Some(U64(300)), None));
+        assert!(book.reading_cycles.is_empty());
+        assert_eq!(contract.get_follow_requests(bob.clone(), None, None).items, vec![alice.clone()]);
+
+        // Requests pending from before the index are indexed on request
+        assert!(contract.sent_follow_requests.get(&alice).is_none());
+        testing_env!(get_context("owner.testnet".parse().unwrap()).build());
+        assert_eq!(contract.migrate_follow_requests(vec![bob.clone(), alice.clone()]), Ok(1));
+        assert_eq!(contract.migrate_follow_requests(vec![bob.clone()]), Ok(0));
+        assert!(contract.sent_follow_requests.get(&alice).unwrap().contains(&bob));
+        testing_env!(get_context(alice.clone()).build());
+        assert!(matches!(contract.migrate_follow_requests(vec![bob]), Err(BookyError::NotOwner(_))));
+    }
+
+    #[test]
+    fn upgrade_deploys_code_and_calls_migrate() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+        let mut contract = setup_contract();
+        assert!(matches!(contract.upgrade(), Err(BookyError::NotOwner(_))));
+
+        testing_env!(get_context("owner.testnet".parse().unwrap()).build());
+        assert!(matches!(contract.upgrade(), Err(BookyError::NoCodeAttached)));
+
+        let mut context = get_context("owner.testnet".parse().unwrap());
+        context.context.input = b"\0asm new code".to_vec().into();
+        testing_env!(context.build());
+        assert!(contract.upgrade().is_ok());
+
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].receiver_id, env::current_account_id());
+        let actions = &receipts[0].actions;
+        assert!(matches!(&actions[0], MockAction::DeployContract { code, .. } if code == b"\0asm new code"));
+        assert!(matches!(
+            &actions[1],
+            MockAction::FunctionCallWeight { method_name, prepaid_gas, .. }
+                if method_name == b"migrate" && prepaid_gas.as_tgas() == 50
+        ));
+    }
+
+    /// Check that every follow appears in both `followed_accounts` and `followers`
+    fn assert_follow_indexes_consistent(contract: &Contract, accounts: &[AccountId]) {
+        for a in accounts {
+            let followed = contract.get_followed_accounts(a.clone(), None, None).items;
+            let followers = contract.get_followers(a.clone(), None, None).items;
+            assert_eq!(contract.get_follower_count(a.clone()), followers.len() as u32);
+            for b in accounts {
+                let forward = followed.contains(b);
+                assert_eq!(forward, contract.is_following(a.clone(), b.clone()));
+                let reverse = contract.get_followers(b.clone(), None, None).items.contains(a);
+                assert_eq!(forward, reverse, "{} -> {} drifted", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn follower_index_never_drifts() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        let accounts: Vec<AccountId> = ["alice.testnet", "bob.testnet", "carol.testnet", "dave.testnet"]
+            .iter()
+            .map(|a| a.parse().unwrap())
+            .collect();
+        for account in &accounts[2..] {
+            let mut context = get_context(account.clone());
+            context.attached_deposit(NearToken::from_near(1));
+            testing_env!(context.build());
+            contract.storage_deposit(None, None).unwrap();
+        }
+
+        // Deterministic pseudo-random follows and unfollows
+        let mut seed: u64 = 42;
+        for _ in 0..200 {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let a = &accounts[(seed >> 33) as usize % accounts.len()];
+            let b = &accounts[(seed >> 40) as usize % accounts.len()];
+            testing_env!(get_context(a.clone()).build());
+            if (seed >> 50) % 2 == 0 {
+                let _ = contract.follow_account(b.clone());
+            } else {
+                let _ = contract.unfollow_account(b.clone());
+            }
+            assert_follow_indexes_consistent(&contract, &accounts);
+        }
+
+        // Force-unregistering removes the account's follows in both directions
+        for b in &accounts[1..] {
+            testing_env!(get_context(accounts[0].clone()).build());
+            let _ = contract.follow_account(b.clone());
+        }
+        let mut context = get_context(accounts[0].clone());
+        context.attached_deposit(NearToken::from_yoctonear(1));
+        testing_env!(context.build());
+        contract.storage_unregister(Some(true)).unwrap();
+        assert_follow_indexes_consistent(&contract, &accounts);
+        for b in &accounts[1..] {
+            assert!(!contract.get_followers(b.clone(), None, None).items.contains(&accounts[0]));
+        }
+    }
+
+    #[test]
+    fn followers_view_and_storage_refund() {
+        let context = get_context("alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = setup_contract();
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        let bob: AccountId = "bob.testnet".parse().unwrap();
+        let initial = contract.storage_balance_of(alice.clone()).unwrap().available.0;
+
+        contract.follow_account(bob.clone()).unwrap();
+        assert_eq!(contract.get_followers(bob.clone(), None, None).items, vec![alice.clone()]);
+        assert_eq!(contract.get_follower_count(bob.clone()), 1);
+        assert!(contract.is_following(alice.clone(), bob.clone()));
+        assert!(!contract.is_following(bob.clone(), alice.clone()));
+        // The follower pays for both index entries
+        assert!(contract.storage_balance_of(alice.clone()).unwrap().available.0 < initial);
+
+        contract.unfollow_account(bob.clone()).unwrap();
+        assert_eq!(contract.get_follower_count(bob.clone()), 0);
+        assert_eq!(contract.get_followers(bob, None, None).total, 0);
+        assert_eq!(contract.storage_balance_of(alice).unwrap().available.0, initial);
+    }
+
+    #[test]
+    fn privacy_enforced_for_each_viewer() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        let bob: AccountId = "bob.testnet".parse().unwrap();
+        let carol: AccountId = "carol.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = setup_contract();
+
+        let mut book2 = create_sample_book();
+        book2.isbn = "978-0061120084".to_string();
+        book2.privacy = Some(PrivacySettings { visibility: Visibility::FollowersOnly, ..Default::default() });
+        contract.add_book(create_sample_book()).unwrap();
+        contract.add_book(book2).unwrap();
+        contract.add_chapter_note("9780451524935".to_string(), 1, "Big Brother".into()).unwrap();
+        contract
+            .set_library_privacy(PrivacySettings { hide_notes: true, hide_comments: true, ..Default::default() })
+            .unwrap();
+
+        testing_env!(get_context(bob.clone()).build());
+        contract.follow_account(alice.clone()).unwrap();
+
+        let library = |viewer: Option<&AccountId>| contract.get_library(alice.clone(), None, None, None, viewer.cloned());
+        assert_eq!(library(Some(&alice)).total, 2);
+        assert_eq!(library(Some(&bob)).total, 2);
+        assert_eq!(library(Some(&carol)).total, 1);
+        assert_eq!(library(None).total, 1);
+        assert_eq!(contract.get_user_stats(alice.clone(), None).total_books, 1);
+        assert!(contract.get_book(alice.clone(), "978-0061120084".to_string(), Some(carol.clone())).is_none());
+
+        // Hidden fields are kept for the owner only
+        let own = contract.get_book(alice.clone(), "9780451524935".to_string(), Some(alice.clone())).unwrap();
+        assert_eq!(own.personal_comments, "Still relevant today".into());
+        let shown = contract.get_book(alice.clone(), "9780451524935".to_string(), Some(bob.clone())).unwrap();
+        assert!(shown.personal_comments.is_empty());
+        assert!(shown.chapter_notes.is_empty());
+        assert_eq!(contract.get_chapter_note(alice.clone(), "9780451524935".to_string(), 1, None), None);
+
+        testing_env!(get_context(alice.clone()).build());
+        contract
+            .set_library_privacy(PrivacySettings { visibility: Visibility::Private, ..Default::default() })
+            .unwrap();
+        assert_eq!(contract.get_library(alice.clone(), None, None, None, Some(bob.clone())).total, 0);
+        assert_eq!(contract.get_reading_stats(alice.clone(), Some(bob.clone())).total_books, 0);
+        let details = contract.get_followed_accounts_with_details(bob.clone(), None, None, None, Some(bob));
+        assert_eq!(details.items[0].library.total, 0);
+        assert_eq!(contract.get_library(alice.clone(), None, None, None, Some(alice)).total, 2);
+    }
+
+    #[test]
+    fn owner_round_trips_hidden_fields_through_update_book() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = setup_contract();
+        let isbn = "9780451524935".to_string();
+        contract.add_book(create_sample_book()).unwrap();
+        contract.add_chapter_note(isbn.clone(), 1, "Big Brother".into()).unwrap();
+        contract
+            .set_library_privacy(PrivacySettings { hide_notes: true, hide_comments: true, ..Default::default() })
+            .unwrap();
+
+        // The web client edits the book it read as the owner and sends it back
+        let mut book = contract.get_book(alice.clone(), isbn.clone(), Some(alice.clone())).unwrap();
+        book.condition = "Worn".to_string();
+        contract.update_book(isbn.clone(), book).unwrap();
+
+        let stored = contract.get_book(alice.clone(), isbn.clone(), Some(alice.clone())).unwrap();
+        assert_eq!(stored.condition, "Worn");
+        assert_eq!(stored.personal_comments, "Still relevant today".into());
+        assert_eq!(stored.chapter_notes.get(&1), Some(&"Big Brother".into()));
+        assert_eq!(contract.get_all_chapter_notes(alice.clone(), isbn, Some(alice)).len(), 1);
+    }
+
+    #[test]
+    fn events_withhold_non_public_data() {
+        testing_env!(get_context("alice.testnet".parse().unwrap()).build());
+        let mut contract = setup_contract();
+        let isbn = "9780451524935".to_string();
+
+        contract
+            .set_library_privacy(PrivacySettings { hide_notes: true, ..Default::default() })
+            .unwrap();
+        contract.add_book(create_sample_book()).unwrap();
+        contract.add_chapter_note(isbn.clone(), 1, "Secret".into()).unwrap();
+        contract
+            .set_book_privacy(isbn.clone(), Some(PrivacySettings { visibility: Visibility::Private, ..Default::default() }))
+            .unwrap();
+        contract.start_reading(isbn.clone(), None).unwrap();
+
+        // Nothing at all is published about the book while it is private
+        contract.add_chapter_note(isbn.clone(), 2, "Also secret".into()).unwrap();
+        contract.delete_chapter_note(isbn.clone(), 1).unwrap();
+        let private = Some(PrivacySettings { visibility: Visibility::Private, ..Default::default() });
+        let updated = BookEntry { condition: "Worn".to_string(), privacy: private, ..create_sample_book() };
+        contract.update_book(isbn.clone(), updated).unwrap();
+        contract.delete_book(isbn).unwrap();
+        let added = BookEntry { isbn: "978-0-306-40615-7".to_string(), privacy: private, ..create_sample_book() };
+        contract.add_book(added).unwrap();
+
+        let events = event_logs();
+        let names: Vec<&str> = events.iter().map(|e| e["event"].as_str().unwrap()).collect();
+        assert_eq!(names, vec!["library_privacy_changed", "book_added", "note_added", "book_updated"]);
+        assert_eq!(events[1]["data"]["book"]["title"], "1984");
+        assert_eq!(events[2]["data"]["note"], serde_json::Value::Null);
+        assert_eq!(events[3]["data"]["isbn"], "9780451524935");
+        assert_eq!(events[3]["data"]["book"], serde_json::Value::Null);
+    }
+
+    fn sample_envelope(recipients: &[&str]) -> EncryptedEnvelope {
+        EncryptedEnvelope {
+            algorithm: "x25519-xsalsa20-poly1305".to_string(),
+            nonce: vec![7; 24].into(),
+            ciphertext: vec![1, 2, 3, 4].into(),
+            recipients: recipients
+                .iter()
+                .map(|account| RecipientKey { account_id: account.parse().unwrap(), wrapped_key: vec![9; 48].into() })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn notes_accept_plain_text_or_envelope_json() {
+        let plain: NoteContent = serde_json::from_str(r#""Plain note""#).unwrap();
+        assert_eq!(plain, "Plain note".into());
+
+        let json = serde_json::to_string(&NoteContent::Encrypted(sample_envelope(&["alice.testnet"]))).unwrap();
+        assert!(json.starts_with(r#"{"algorithm":"#));
+        let encrypted: NoteContent = serde_json::from_str(&json).unwrap();
+        assert_eq!(encrypted, NoteContent::Encrypted(sample_envelope(&["alice.testnet"])));
+    }
+
+    #[test]
+    fn encrypted_envelope_validated() {
+        testing_env!(get_context("alice.testnet".parse().unwrap()).build());
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
+        let isbn = "9780451524935".to_string();
+        let note = |envelope| NoteContent::Encrypted(envelope);
+
+        let bad_nonce = EncryptedEnvelope { nonce: Vec::new().into(), ..sample_envelope(&[]) };
+        assert_eq!(
+            contract.add_chapter_note(isbn.clone(), 1, note(bad_nonce)),
+            Err(BookyError::InvalidEnvelope { field: "note", reason: "nonce must be between 1 and 64 bytes" })
+        );
+        let oversized = EncryptedEnvelope { ciphertext: vec![0; 4097].into(), ..sample_envelope(&[]) };
+        assert_eq!(
+            contract.add_chapter_note(isbn.clone(), 1, note(oversized)),
+            Err(BookyError::FieldTooLong { field: "note", max_length: 4096 })
+        );
+        assert!(matches!(
+            contract.add_chapter_note(isbn.clone(), 1, note(sample_envelope(&["bob.testnet", "bob.testnet"]))),
+            Err(BookyError::InvalidEnvelope { .. })
+        ));
+        let crowd: Vec<String> = (0..17).map(|i| format!("reader{}.testnet", i)).collect();
+        let crowd: Vec<&str> = crowd.iter().map(String::as_str).collect();
+        assert_eq!(
+            contract.add_chapter_note(isbn.clone(), 1, note(sample_envelope(&crowd))),
+            Err(BookyError::TooManyRecipients { max: 16 })
+        );
+
+        let mut book = create_sample_book();
+        book.isbn = "978-0061120084".to_string();
+        book.personal_comments = note(EncryptedEnvelope { algorithm: String::new(), ..sample_envelope(&[]) });
+        assert!(matches!(
+            contract.add_book(book),
+            Err(BookyError::InvalidEnvelope { field: "personal_comments", .. })
+        ));
+    }
+
+    #[test]
+    fn share_note_key_grants_recipient() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        let bob: AccountId = "bob.testnet".parse().unwrap();
+        testing_env!(get_context(bob.clone()).build());
+        let mut contract = setup_contract();
+        assert_eq!(
+            contract.set_note_public_key(Some(vec![0; 129].into())),
+            Err(BookyError::InvalidPublicKey { max_length: 128 })
+        );
+        contract.set_note_public_key(Some(vec![5; 32].into())).unwrap();
+        assert_eq!(contract.get_note_public_key(bob.clone()), Some(vec![5; 32].into()));
+
+        testing_env!(get_context(alice.clone()).build());
+        contract.add_book(create_sample_book()).unwrap();
+        let isbn = "9780451524935".to_string();
+        contract.add_chapter_note(isbn.clone(), 1, "Plain".into()).unwrap();
+        contract.add_chapter_note(isbn.clone(), 2, NoteContent::Encrypted(sample_envelope(&["alice.testnet"]))).unwrap();
+
+        let wrapped: Base64VecU8 = vec![3; 48].into();
+        assert_eq!(
+            contract.share_note_key(isbn.clone(), 2, "carol.testnet".parse().unwrap(), wrapped.clone()),
+            Err(BookyError::NoPublicKey("carol.testnet".parse().unwrap()))
+        );
+        assert_eq!(
+            contract.share_note_key(isbn.clone(), 1, bob.clone(), wrapped.clone()),
+            Err(BookyError::NoteNotEncrypted { chapter: 1 })
+        );
+        assert_eq!(
+            contract.share_note_key(isbn.clone(), 3, bob.clone(), wrapped.clone()),
+            Err(BookyError::NoteNotFound { chapter: 3 })
+        );
+
+        contract.share_note_key(isbn.clone(), 2, bob.clone(), wrapped.clone()).unwrap();
+        // Sharing again replaces the wrapped key instead of adding a recipient
+        contract.share_note_key(isbn.clone(), 2, bob.clone(), wrapped.clone()).unwrap();
+        match contract.get_chapter_note(alice, isbn, 2, None) {
+            Some(NoteContent::Encrypted(envelope)) => {
+                assert_eq!(envelope.recipients.len(), 2);
+                assert_eq!(envelope.recipients[1], RecipientKey { account_id: bob, wrapped_key: wrapped });
+            }
+            other => panic!("expected an encrypted note, got {:?}", other),
+        }
+        assert_eq!(event_logs().last().unwrap()["event"], "note_key_shared");
+    }
+
+    #[test]
+    fn follow_requests_need_approval() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        let bob: AccountId = "bob.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
+        contract.set_follow_approval(true).unwrap();
+        contract
+            .set_library_privacy(PrivacySettings { visibility: Visibility::FollowersOnly, ..Default::default() })
+            .unwrap();
+
+        testing_env!(get_context(bob.clone()).build());
+        let initial = contract.storage_balance_of(bob.clone()).unwrap().available.0;
+        contract.follow_account(alice.clone()).unwrap();
+        assert!(!contract.is_following(bob.clone(), alice.clone()));
+        assert_eq!(contract.get_follow_requests(alice.clone(), None, None).items, vec![bob.clone()]);
+        // Pending requests don't unlock followers-only data
+        assert_eq!(contract.get_user_library(alice.clone(), None, None, None, Some(bob.clone())).total, 0);
+
+        testing_env!(get_context(alice.clone()).build());
+        contract.reject_follow(bob.clone()).unwrap();
+        assert_eq!(contract.get_follow_requests(alice.clone(), None, None).total, 0);
+        assert_eq!(contract.storage_balance_of(bob.clone()).unwrap().available.0, initial);
+        assert_eq!(contract.approve_follow(bob.clone()), Err(BookyError::FollowRequestNotFound(bob.clone())));
+
+        testing_env!(get_context(bob.clone()).build());
+        contract.follow_account(alice.clone()).unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        contract.approve_follow(bob.clone()).unwrap();
+        assert!(contract.is_following(bob.clone(), alice.clone()));
+        assert_eq!(contract.get_user_library(alice.clone(), None, None, None, Some(bob.clone())).total, 1);
+        assert_follow_indexes_consistent(&contract, &[alice.clone(), bob.clone()]);
+
+        contract.remove_follower(bob.clone()).unwrap();
+        assert!(!contract.is_following(bob.clone(), alice.clone()));
+        assert_eq!(contract.remove_follower(bob.clone()), Err(BookyError::NotAFollower(bob.clone())));
+        assert_eq!(contract.storage_balance_of(bob).unwrap().available.0, initial);
+    }
+
+    #[test]
+    fn turning_off_follow_approval_approves_pending_requests() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        let bob: AccountId = "bob.testnet".parse().unwrap();
+        let carol: AccountId = "carol.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = setup_contract();
+        contract.set_follow_approval(true).unwrap();
+
+        let mut context = get_context(carol.clone());
+        context.attached_deposit(NearToken::from_near(1));
+        testing_env!(context.build());
+        contract.storage_deposit(None, None).unwrap();
+        contract.follow_account(alice.clone()).unwrap();
+        let mut context = get_context(carol.clone());
+        context.attached_deposit(NearToken::from_yoctonear(1));
+        testing_env!(context.build());
+        contract.storage_withdraw(None).unwrap();
+        let carol_used = contract.storage_accounts.get(&carol).unwrap().used_bytes;
+
+        testing_env!(get_context(bob.clone()).build());
+        let bob_used = contract.storage_accounts.get(&bob).unwrap().used_bytes;
+        contract.follow_account(alice.clone()).unwrap();
+
+        testing_env!(get_context(alice.clone()).build());
+        contract.set_follow_approval(false).unwrap();
+        assert!(!contract.requires_follow_approval(alice.clone()));
+        assert_eq!(contract.get_follow_requests(alice.clone(), None, None).total, 0);
+        assert!(contract.is_following(bob.clone(), alice.clone()));
+        assert!(contract.storage_accounts.get(&bob).unwrap().used_bytes > bob_used);
+        // Carol can't pay for the follow, so the request is dropped and refunded
+        assert!(!contract.is_following(carol.clone(), alice.clone()));
+        assert!(contract.storage_accounts.get(&carol).unwrap().used_bytes < carol_used);
+        assert_follow_indexes_consistent(&contract, &[alice.clone(), bob.clone(), carol.clone()]);
+        let events: Vec<_> = event_logs().iter().map(|event| event["event"].clone()).collect();
+        assert!(events.contains(&"follow_approved".into()) && events.contains(&"follow_rejected".into()));
+    }
+
+    #[test]
+    fn blocking_removes_follower_and_prevents_requests() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        let bob: AccountId = "bob.testnet".parse().unwrap();
+        testing_env!(get_context(bob.clone()).build());
+        let mut contract = setup_contract();
+        contract.follow_account(alice.clone()).unwrap();
+
+        testing_env!(get_context(alice.clone()).build());
+        assert_eq!(contract.block_account(alice.clone()), Err(BookyError::SelfBlock));
+        contract.block_account(bob.clone()).unwrap();
+        assert!(contract.is_blocked(alice.clone(), bob.clone()));
+        assert_eq!(contract.get_follower_count(alice.clone()), 0);
+        assert_follow_indexes_consistent(&contract, &[alice.clone(), bob.clone()]);
+
+        testing_env!(get_context(bob.clone()).build());
+        assert_eq!(contract.follow_account(alice.clone()), Err(BookyError::BlockedBy(alice.clone())));
+        testing_env!(get_context(alice.clone()).build());
+        contract.set_follow_approval(true).unwrap();
+        testing_env!(get_context(bob.clone()).build());
+        assert_eq!(contract.follow_account(alice.clone()), Err(BookyError::BlockedBy(alice.clone())));
+        assert_eq!(contract.get_follow_requests(alice.clone(), None, None).total, 0);
+
+        testing_env!(get_context(alice.clone()).build());
+        contract.set_follow_approval(false).unwrap();
+        contract.unblock_account(bob.clone()).unwrap();
+        assert_eq!(contract.unblock_account(bob.clone()), Err(BookyError::NotBlocked(bob.clone())));
+        assert_eq!(contract.get_blocked_accounts(alice.clone(), None, None).total, 0);
+        testing_env!(get_context(bob.clone()).build());
+        contract.follow_account(alice).unwrap();
+    }
+
+    #[test]
+    fn blocked_viewers_see_nothing_and_mutes_filter_feeds() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        let bob: AccountId = "bob.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
+        contract.follow_account(bob.clone()).unwrap();
+        testing_env!(get_context(bob.clone()).build());
+        contract.add_book(create_sample_book()).unwrap();
+        contract.follow_account(alice.clone()).unwrap();
+
+        testing_env!(get_context(alice.clone()).build());
+        contract.block_account(bob.clone()).unwrap();
+        assert_eq!(contract.get_user_library(alice.clone(), None, None, None, Some(bob.clone())).total, 0);
+        assert_eq!(contract.get_user_stats(alice.clone(), Some(bob.clone())).total_books, 0);
+        assert!(contract.get_book(alice.clone(), "9780451524935".to_string(), Some(bob.clone())).is_none());
+        assert_eq!(contract.get_user_library(alice.clone(), None, None, None, None).total, 1);
+
+        // Muting only filters feed views; the follow and the library stay visible
+        assert_eq!(contract.mute_account(alice.clone()), Err(BookyError::SelfMute));
+        contract.mute_account(bob.clone()).unwrap();
+        assert!(contract.is_following(alice.clone(), bob.clone()));
+        let feed = contract.get_followed_accounts_with_details(alice.clone(), None, None, None, Some(alice.clone()));
+        assert_eq!((feed.items.len(), feed.total), (0, 0));
+        assert_eq!(contract.get_followed_accounts_with_summaries(alice.clone(), None, None, None, None).total, 0);
+        assert_eq!(contract.get_user_library(bob.clone(), None, None, None, Some(alice.clone())).total, 1);
+        assert_eq!(contract.get_muted_accounts(alice.clone(), None, None).items, vec![bob.clone()]);
+
+        contract.unmute_account(bob.clone()).unwrap();
+        assert_eq!(contract.unmute_account(bob.clone()), Err(BookyError::NotMuted(bob.clone())));
+        let feed = contract.get_followed_accounts_with_details(alice.clone(), None, None, None, Some(alice));
+        assert_eq!(feed.items[0].account_id, bob);
+    }
+
+    #[test]
+    fn feed_merges_followed_activity_newest_first() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        let bob: AccountId = "bob.testnet".parse().unwrap();
+        let carol: AccountId = "carol.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = setup_contract();
+        let mut context = get_context(carol.clone());
+        context.attached_deposit(NearToken::from_near(1));
+        testing_env!(context.build());
+        contract.storage_deposit(None, None).unwrap();
+
+        let at = |account: &AccountId, timestamp: u64| {
+            testing_env!(get_context(account.clone()).block_timestamp(timestamp).build());
+        };
+        at(&bob, 100);
+        contract.add_book(create_sample_book()).unwrap();
+        at(&carol, 200);
+        contract.add_book(create_sample_book()).unwrap();
+        at(&bob, 300);
+        contract.start_reading("9780451524935".to_string(), None).unwrap();
+        at(&carol, 400);
+        contract.add_chapter_note("9780451524935".to_string(), 3, "Chilling".into()).unwrap();
+        at(&alice, 500);
+        contract.follow_account(bob.clone()).unwrap();
+        contract.follow_account(carol.clone()).unwrap();
+
+        let summary = |feed: Vec<FeedItem>| -> Vec<(String, u64, ActivityKind)> {
+            feed.into_iter()
+                .map(|item| (item.account_id.to_string(), item.activity.timestamp.0, item.activity.kind))
+                .collect()
+        };
+        assert_eq!(summary(contract.get_feed(alice.clone(), None, None)), vec![
+            ("carol.testnet".to_string(), 400, ActivityKind::NoteAdded { chapter: 3 }),
+            ("bob.testnet".to_string(), 300, ActivityKind::StartedReading),
+            ("carol.testnet".to_string(), 200, ActivityKind::BookAdded),
+            ("bob.testnet".to_string(), 100, ActivityKind::BookAdded),
+        ]);
+        assert_eq!(contract.get_feed(alice.clone(), Some(U64(250)), None).len(), 2);
+        assert_eq!(contract.get_feed(alice.clone(), None, Some(1)).len(), 1);
+
+        // Muted accounts leave the feed
+        contract.mute_account(carol.clone()).unwrap();
+        assert_eq!(contract.get_feed(alice.clone(), None, None).len(), 2);
+        contract.unmute_account(carol.clone()).unwrap();
+
+        // Hidden notes are left out for everyone but the owner
+        at(&carol, 600);
+        let hidden = PrivacySettings { hide_notes: true, ..Default::default() };
+        contract.set_book_privacy("9780451524935".to_string(), Some(hidden)).unwrap();
+        assert_eq!(contract.get_feed(alice.clone(), None, None).len(), 3);
+        assert_eq!(contract.get_activity(carol.clone(), None, None, Some(carol.clone())).len(), 2);
+        assert_eq!(contract.get_activity(carol.clone(), None, None, None).len(), 1);
+
+        // Deleting the note or the book takes its activity with it
+        contract.delete_chapter_note("9780451524935".to_string(), 3).unwrap();
+        assert_eq!(contract.get_activity(carol.clone(), None, None, Some(carol.clone())).len(), 1);
+        contract.delete_book("9780451524935".to_string()).unwrap();
+        assert!(contract.get_activity(carol.clone(), None, None, Some(carol)).is_empty());
+    }
+
+    #[test]
+    fn activity_log_keeps_newest_entries() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
+
+        for i in 0..105u64 {
+            testing_env!(get_context(alice.clone()).block_timestamp(1_000 + i).build());
+            contract.add_chapter_note("9780451524935".to_string(), (i % 10) as u32 + 1, "Note".into()).unwrap();
+        }
+        let activity = contract.get_activity(alice.clone(), None, Some(200), None);
+        assert_eq!(activity.len(), activity::MAX_ACTIVITY_PER_ACCOUNT as usize);
+        assert_eq!(activity[0].timestamp.0, 1_104);
+        assert_eq!(activity[99].timestamp.0, 1_005);
+
+        // Activity follows the book to a new ISBN
+        let moved = BookEntry { isbn: "978-0-306-40615-7".to_string(), ..create_sample_book() };
+        contract.update_book("9780451524935".to_string(), moved).unwrap();
+        let activity = contract.get_activity(alice.clone(), None, Some(1), None);
+        assert_eq!((activity[0].isbn.as_str(), activity[0].timestamp.0), ("9780306406157", 1_104));
+    }
+
+    #[test]
+    fn reading_sessions_logged_and_compacted() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        let bob: AccountId = "bob.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = setup_contract();
+        contract.add_book(BookEntry { total_chapters: Some(100), ..create_sample_book() }).unwrap();
+
+        const DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+        let progress = |chapter: u32, minutes: Option<u32>| ProgressUpdate {
+            current_chapter: Some(chapter),
+            chapters_completed: vec![],
+            last_read_position: Some(format!("chapter {}", chapter)),
+            last_read_date: None,
+            reading_status: None,
+            minutes_read: minutes,
+            position: None,
+        };
+        for day in 1..=3u64 {
+            testing_env!(get_context(alice.clone()).block_timestamp(day * DAY).build());
+            contract.update_reading_progress("9780451524935".to_string(), progress(day as u32 * 2, Some(30))).unwrap();
+        }
+        assert_eq!(
+            contract.update_reading_progress("9780451524935".to_string(), progress(7, Some(24 * 60 + 1))),
+            Err(BookyError::SessionTooLong { minutes: 24 * 60 + 1, max: 24 * 60 })
+        );
+
+        let sessions = contract.get_reading_sessions(alice.clone(), "9780451524935".to_string(), None, None);
+        assert_eq!(sessions.len(), 3);
+        assert_eq!((sessions[1].from_chapter, sessions[1].to_chapter), (2, 4));
+        assert_eq!(sessions[2].position, "chapter 6");
+        let range = TimeRange { from: Some(U64(2 * DAY)), to: Some(U64(2 * DAY)) };
+        assert_eq!(contract.get_reading_sessions(alice.clone(), "9780451524935".to_string(), Some(range), None).len(), 1);
+
+        // Two days from the first session to the last, six chapters at ten minutes each
+        let pace = contract.get_reading_pace(alice.clone(), "9780451524935".to_string(), None).unwrap();
+        assert_eq!((pace.updates, pace.chapters_advanced, pace.minutes_read), (3, 6, 90));
+        assert_eq!((pace.chapters_per_week, pace.minutes_per_chapter), (Some(21), Some(15)));
+
+        // Hidden books hide their sessions
+        let private = PrivacySettings { visibility: Visibility::Private, ..Default::default() };
+        contract.set_book_privacy("9780451524935".to_string(), Some(private)).unwrap();
+        assert!(contract.get_reading_sessions(alice.clone(), "9780451524935".to_string(), None, Some(bob)).is_empty());
+        assert!(contract.get_reading_pace(alice.clone(), "9780451524935".to_string(), None).is_none());
+
+        // A full log merges its oldest sessions, keeping the totals
+        for chapter in 7..7 + sessions::MAX_SESSIONS_PER_BOOK as u32 {
+            contract.update_reading_progress("9780451524935".to_string(), progress(chapter, None)).unwrap();
+        }
+        let sessions = contract.get_reading_sessions(alice.clone(), "9780451524935".to_string(), None, Some(alice.clone()));
+        assert_eq!(sessions.len(), sessions::MAX_SESSIONS_PER_BOOK);
+        assert_eq!((sessions[0].from_chapter, sessions[0].to_chapter, sessions[0].updates), (0, 7, 4));
+        assert_eq!((sessions[0].timestamp.0, sessions[0].minutes), (DAY, Some(90)));
+        let pace = contract.get_reading_pace(alice.clone(), "9780451524935".to_string(), Some(alice.clone())).unwrap();
+        assert_eq!((pace.updates, pace.chapters_advanced), (53, 56));
+
+        contract.delete_book("9780451524935".to_string()).unwrap();
+        contract.add_book(create_sample_book()).unwrap();
+        assert!(contract.get_reading_sessions(alice.clone(), "9780451524935".to_string(), None, Some(alice)).is_empty());
+    }
+
+    #[test]
+    fn contract_records_book_timestamps() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        let at = |timestamp: u64| testing_env!(get_context(alice.clone()).block_timestamp(timestamp).build());
+        let mut contract = setup_contract();
+        at(100);
+        let forged = BookEntry { added_at: U64(1), completed_at: Some(U64(1)), ..create_sample_book() };
+        contract.add_book(forged).unwrap();
+        at(200);
+        contract.start_reading("9780451524935".to_string(), None).unwrap();
+        at(300);
+        contract.update_book("9780451524935".to_string(), create_sample_book()).unwrap();
+        at(400);
+        contract.update_reading_progress("9780451524935".to_string(), ProgressUpdate {
+            current_chapter: Some(2),
+            chapters_completed: vec![1],
+            last_read_position: None,
+            last_read_date: None,
+            reading_status: Some(ReadingStatus::Reading),
+            minutes_read: None,
+            position: None,
+        }).unwrap();
+        at(500);
+        contract.mark_completed("9780451524935".to_string()).unwrap();
+
+        let book = contract.get_book(alice.clone(), "9780451524935".to_string(), None).unwrap();
+        assert_eq!((book.added_at, book.updated_at), (U64(100), U64(500)));
+        // Resuming after the update reset the status keeps the first start
+        assert_eq!((book.started_at, book.completed_at), (Some(U64(200)), Some(U64(500))));
+        assert_eq!(book.last_read_date.as_deref(), Some("1970-01-01"));
+    }
+
+    #[test]
+    fn v1_entries_gain_timestamps_from_stored_dates() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = setup_contract();
+        testing_env!(get_context(alice.clone()).block_timestamp(1_800_000_000_000_000_000).build());
+        contract.add_book(create_sample_book()).unwrap();
+
+        let sample = create_sample_book();
+        let v1 = |isbn: &str, status, last_read_date: &str| BookEntryV1 {
+            isbn: isbn.to_string(),
+            title: sample.title.clone(),
+            author: sample.author.clone(),
+            acquisition_date: "2024-01-15".to_string(),
+            condition: sample.condition.clone(),
+            personal_comments: sample.personal_comments.clone(),
+            media_hash: None,
+            reading_status: status,
+            current_chapter: 0,
+            total_chapters: Some(10),
+            chapters_read: HashSet::new(),
+            last_read_position: String::new(),
+            last_read_date: Some(last_read_date.to_string()),
+            chapter_notes: HashMap::new(),
+            privacy: None,
+        };
+        let library = contract.libraries.get_mut(&alice).unwrap();
+        // Completed by the old mark_completed, which stored raw nanoseconds
+        library.insert(
+            "9780306406157".to_string(),
+            VersionedBookEntry::V1(v1("9780306406157", ReadingStatus::Completed, "1709208000000000000")),
+        );
+        library.insert(
+            "9780140449136".to_string(),
+            VersionedBookEntry::V1(v1("9780140449136", ReadingStatus::Reading, "2024-03-01")),
+        );
+        // Neither a date too late for a timestamp nor a short number derails the conversion
+        for (key, last_read_date) in [("far-future", "9999-12-31"), ("digits", "20240115")] {
+            library.insert(key.to_string(), VersionedBookEntry::V1(v1(key, ReadingStatus::Completed, last_read_date)));
+        }
+
+        let completed = contract.get_book(alice.clone(), "9780306406157".to_string(), None).unwrap();
+        assert_eq!(completed.last_read_date.as_deref(), Some("2024-02-29"));
+        assert_eq!(completed.added_at, U64(1_705_276_800_000_000_000));
+        assert_eq!(completed.completed_at, Some(U64(1_709_208_000_000_000_000)));
+        assert_eq!(completed.updated_at, U64(1_709_208_000_000_000_000));
+
+        let reading = contract.get_book(alice.clone(), "9780140449136".to_string(), None).unwrap();
+        assert_eq!((reading.started_at, reading.completed_at), (None, None));
+        assert_eq!(reading.updated_at, U64(1_709_251_200_000_000_000));
+
+        let far_future = contract.get_book(alice.clone(), "far-future".to_string(), None).unwrap();
+        assert_eq!(far_future.last_read_date.as_deref(), Some("9999-12-31"));
+        assert_eq!((far_future.completed_at, far_future.updated_at), (None, U64(1_705_276_800_000_000_000)));
+        let digits = contract.get_book(alice.clone(), "digits".to_string(), None).unwrap();
+        assert_eq!(digits.last_read_date.as_deref(), Some("20240115"));
+        assert_eq!(digits.completed_at, None);
+
+        // Writing upgrades the stored entry
+        contract.start_reading("9780140449136".to_string(), None).unwrap();
+        let library = contract.libraries.get(&alice).unwrap();
+        assert!(matches!(library.get("9780140449136"), Some(VersionedBookEntry::V8(_))));
+        assert_eq!(library.get("9780140449136").unwrap().current().updated_at, U64(1_800_000_000_000_000_000));
+    }
+
+    #[test]
+    fn rereads_keep_each_reading_cycle() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = setup_contract();
+        let at = |timestamp: u64| testing_env!(get_context(alice.clone()).block_timestamp(timestamp).build());
+        contract.add_book(create_sample_book()).unwrap();
+        contract.add_book(BookEntry { isbn: "978-0-306-40615-7".to_string(), ..create_sample_book() }).unwrap();
+
+        assert_eq!(
+            contract.start_reread("9780451524935".to_string(), None),
+            Err(BookyError::NotFinished { status: ReadingStatus::ToRead })
+        );
+        at(100);
+        contract.start_reading("9780451524935".to_string(), None).unwrap();
+        at(200);
+        contract.mark_completed("9780451524935".to_string()).unwrap();
+
+        // Reopening a finished book any other way would lose the finished read
+        let already_finished = Err(BookyError::AlreadyFinished { status: ReadingStatus::Completed });
+        assert_eq!(contract.start_reading("9780451524935".to_string(), None), already_finished);
+        let reopen = ProgressUpdate {
+            current_chapter: Some(1),
+            chapters_completed: Vec::new(),
+            last_read_position: None,
+            last_read_date: None,
+            reading_status: Some(ReadingStatus::Reading),
+            minutes_read: None,
+            position: None,
+        };
+        assert_eq!(contract.update_reading_progress("9780451524935".to_string(), reopen), already_finished);
+        let book = contract.get_book(alice.clone(), "9780451524935".to_string(), None).unwrap();
+        let reopened = BookEntry { reading_status: ReadingStatus::OnHold, ..book };
+        assert_eq!(contract.update_book("9780451524935".to_string(), reopened), already_finished);
+        assert_eq!(contract.get_reading_stats(alice.clone(), None).total_reads, 1);
+        contract.rate_book("9780451524935".to_string(), Some(8)).unwrap();
+
+        at(300);
+        contract.start_reread("9780451524935".to_string(), Some(2)).unwrap();
+
+        // The new read starts with no rating and no position
+        let book = contract.get_book(alice.clone(), "9780451524935".to_string(), None).unwrap();
+        assert_eq!((book.reading_status, book.current_chapter), (ReadingStatus::Reading, 2));
+        assert!(book.chapters_read.is_empty());
+        assert_eq!((book.rating, book.last_read_position.as_str(), book.position), (None, "", None));
+        assert!(contract.rating_tallies.get("9780451524935").is_none());
+        assert_eq!((book.started_at, book.completed_at), (Some(U64(300)), None));
+        assert_eq!(book.progress_percent(), Some(0));
+
+        let cycles = contract.get_reading_cycles(alice.clone(), "9780451524935".to_string(), None);
+        assert_eq!(cycles.len(), 2);
+        assert_eq!((cycles[0].started_at, cycles[0].finished_at), (U64(100), Some(U64(200))));
+        assert_eq!((cycles[0].rating, cycles[1].rating), (Some(8), None));
+        assert_eq!((cycles[0].outcome, cycles[0].chapters_read.len()), (Some(ReadingOutcome::Completed), 10));
+        assert_eq!((cycles[1].outcome, cycles[1].finished_at), (None, None));
+
+        assert_eq!(
+            contract.rate_reading_cycle("9780451524935".to_string(), 0, Some(11)),
+            Err(BookyError::InvalidRating { rating: 11, max: 10 })
+        );
+        assert_eq!(
+            contract.rate_reading_cycle("9780451524935".to_string(), 1, Some(9)),
+            Err(BookyError::CycleNotFound { cycle: 1 })
+        );
+        contract.rate_reading_cycle("9780451524935".to_string(), 0, Some(9)).unwrap();
+
+        // The second read counts as another read of the same book
+        at(400);
+        contract.mark_completed("9780451524935".to_string()).unwrap();
+        contract.mark_completed("9780306406157".to_string()).unwrap();
+        let stats = contract.get_reading_stats(alice.clone(), None);
+        assert_eq!((stats.completed, stats.total_reads, stats.unique_books_read), (2, 3, 2));
+
+        let cycles = contract.get_reading_cycles(alice.clone(), "9780451524935".to_string(), None);
+        assert_eq!(cycles[0].rating, Some(9));
+        assert_eq!((cycles[1].started_at, cycles[1].finished_at), (U64(300), Some(U64(400))));
+    }
+
+    #[test]
+    fn ratings_summarized_across_libraries() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        let bob: AccountId = "bob.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
+        assert_eq!(
+            contract.rate_book("9780451524935".to_string(), Some(0)),
+            Err(BookyError::InvalidRating { rating: 0, max: 10 })
+        );
+        // The first rater pays for their entry's rating but not the shared tally
+        let available = contract.storage_balance_of(alice.clone()).unwrap().available.0;
+        contract.rate_book("9780451524935".to_string(), Some(9)).unwrap();
+        let paid = available - contract.storage_balance_of(alice.clone()).unwrap().available.0;
+        assert_eq!(paid, env::storage_byte_cost().as_yoctonear());
+        contract.write_review("9780451524935".to_string(), Some("Big Brother wins".to_string()), Some(true)).unwrap();
+        assert_eq!(
+            contract.write_review("9780451524935".to_string(), Some("x".repeat(8193)), None),
+            Err(BookyError::FieldTooLong { field: "review", max_length: 8192 })
+        );
+
+        testing_env!(get_context(bob.clone()).build());
+        contract.add_book(BookEntry { rating: Some(1), ..create_sample_book() }).unwrap();
+        contract.rate_book("0451524934".to_string(), Some(4)).unwrap();
+        contract.rate_book("9780451524935".to_string(), Some(6)).unwrap();
+
+        // Two ratings would let either rater work out the other's
+        let summary = contract.get_book_rating_summary("0-451-52493-4".to_string());
+        assert_eq!((summary.ratings, summary.average_x100), (0, None));
+
+        let carol: AccountId = "carol.testnet".parse().unwrap();
+        let mut context = get_context(carol.clone());
+        testing_env!(context.attached_deposit(NearToken::from_near(1)).build());
+        contract.storage_deposit(None, None).unwrap();
+        testing_env!(get_context(carol).build());
+        contract.add_book(create_sample_book()).unwrap();
+        contract.rate_book("9780451524935".to_string(), Some(10)).unwrap();
+        let summary = contract.get_book_rating_summary("0-451-52493-4".to_string());
+        assert_eq!((summary.isbn.as_str(), summary.ratings, summary.average_x100), ("9780451524935", 3, Some(833)));
+        assert_eq!(summary.histogram, vec![0, 0, 0, 0, 0, 1, 0, 0, 1, 1]);
+        testing_env!(get_context(bob.clone()).build());
+
+        let book = contract.get_book(alice.clone(), "9780451524935".to_string(), None).unwrap();
+        let review = book.review.unwrap();
+        assert_eq!((book.rating, review.text.as_str(), review.spoiler), (Some(9), "Big Brother wins", true));
+
+        // Ratings leave the summary with the book, and follow it to a new ISBN
+        contract.delete_book("9780451524935".to_string()).unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let moved = BookEntry { isbn: "978-0-306-40615-7".to_string(), rating: None, ..create_sample_book() };
+        contract.update_book("9780451524935".to_string(), moved).unwrap();
+        assert!(contract.rating_tallies.get("9780306406157").is_some());
+        assert_eq!(contract.get_book_rating_summary("9780306406157".to_string()).ratings, 0);
+
+        // A re-read leaves the rating with the finished read, and out of the summary
+        contract.mark_completed("9780306406157".to_string()).unwrap();
+        contract.start_reread("9780306406157".to_string(), None).unwrap();
+        let cycles = contract.get_reading_cycles(alice, "9780306406157".to_string(), None);
+        assert_eq!((cycles[0].rating, cycles[1].rating), (Some(9), None));
+        assert!(contract.rating_tallies.get("9780306406157").is_none());
+    }
+
+    #[test]
+    fn shelves_keep_their_own_order() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        let bob: AccountId = "bob.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = setup_contract();
+        contract.add_book(create_sample_book()).unwrap();
+        contract.add_book(BookEntry { isbn: "978-0-306-40615-7".to_string(), ..create_sample_book() }).unwrap();
+        assert_eq!(
+            contract.create_shelf(" ".to_string(), None),
+            Err(BookyError::EmptyField { field: "shelf name" })
+        );
+        let shelf_id = contract.create_shelf("Dystopias".to_string(), Some("Grim futures".to_string())).unwrap();
+        assert_eq!(contract.create_shelf("Favorites".to_string(), None), Ok(1));
+
+        contract.add_to_shelf(shelf_id, "0451524934".to_string(), None).unwrap();
+        contract.add_to_shelf(shelf_id, "978-0-306-40615-7".to_string(), Some(0)).unwrap();
+        let page = contract.get_shelf(alice.clone(), shelf_id, None, None, None).unwrap();
+        let isbns: Vec<_> = page.books.items.iter().map(|book| book.isbn.as_str()).collect();
+        assert_eq!(isbns, ["9780306406157", "9780451524935"]);
+        assert_eq!((page.shelf.name.as_str(), page.shelf.book_count), ("Dystopias", 2));
+
+        assert_eq!(
+            contract.reorder_shelf(shelf_id, vec!["9780451524935".to_string()]),
+            Err(BookyError::InvalidShelfOrder { shelf_id })
+        );
+        contract.reorder_shelf(shelf_id, vec!["0451524934".to_string(), "9780306406157".to_string()]).unwrap();
+        let page = contract.get_shelf(alice.clone(), shelf_id, None, Some(1), None).unwrap();
+        assert_eq!((page.books.items[0].isbn.as_str(), page.books.next_index), ("9780451524935", Some(1)));
+
+        // Private books are left out for other viewers
+        let private = PrivacySettings { visibility: Visibility::Private, ..Default::default() };
+        contract.set_book_privacy("9780451524935".to_string(), Some(private)).unwrap();
+        let page = contract.get_shelf(alice.clone(), shelf_id, None, None, Some(bob)).unwrap();
+        assert_eq!((page.books.total, page.books.items[0].isbn.as_str()), (1, "9780306406157"));
+
+        // Deleting a book takes it off its shelves
+        contract.delete_book("9780306406157".to_string()).unwrap();
+        assert_eq!(contract.get_shelves(alice.clone(), None)[0].book_count, 1);
+        assert_eq!(
+            contract.remove_from_shelf(shelf_id, "9780306406157".to_string()),
+            Err(BookyError::BookNotFound)
+        );
+        contract.remove_from_shelf(shelf_id, "9780451524935".to_string()).unwrap();
+        assert_eq!(
+            contract.remove_from_shelf(shelf_id, "9780451524935".to_string()),
+            Err(BookyError::NotOnShelf { shelf_id, isbn: "9780451524935".to_string() })
+        );
+        contract.delete_shelf(shelf_id).unwrap();
+        assert_eq!(contract.delete_shelf(shelf_id), Err(BookyError::ShelfNotFound { shelf_id }));
+        assert_eq!(contract.get_shelves(alice, None).len(), 1);
+    }
+
+    #[test]
+    fn tags_indexed_per_account() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = setup_contract();
+        contract.add_book(BookEntry { tags: vec!["ignored".to_string()], ..create_sample_book() }).unwrap();
+        contract.add_book(BookEntry { isbn: "978-0-306-40615-7".to_string(), ..create_sample_book() }).unwrap();
+        assert_eq!(
+            contract.tag_book("9780451524935".to_string(), vec!["ok".to_string(), "  ".to_string()]),
+            Err(BookyError::EmptyField { field: "tag" })
+        );
+        let tags = vec![" Classic".to_string(), "classic".to_string(), "SciFi".to_string()];
+        contract.tag_book("9780451524935".to_string(), tags).unwrap();
+        contract.tag_book("9780306406157".to_string(), vec!["classic".to_string()]).unwrap();
+
+        let book = contract.get_book(alice.clone(), "9780451524935".to_string(), None).unwrap();
+        assert_eq!(book.tags, ["classic", "scifi"]);
+        let tagged = contract.get_books_by_tag(alice.clone(), "CLASSIC".to_string(), None, None, None);
+        assert_eq!(tagged.total, 2);
+        let tags: Vec<_> = contract.get_tags(alice.clone(), None).into_iter().map(|tag| (tag.tag, tag.books)).collect();
+        assert_eq!(tags, [("classic".to_string(), 2), ("scifi".to_string(), 1)]);
+
+        // Tags follow a book to a new ISBN, and update_book keeps them
+        contract.delete_book("9780306406157".to_string()).unwrap();
+        let moved = BookEntry { isbn: "978-0-306-40615-7".to_string(), ..create_sample_book() };
+        contract.update_book("9780451524935".to_string(), moved).unwrap();
+        let tagged = contract.get_books_by_tag(alice.clone(), "scifi".to_string(), None, None, None);
+        assert_eq!((tagged.total, tagged.items[0].tags.len()), (1, 2));
+        assert_eq!(tagged.items[0].isbn, "9780306406157");
+
+        contract.tag_book("9780306406157".to_string(), Vec::new()).unwrap();
+        assert!(contract.get_tags(alice.clone(), None).is_empty());
+        assert_eq!(contract.get_books_by_tag(alice, "classic".to_string(), None, None, None).total, 0);
+    }
+
+    #[test]
+    fn unregistering_keeps_the_deposit_for_catalog_entries() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = setup_contract();
+        let entry = CatalogEntry {
+            isbn: "9780451524935".to_string(),
+            title: "1984".to_string(),
+            subtitle: None,
+            authors: vec!["George Orwell".to_string()],
+            publisher: None,
+            year: Some(1949),
+            language: Some("en".to_string()),
+            page_count: Some(328),
+            chapters: Vec::new(),
+            updated_at: U64(0),
+        };
+        let initial_storage = env::storage_usage();
+        contract.add_catalog_entry(entry).unwrap();
+        let catalog_bytes = *contract.catalog_bytes.get(&alice).unwrap();
+        assert!(catalog_bytes > 0 && catalog_bytes < env::storage_usage() - initial_storage);
+
+        let mut context = get_context(alice.clone());
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(1)).build());
+        assert_eq!(contract.storage_unregister(Some(true)), Ok(true));
+        assert!(contract.get_catalog_entry("9780451524935".to_string()).is_some());
+        assert!(contract.catalog_bytes.get(&alice).is_none());
+        let kept = env::storage_byte_cost().saturating_mul(catalog_bytes as u128);
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        assert!(matches!(
+            receipts[0].actions[0],
+            MockAction::Transfer { deposit, .. } if deposit == NearToken::from_near(1).saturating_sub(kept)
+        ));
+    }
+
+    #[test]
+    fn catalog_fills_in_library_entries() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        let bob: AccountId = "bob.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = setup_contract();
+        let entry = CatalogEntry {
+            isbn: "0-451-52493-4".to_string(),
+            title: "Nineteen Eigty-Four".to_string(),
+            subtitle: None,
+            authors: vec!["George Orwell".to_string()],
+            publisher: Some("Signet Classics".to_string()),
+            year: Some(1950),
+            language: Some("en".to_string()),
+            page_count: Some(328),
+            chapters: (1..=12)
+                .map(|id| Section { id, label: format!("Chapter {}", id), ordinal: id, start_page: None, end_page: None })
+                .collect(),
+            updated_at: U64(0),
+        };
+        contract.add_catalog_entry(entry.clone()).unwrap();
+        assert_eq!(
+            contract.add_catalog_entry(entry.clone()),
+            Err(BookyError::CatalogEntryExists("9780451524935".to_string()))
+        );
+
+        // Fields left empty come from the catalog; the rest override it, even where they match it
+        let book = BookEntry {
+            title: String::new(),
+            author: "G. Orwell".to_string(),
+            total_chapters: Some(12),
+            ..create_sample_book()
+        };
+        contract.add_book(book).unwrap();
+        let stored = |contract: &Contract| {
+            contract.libraries.get(&alice).unwrap().get("9780451524935").unwrap().current().into_owned()
+        };
+        let book = stored(&contract);
+        assert_eq!((book.title.as_str(), book.author.as_str(), book.total_chapters), ("", "G. Orwell", Some(12)));
+        assert!(book.catalog.is_none());
+        // The entry's own chapter count keeps the catalog's sections off it, and writing
+        // back the book as a view shows it leaves the catalog's fields to the catalog
+        let viewed = contract.get_book(alice.clone(), "9780451524935".to_string(), Some(alice.clone())).unwrap();
+        assert_eq!((viewed.title.as_str(), viewed.sections.len()), ("Nineteen Eigty-Four", 0));
+        contract.update_book("9780451524935".to_string(), viewed).unwrap();
+        let book = stored(&contract);
+        assert_eq!((book.title.as_str(), book.total_chapters, book.sections.len()), ("", Some(12), 0));
+        // Events carry the stored entry, not the catalog's metadata
+        let events = event_logs();
+        let added = &events.iter().find(|event| event["event"] == "book_added").unwrap()["data"]["book"];
+        assert_eq!((added["title"].as_str(), added["section_count"].as_u64()), (Some(""), Some(0)));
+        contract.update_reading_progress("9780451524935".to_string(), ProgressUpdate {
+            current_chapter: Some(12),
+            chapters_completed: vec![11],
+            last_read_position: None,
+            last_read_date: None,
+            reading_status: None,
+            minutes_read: None,
+            position: None,
+        }).unwrap();
+
+        testing_env!(get_context(bob.clone()).build());
+        let book = BookEntry { title: "1984".to_string(), author: String::new(), ..create_sample_book() };
+        contract.add_book(book).unwrap();
+        assert_eq!(
+            contract.correct_catalog_entry(entry.clone()),
+            Err(BookyError::MissingRole { account_id: bob.clone(), role: Role::Moderator })
+        );
+
+        testing_env!(get_context("owner.testnet".parse().unwrap()).build());
+        contract.grant_role(bob.clone(), Role::Moderator).unwrap();
+        testing_env!(get_context(bob.clone()).build());
+        let oversized = CatalogEntry {
+            chapters: (1..=1001)
+                .map(|id| Section { id, label: String::new(), ordinal: id, start_page: None, end_page: None })
+                .collect(),
+            ..entry.clone()
+        };
+        let too_many = BookyError::TooManyChapters { total_chapters: 1001, max: 1000 };
+        assert_eq!(contract.correct_catalog_entry(oversized.clone()), Err(too_many.clone()));
+        let unknown = CatalogEntry { isbn: "978-0-306-40615-7".to_string(), ..oversized };
+        assert_eq!(contract.add_catalog_entry(unknown), Err(too_many));
+        let corrected = CatalogEntry { title: "Nineteen Eighty-Four".to_string(), ..entry };
+        contract.correct_catalog_entry(corrected).unwrap();
+        assert_eq!(event_logs()[0]["event"], "catalog_entry_corrected");
+
+        let book = contract.get_book(alice.clone(), "9780451524935".to_string(), None).unwrap();
+        assert_eq!((book.title.as_str(), book.author.as_str()), ("Nineteen Eighty-Four", "G. Orwell"));
+        assert_eq!((book.total_chapters, book.catalog.unwrap().page_count), (Some(12), Some(328)));
+        let book = contract.get_book(bob.clone(), "9780451524935".to_string(), None).unwrap();
+        assert_eq!((book.title.as_str(), book.author.as_str()), ("1984", "George Orwell"));
+        assert_eq!(book.total_chapters, Some(10));
+        assert_eq!(contract.get_catalog_entry("9780451524935".to_string()).unwrap().year, Some(1950));
+        assert!(contract.get_catalog_entry("9780306406157".to_string()).is_none());
+    }
+
+    #[test]
+    fn catalog_reads_entries_stored_before_tables_of_contents() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = setup_contract();
+
+        // Written untagged, with chapter titles only
+        let mut legacy: LookupMap<String, CatalogEntryV1> = LookupMap::new(StorageKey::Catalog);
+        legacy.insert("9780451524935".to_string(), CatalogEntryV1 {
+            isbn: "9780451524935".to_string(),
+            title: "Nineteen Eighty-Four".to_string(),
+            subtitle: None,
+            authors: vec!["George Orwell".to_string()],
+            publisher: None,
+            year: Some(1949),
+            language: None,
+            page_count: Some(328),
+            chapters: vec!["Part One".to_string(), "Part Two".to_string()],
+            updated_at: U64(0),
+        });
+        legacy.flush();
+        contract.add_book(BookEntry { total_chapters: None, ..create_sample_book() }).unwrap();
+
+        let entry = contract.get_catalog_entry("978-0451524935".to_string()).unwrap();
+        assert_eq!(entry.year, Some(1949));
+        assert_eq!(
+            entry.chapters.iter().map(|section| (section.id, section.label.as_str())).collect::<Vec<_>>(),
+            vec![(1, "Part One"), (2, "Part Two")]
+        );
+        let book = contract.get_book(alice, "9780451524935".to_string(), None).unwrap();
+        assert_eq!((book.total_chapters, book.sections.len()), (Some(2), 2));
+    }
+
+    #[test]
+    fn table_of_contents_sections() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = setup_contract();
+        let section = |id: u32, label: &str, ordinal: u32, pages: Option<(u32, u32)>| Section {
+            id,
+            label: label.to_string(),
+            ordinal,
+            start_page: pages.map(|(start, _)| start),
+            end_page: pages.map(|(_, end)| end),
+        };
+        let sections = vec![
+            section(1, "Chapter 1", 1, Some((11, 40))),
+            section(100, "Prologue", 0, Some((1, 10))),
+            section(200, "Part II", 2, None),
+            section(300, "Appendix", 3, None),
+        ];
+        let book = BookEntry { total_chapters: None, sections: sections.clone(), ..create_sample_book() };
+        let mut invalid = book.clone();
+        invalid.sections.push(section(100, "Prologue again", 4, None));
+        assert_eq!(
+            contract.add_book(invalid),
+            Err(BookyError::InvalidSection { section: 100, reason: "section listed more than once" })
+        );
+        let mut invalid = book.clone();
+        invalid.sections[0].end_page = Some(5);
+        assert_eq!(
+            contract.add_book(invalid),
+            Err(BookyError::InvalidSection { section: 1, reason: "section ends before it starts" })
+        );
+        contract.add_book(book).unwrap();
+
+        // Chapter numbers are section ids, and reading starts from the first section
+        assert_eq!(
+            contract.add_chapter_note("9780451524935".to_string(), 2, "Missing".into()),
+            Err(BookyError::SectionNotFound { section: 2 })
+        );
+        contract.add_chapter_note("9780451524935".to_string(), 100, "Sets the tone".into()).unwrap();
+        contract.start_reading("9780451524935".to_string(), None).unwrap();
+        contract.update_reading_progress("9780451524935".to_string(), ProgressUpdate {
+            current_chapter: Some(200),
+            chapters_completed: vec![100, 1],
+            last_read_position: None,
+            last_read_date: None,
+            reading_status: None,
+            minutes_read: None,
+            position: None,
+        }).unwrap();
+
+        let contents = contract.get_table_of_contents(alice.clone(), "9780451524935".to_string(), None);
+        let ids: Vec<u32> = contents.iter().map(|entry| entry.section.id).collect();
+        assert_eq!(ids, [100, 1, 200, 300]);
+        assert_eq!((contents[0].read, contents[0].has_note, contents[0].section.end_page), (true, true, Some(10)));
+        assert_eq!((contents[2].read, contents[2].has_note), (false, false));
+        let book = contract.get_book(alice.clone(), "9780451524935".to_string(), None).unwrap();
+        assert_eq!(book.progress_percent(), Some(50));
+
+        contract.mark_completed("9780451524935".to_string()).unwrap();
+        let book = contract.get_book(alice.clone(), "9780451524935".to_string(), None).unwrap();
+        assert_eq!(book.chapters_read, HashSet::from([1, 100, 200, 300]));
+
+        // Books without one get numbered chapters
+        contract.add_book(BookEntry { isbn: "978-0-306-40615-7".to_string(), ..create_sample_book() }).unwrap();
+        let contents = contract.get_table_of_contents(alice, "9780306406157".to_string(), None);
+        assert_eq!(contents.len(), 10);
+        assert_eq!((contents[9].section.id, contents[9].section.label.as_str()), (10, "Chapter 10"));
+    }
+
+    #[test]
+    fn structured_positions_drive_progress() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = setup_contract();
+        let progress = |position: ReadingPosition| ProgressUpdate {
+            current_chapter: None,
+            chapters_completed: Vec::new(),
+            last_read_position: None,
+            position: Some(position),
+            last_read_date: None,
+            reading_status: Some(ReadingStatus::Reading),
+            minutes_read: None,
+        };
+        contract.add_book(BookEntry { total_pages: Some(300), ..create_sample_book() }).unwrap();
+        contract.add_book(BookEntry { isbn: "978-0-306-40615-7".to_string(), ..create_sample_book() }).unwrap();
+
+        let isbn = "9780451524935".to_string();
+        assert_eq!(
+            contract.update_reading_progress(isbn.clone(), progress(ReadingPosition::Page { page: 301 })),
+            Err(BookyError::InvalidPosition { reason: "page is past the book's total_pages" })
+        );
+        assert_eq!(
+            contract.update_reading_progress(isbn.clone(), progress(ReadingPosition::Percent { percent: 101 })),
+            Err(BookyError::InvalidPosition { reason: "percent is above 100" })
+        );
+        contract.update_reading_progress(isbn, progress(ReadingPosition::Page { page: 150 })).unwrap();
+        let book = contract.get_book(alice.clone(), "9780451524935".to_string(), None).unwrap();
+        assert_eq!((book.last_read_position.as_str(), book.progress_percent()), ("page 150", Some(50)));
+
+        // An audiobook position without a length can't tell progress, so chapters do
+        let listening = ReadingPosition::Timestamp { seconds: 3723, total_seconds: None };
+        let mut update = progress(listening);
+        update.chapters_completed = vec![1];
+        contract.update_reading_progress("9780306406157".to_string(), update).unwrap();
+        let summaries = contract.get_library_summaries(alice.clone(), None, None, None, None).items;
+        let summary = summaries.iter().find(|summary| summary.isbn == "9780306406157").unwrap();
+        assert_eq!(summary.progress_percent, Some(10));
+        let book = contract.get_book(alice.clone(), "9780306406157".to_string(), None).unwrap();
+        assert_eq!((book.last_read_position.as_str(), book.position), ("1:02:03", Some(listening)));
+        assert_eq!(contract.get_reading_stats(alice.clone(), None).average_progress_percent, Some(30));
+
+        // A chapter and paragraph also moves the current chapter
+        let reading = ReadingPosition::ChapterParagraph { chapter: 4, paragraph: 2 };
+        contract.update_reading_progress("9780306406157".to_string(), progress(reading)).unwrap();
+        let book = contract.get_book(alice.clone(), "9780306406157".to_string(), None).unwrap();
+        assert_eq!((book.current_chapter, book.last_read_position.as_str()), (4, "chapter 4 paragraph 2"));
+        assert_eq!(
+            contract.update_reading_progress(
+                "9780306406157".to_string(),
+                progress(ReadingPosition::ChapterParagraph { chapter: 11, paragraph: 1 })
+            ),
+            Err(BookyError::ChapterOutOfRange { chapter: 11, total_chapters: Some(10) })
+        );
+
+        contract.mark_completed("9780451524935".to_string()).unwrap();
+        assert_eq!(contract.get_reading_stats(alice, None).average_progress_percent, Some(10));
+    }
+
+    #[test]
+    fn owned_copies_and_format_stats() {
+        let alice: AccountId = "alice.testnet".parse().unwrap();
+        let bob: AccountId = "bob.testnet".parse().unwrap();
+        testing_env!(get_context(alice.clone()).build());
+        let mut contract = setup_contract();
+        let copy = |format: BookFormat, location: Option<&str>| OwnedCopy {
+            format,
+            condition: "Like New".to_string(),
+            acquisition_date: "2024-05-01".to_string(),
+            price_paid: Some(Price { amount: U64(2499), currency: "USD".to_string() }),
+            location: location.map(str::to_string),
+        };
 
-        assert_eq!(contract.get_total_books(), 3);
+        // One entry holds the hardcover and the audiobook of the same ISBN
+        let copies = vec![copy(BookFormat::Hardcover, Some("Office shelf")), copy(BookFormat::Audiobook, None)];
+        let book = BookEntry { format: BookFormat::Audiobook, copies, ..create_sample_book() };
+        let mut bad = book.clone();
+        bad.copies[0].price_paid = Some(Price { amount: U64(2499), currency: "usd".to_string() });
+        assert_eq!(contract.add_book(bad), Err(BookyError::InvalidCurrency("usd".to_string())));
+        let mut bad = book.clone();
+        bad.copies[1].acquisition_date = "May 2024".to_string();
+        assert!(matches!(contract.add_book(bad), Err(BookyError::InvalidDate { .. })));
+        let too_many = BookEntry { copies: vec![copy(BookFormat::Ebook, None); 21], ..book.clone() };
+        assert_eq!(contract.add_book(too_many), Err(BookyError::TooManyCopies { max: 20 }));
+        contract.add_book(book).unwrap();
+        contract.add_book(BookEntry { isbn: "978-0-306-40615-7".to_string(), ..create_sample_book() }).unwrap();
+        contract.mark_completed("9780306406157".to_string()).unwrap();
+
+        let stats = contract.get_reading_stats(alice.clone(), None).by_format;
+        let counts: Vec<_> = stats.iter().map(|s| (s.format, s.books, s.completed, s.copies)).collect();
+        assert_eq!(
+            counts,
+            vec![(BookFormat::Hardcover, 0, 0, 1), (BookFormat::Paperback, 1, 1, 0), (BookFormat::Audiobook, 1, 0, 1)]
+        );
+
+        // What was paid and where copies are kept goes with the hidden comments
+        contract.set_library_privacy(PrivacySettings { hide_comments: true, ..Default::default() }).unwrap();
+        let shown = contract.get_book(alice.clone(), "9780451524935".to_string(), Some(bob)).unwrap();
+        assert_eq!(shown.copies.len(), 2);
+        assert!(shown.copies.iter().all(|copy| copy.price_paid.is_none() && copy.location.is_none()));
+        let own = contract.get_book(alice.clone(), "9780451524935".to_string(), Some(alice)).unwrap();
+        assert_eq!(own.copies[0].location.as_deref(), Some("Office shelf"));
     }
 }
@@ -0,0 +1,1219 @@
+// State migrations between on-chain layouts of `Contract`, and self-upgrade
+use crate::access::AccessControl;
+use crate::activity::ActivityLog;
+use crate::ratings::RatingTally;
+use crate::shelves::AccountShelves;
+use crate::storage::AccountStorage;
+use crate::validation::{iso_date_from_timestamp, timestamp_from_iso_date};
+use crate::{
+    is_published, normalize_isbn, BookEntry, BookFormat, BookyError, BookyEvent, CatalogEntry, Contract, ContractExt,
+    FieldLimits, NoteContent, PrivacySettings, ReadingCycle, ReadingPosition, ReadingSession, ReadingStatus, Review,
+    Section, StorageKey, VersionedBookEntry, VersionedCatalogEntry,
+};
+use near_sdk::json_types::{Base64VecU8, U64};
+use near_sdk::store::{IterableMap, IterableSet, LookupMap, LookupSet};
+use near_sdk::{env, log, near, AccountId, Gas, GasWeight, NearToken, Promise};
+use std::collections::{HashMap, HashSet};
+
+/// Layout version of the `Contract` this code stores. Bump it, and add a
+/// `VersionedContract` variant for the old layout, whenever a release changes
+/// the layout of a deployed `Contract`.
+pub const CURRENT_STATE_VERSION: u16 = 7;
+
+const STATE_MAGIC: [u8; 4] = *b"BKY\0";
+
+/// Leading bytes of every stored `Contract` layout from version 1 on. The legacy
+/// HashMap layout starts with an account count instead, which would need millions
+/// of accounts to collide with the magic.
+#[near(serializers = [borsh])]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StateVersion {
+    magic: [u8; 4],
+    pub version: u16,
+}
+
+impl StateVersion {
+    pub fn current() -> Self {
+        Self { magic: STATE_MAGIC, version: CURRENT_STATE_VERSION }
+    }
+}
+
+/// Contract layout before libraries moved to persistent collections
+#[near(serializers = [borsh])]
+pub struct ContractV0 {
+    pub libraries: HashMap<AccountId, Vec<BookEntryV0>>,
+    pub followed_accounts: HashMap<AccountId, Vec<AccountId>>,
+}
+
+/// `BookEntry` as stored in `ContractV0`
+#[near(serializers = [borsh])]
+pub struct BookEntryV0 {
+    pub isbn: String,
+    pub title: String,
+    pub author: String,
+    pub acquisition_date: String,
+    pub condition: String,
+    pub personal_comments: String,
+    pub media_hash: Option<String>,
+    pub reading_status: ReadingStatus,
+    pub current_chapter: u32,
+    pub total_chapters: Option<u32>,
+    pub chapters_read: HashSet<u32>,
+    pub last_read_position: String,
+    pub last_read_date: Option<String>,
+    pub chapter_notes: HashMap<u32, String>,
+}
+
+/// `BookEntry` before the contract recorded timestamps, stored as `VersionedBookEntry::V1`
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub struct BookEntryV1 {
+    pub isbn: String,
+    pub title: String,
+    pub author: String,
+    pub acquisition_date: String,
+    pub condition: String,
+    pub personal_comments: NoteContent,
+    pub media_hash: Option<String>,
+    pub reading_status: ReadingStatus,
+    pub current_chapter: u32,
+    pub total_chapters: Option<u32>,
+    pub chapters_read: HashSet<u32>,
+    pub last_read_position: String,
+    pub last_read_date: Option<String>,
+    pub chapter_notes: HashMap<u32, NoteContent>,
+    pub privacy: Option<PrivacySettings>,
+}
+
+impl From<BookEntryV0> for BookEntryV1 {
+    fn from(old: BookEntryV0) -> Self {
+        Self {
+            isbn: old.isbn,
+            title: old.title,
+            author: old.author,
+            acquisition_date: old.acquisition_date,
+            condition: old.condition,
+            personal_comments: old.personal_comments.into(),
+            media_hash: old.media_hash,
+            reading_status: old.reading_status,
+            current_chapter: old.current_chapter,
+            total_chapters: old.total_chapters,
+            chapters_read: old.chapters_read,
+            last_read_position: old.last_read_position,
+            last_read_date: old.last_read_date,
+            chapter_notes: old.chapter_notes.into_iter().map(|(chapter, note)| (chapter, note.into())).collect(),
+            privacy: None,
+        }
+    }
+}
+
+/// `BookEntry` as first stored with contract timestamps, as `VersionedBookEntry::V2`
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub struct BookEntryV2 {
+    pub isbn: String,
+    pub title: String,
+    pub author: String,
+    pub acquisition_date: String,
+    pub condition: String,
+    pub personal_comments: NoteContent,
+    pub media_hash: Option<String>,
+    pub reading_status: ReadingStatus,
+    pub current_chapter: u32,
+    pub total_chapters: Option<u32>,
+    pub chapters_read: HashSet<u32>,
+    pub last_read_position: String,
+    pub last_read_date: Option<String>,
+    pub added_at: U64,
+    pub started_at: Option<U64>,
+    pub completed_at: Option<U64>,
+    pub updated_at: U64,
+    pub chapter_notes: HashMap<u32, NoteContent>,
+    pub privacy: Option<PrivacySettings>,
+}
+
+/// Fewest digits of a `last_read_date` taken for a raw block timestamp. Any
+/// nanosecond timestamp after 1970-01-12 has at least 16.
+const MIN_RAW_TIMESTAMP_DIGITS: usize = 16;
+
+/// Timestamps are derived from the stored dates where possible: the acquisition
+/// date stands in for when the book was added, and the last read date for when
+/// it was last updated and, if completed, finished. When the start isn't known it stays unset.
+impl From<BookEntryV1> for BookEntryV2 {
+    fn from(old: BookEntryV1) -> Self {
+        // mark_completed used to store the raw block timestamp instead of a date.
+        // Shorter numbers, like "20240115", are kept as they were.
+        let raw_timestamp = old.last_read_date
+            .as_deref()
+            .filter(|date| date.len() >= MIN_RAW_TIMESTAMP_DIGITS && date.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|date| date.parse().ok());
+        let last_read_date = raw_timestamp.map(iso_date_from_timestamp).or(old.last_read_date);
+        let last_read_at = raw_timestamp.or_else(|| last_read_date.as_deref().and_then(timestamp_from_iso_date));
+        let added_at = timestamp_from_iso_date(&old.acquisition_date).unwrap_or(0);
+
+        Self {
+            isbn: old.isbn,
+            title: old.title,
+            author: old.author,
+            acquisition_date: old.acquisition_date,
+            condition: old.condition,
+            personal_comments: old.personal_comments,
+            media_hash: old.media_hash,
+            reading_status: old.reading_status,
+            current_chapter: old.current_chapter,
+            total_chapters: old.total_chapters,
+            chapters_read: old.chapters_read,
+            last_read_position: old.last_read_position,
+            last_read_date,
+            added_at: U64(added_at),
+            started_at: None,
+            completed_at: last_read_at.filter(|_| old.reading_status == ReadingStatus::Completed).map(U64),
+            updated_at: U64(last_read_at.unwrap_or(0).max(added_at)),
+            chapter_notes: old.chapter_notes,
+            privacy: old.privacy,
+        }
+    }
+}
+
+/// `BookEntry` with reading cycles, stored as `VersionedBookEntry::V3`
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub struct BookEntryV3 {
+    pub isbn: String,
+    pub title: String,
+    pub author: String,
+    pub acquisition_date: String,
+    pub condition: String,
+    pub personal_comments: NoteContent,
+    pub media_hash: Option<String>,
+    pub reading_status: ReadingStatus,
+    pub current_chapter: u32,
+    pub total_chapters: Option<u32>,
+    pub chapters_read: HashSet<u32>,
+    pub last_read_position: String,
+    pub last_read_date: Option<String>,
+    pub added_at: U64,
+    pub started_at: Option<U64>,
+    pub completed_at: Option<U64>,
+    pub updated_at: U64,
+    pub reading_cycles: Vec<ReadingCycle>,
+    pub chapter_notes: HashMap<u32, NoteContent>,
+    pub privacy: Option<PrivacySettings>,
+}
+
+/// A book finished before cycles were tracked gets no cycle, since when it was
+/// started is not known
+impl From<BookEntryV2> for BookEntryV3 {
+    fn from(old: BookEntryV2) -> Self {
+        Self {
+            isbn: old.isbn,
+            title: old.title,
+            author: old.author,
+            acquisition_date: old.acquisition_date,
+            condition: old.condition,
+            personal_comments: old.personal_comments,
+            media_hash: old.media_hash,
+            reading_status: old.reading_status,
+            current_chapter: old.current_chapter,
+            total_chapters: old.total_chapters,
+            chapters_read: old.chapters_read,
+            last_read_position: old.last_read_position,
+            last_read_date: old.last_read_date,
+            added_at: old.added_at,
+            started_at: old.started_at,
+            completed_at: old.completed_at,
+            updated_at: old.updated_at,
+            reading_cycles: Vec::new(),
+            chapter_notes: old.chapter_notes,
+            privacy: old.privacy,
+        }
+    }
+}
+
+/// `BookEntry` with ratings and reviews, stored as `VersionedBookEntry::V4`
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub struct BookEntryV4 {
+    pub isbn: String,
+    pub title: String,
+    pub author: String,
+    pub acquisition_date: String,
+    pub condition: String,
+    pub personal_comments: NoteContent,
+    pub media_hash: Option<String>,
+    pub rating: Option<u8>,
+    pub review: Option<Review>,
+    pub reading_status: ReadingStatus,
+    pub current_chapter: u32,
+    pub total_chapters: Option<u32>,
+    pub chapters_read: HashSet<u32>,
+    pub last_read_position: String,
+    pub last_read_date: Option<String>,
+    pub added_at: U64,
+    pub started_at: Option<U64>,
+    pub completed_at: Option<U64>,
+    pub updated_at: U64,
+    pub reading_cycles: Vec<ReadingCycle>,
+    pub chapter_notes: HashMap<u32, NoteContent>,
+    pub privacy: Option<PrivacySettings>,
+}
+
+/// Books start out unrated
+impl From<BookEntryV3> for BookEntryV4 {
+    fn from(old: BookEntryV3) -> Self {
+        Self {
+            isbn: old.isbn,
+            title: old.title,
+            author: old.author,
+            acquisition_date: old.acquisition_date,
+            condition: old.condition,
+            personal_comments: old.personal_comments,
+            media_hash: old.media_hash,
+            rating: None,
+            review: None,
+            reading_status: old.reading_status,
+            current_chapter: old.current_chapter,
+            total_chapters: old.total_chapters,
+            chapters_read: old.chapters_read,
+            last_read_position: old.last_read_position,
+            last_read_date: old.last_read_date,
+            added_at: old.added_at,
+            started_at: old.started_at,
+            completed_at: old.completed_at,
+            updated_at: old.updated_at,
+            reading_cycles: old.reading_cycles,
+            chapter_notes: old.chapter_notes,
+            privacy: old.privacy,
+        }
+    }
+}
+
+/// `BookEntry` with tags, stored as `VersionedBookEntry::V5`
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub struct BookEntryV5 {
+    pub isbn: String,
+    pub title: String,
+    pub author: String,
+    pub acquisition_date: String,
+    pub condition: String,
+    pub personal_comments: NoteContent,
+    pub media_hash: Option<String>,
+    pub rating: Option<u8>,
+    pub review: Option<Review>,
+    pub tags: Vec<String>,
+    pub reading_status: ReadingStatus,
+    pub current_chapter: u32,
+    pub total_chapters: Option<u32>,
+    pub chapters_read: HashSet<u32>,
+    pub last_read_position: String,
+    pub last_read_date: Option<String>,
+    pub added_at: U64,
+    pub started_at: Option<U64>,
+    pub completed_at: Option<U64>,
+    pub updated_at: U64,
+    pub reading_cycles: Vec<ReadingCycle>,
+    pub chapter_notes: HashMap<u32, NoteContent>,
+    pub privacy: Option<PrivacySettings>,
+}
+
+/// Books start out untagged
+impl From<BookEntryV4> for BookEntryV5 {
+    fn from(old: BookEntryV4) -> Self {
+        Self {
+            isbn: old.isbn,
+            title: old.title,
+            author: old.author,
+            acquisition_date: old.acquisition_date,
+            condition: old.condition,
+            personal_comments: old.personal_comments,
+            media_hash: old.media_hash,
+            rating: old.rating,
+            review: old.review,
+            tags: Vec::new(),
+            reading_status: old.reading_status,
+            current_chapter: old.current_chapter,
+            total_chapters: old.total_chapters,
+            chapters_read: old.chapters_read,
+            last_read_position: old.last_read_position,
+            last_read_date: old.last_read_date,
+            added_at: old.added_at,
+            started_at: old.started_at,
+            completed_at: old.completed_at,
+            updated_at: old.updated_at,
+            reading_cycles: old.reading_cycles,
+            chapter_notes: old.chapter_notes,
+            privacy: old.privacy,
+        }
+    }
+}
+
+/// `BookEntry` with a table of contents, stored as `VersionedBookEntry::V6`
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub struct BookEntryV6 {
+    pub isbn: String,
+    pub title: String,
+    pub author: String,
+    pub acquisition_date: String,
+    pub condition: String,
+    pub personal_comments: NoteContent,
+    pub media_hash: Option<String>,
+    pub rating: Option<u8>,
+    pub review: Option<Review>,
+    pub tags: Vec<String>,
+    pub reading_status: ReadingStatus,
+    pub current_chapter: u32,
+    pub total_chapters: Option<u32>,
+    pub sections: Vec<Section>,
+    pub chapters_read: HashSet<u32>,
+    pub last_read_position: String,
+    pub last_read_date: Option<String>,
+    pub added_at: U64,
+    pub started_at: Option<U64>,
+    pub completed_at: Option<U64>,
+    pub updated_at: U64,
+    pub reading_cycles: Vec<ReadingCycle>,
+    pub chapter_notes: HashMap<u32, NoteContent>,
+    pub privacy: Option<PrivacySettings>,
+}
+
+/// Books start out without a table of contents
+impl From<BookEntryV5> for BookEntryV6 {
+    fn from(old: BookEntryV5) -> Self {
+        Self {
+            isbn: old.isbn,
+            title: old.title,
+            author: old.author,
+            acquisition_date: old.acquisition_date,
+            condition: old.condition,
+            personal_comments: old.personal_comments,
+            media_hash: old.media_hash,
+            rating: old.rating,
+            review: old.review,
+            tags: old.tags,
+            reading_status: old.reading_status,
+            current_chapter: old.current_chapter,
+            total_chapters: old.total_chapters,
+            sections: Vec::new(),
+            chapters_read: old.chapters_read,
+            last_read_position: old.last_read_position,
+            last_read_date: old.last_read_date,
+            added_at: old.added_at,
+            started_at: old.started_at,
+            completed_at: old.completed_at,
+            updated_at: old.updated_at,
+            reading_cycles: old.reading_cycles,
+            chapter_notes: old.chapter_notes,
+            privacy: old.privacy,
+        }
+    }
+}
+
+/// `BookEntry` with page counts and structured positions, stored as `VersionedBookEntry::V7`
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub struct BookEntryV7 {
+    pub isbn: String,
+    pub title: String,
+    pub author: String,
+    pub acquisition_date: String,
+    pub condition: String,
+    pub personal_comments: NoteContent,
+    pub media_hash: Option<String>,
+    pub rating: Option<u8>,
+    pub review: Option<Review>,
+    pub tags: Vec<String>,
+    pub reading_status: ReadingStatus,
+    pub current_chapter: u32,
+    pub total_chapters: Option<u32>,
+    pub sections: Vec<Section>,
+    pub total_pages: Option<u32>,
+    pub chapters_read: HashSet<u32>,
+    pub last_read_position: String,
+    pub position: Option<ReadingPosition>,
+    pub last_read_date: Option<String>,
+    pub added_at: U64,
+    pub started_at: Option<U64>,
+    pub completed_at: Option<U64>,
+    pub updated_at: U64,
+    pub reading_cycles: Vec<ReadingCycle>,
+    pub chapter_notes: HashMap<u32, NoteContent>,
+    pub privacy: Option<PrivacySettings>,
+}
+
+/// Books start out without a page count or structured position
+impl From<BookEntryV6> for BookEntryV7 {
+    fn from(old: BookEntryV6) -> Self {
+        Self {
+            isbn: old.isbn,
+            title: old.title,
+            author: old.author,
+            acquisition_date: old.acquisition_date,
+            condition: old.condition,
+            personal_comments: old.personal_comments,
+            media_hash: old.media_hash,
+            rating: old.rating,
+            review: old.review,
+            tags: old.tags,
+            reading_status: old.reading_status,
+            current_chapter: old.current_chapter,
+            total_chapters: old.total_chapters,
+            sections: old.sections,
+            total_pages: None,
+            chapters_read: old.chapters_read,
+            last_read_position: old.last_read_position,
+            position: None,
+            last_read_date: old.last_read_date,
+            added_at: old.added_at,
+            started_at: old.started_at,
+            completed_at: old.completed_at,
+            updated_at: old.updated_at,
+            reading_cycles: old.reading_cycles,
+            chapter_notes: old.chapter_notes,
+            privacy: old.privacy,
+        }
+    }
+}
+
+/// Books start out in the default format, with no owned copies recorded
+impl From<BookEntryV7> for BookEntry {
+    fn from(old: BookEntryV7) -> Self {
+        Self {
+            isbn: old.isbn,
+            title: old.title,
+            author: old.author,
+            acquisition_date: old.acquisition_date,
+            condition: old.condition,
+            format: BookFormat::default(),
+            copies: Vec::new(),
+            personal_comments: old.personal_comments,
+            media_hash: old.media_hash,
+            rating: old.rating,
+            review: old.review,
+            tags: old.tags,
+            reading_status: old.reading_status,
+            current_chapter: old.current_chapter,
+            total_chapters: old.total_chapters,
+            sections: old.sections,
+            total_pages: old.total_pages,
+            chapters_read: old.chapters_read,
+            last_read_position: old.last_read_position,
+            position: old.position,
+            last_read_date: old.last_read_date,
+            added_at: old.added_at,
+            started_at: old.started_at,
+            completed_at: old.completed_at,
+            updated_at: old.updated_at,
+            reading_cycles: old.reading_cycles,
+            chapter_notes: old.chapter_notes,
+            privacy: old.privacy,
+            catalog: None,
+        }
+    }
+}
+
+/// `CatalogEntry` before tables of contents, when chapters were only titles.
+/// Stored untagged, and read back as `VersionedCatalogEntry::V1`.
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub struct CatalogEntryV1 {
+    pub isbn: String,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub authors: Vec<String>,
+    pub publisher: Option<String>,
+    pub year: Option<u16>,
+    pub language: Option<String>,
+    pub page_count: Option<u32>,
+    pub chapters: Vec<String>,
+    pub updated_at: U64,
+}
+
+/// Chapter titles become sections numbered from 1 in reading order, the chapter
+/// numbers library entries already use for them
+impl From<CatalogEntryV1> for CatalogEntry {
+    fn from(old: CatalogEntryV1) -> Self {
+        let chapters = (1..)
+            .zip(old.chapters)
+            .map(|(id, label)| Section { id, label, ordinal: id, start_page: None, end_page: None })
+            .collect();
+        Self {
+            isbn: old.isbn,
+            title: old.title,
+            subtitle: old.subtitle,
+            authors: old.authors,
+            publisher: old.publisher,
+            year: old.year,
+            language: old.language,
+            page_count: old.page_count,
+            chapters,
+            updated_at: old.updated_at,
+        }
+    }
+}
+
+/// `FieldLimits` as stored in state version 1
+#[near(serializers = [borsh])]
+pub struct FieldLimitsV1 {
+    pub max_title_len: u32,
+    pub max_author_len: u32,
+    pub max_condition_len: u32,
+    pub max_comments_len: u32,
+    pub max_media_hash_len: u32,
+    pub max_position_len: u32,
+    pub max_note_len: u32,
+    pub max_total_chapters: u32,
+    pub max_note_recipients: u32,
+    pub max_session_minutes: u32,
+}
+
+/// `FieldLimits` as stored in state version 2
+#[near(serializers = [borsh])]
+pub struct FieldLimitsV2 {
+    pub max_title_len: u32,
+    pub max_author_len: u32,
+    pub max_condition_len: u32,
+    pub max_comments_len: u32,
+    pub max_media_hash_len: u32,
+    pub max_position_len: u32,
+    pub max_note_len: u32,
+    pub max_total_chapters: u32,
+    pub max_note_recipients: u32,
+    pub max_session_minutes: u32,
+    pub max_review_len: u32,
+}
+
+impl From<FieldLimitsV1> for FieldLimitsV2 {
+    fn from(old: FieldLimitsV1) -> Self {
+        Self {
+            max_title_len: old.max_title_len,
+            max_author_len: old.max_author_len,
+            max_condition_len: old.max_condition_len,
+            max_comments_len: old.max_comments_len,
+            max_media_hash_len: old.max_media_hash_len,
+            max_position_len: old.max_position_len,
+            max_note_len: old.max_note_len,
+            max_total_chapters: old.max_total_chapters,
+            max_note_recipients: old.max_note_recipients,
+            max_session_minutes: old.max_session_minutes,
+            max_review_len: FieldLimits::default().max_review_len,
+        }
+    }
+}
+
+/// `FieldLimits` as stored in state version 3
+#[near(serializers = [borsh])]
+pub struct FieldLimitsV3 {
+    pub max_title_len: u32,
+    pub max_author_len: u32,
+    pub max_condition_len: u32,
+    pub max_comments_len: u32,
+    pub max_media_hash_len: u32,
+    pub max_position_len: u32,
+    pub max_note_len: u32,
+    pub max_total_chapters: u32,
+    pub max_note_recipients: u32,
+    pub max_session_minutes: u32,
+    pub max_review_len: u32,
+    pub max_shelf_name_len: u32,
+    pub max_shelf_description_len: u32,
+    pub max_tag_len: u32,
+    pub max_tags_per_book: u32,
+}
+
+impl From<FieldLimitsV2> for FieldLimitsV3 {
+    fn from(old: FieldLimitsV2) -> Self {
+        Self {
+            max_title_len: old.max_title_len,
+            max_author_len: old.max_author_len,
+            max_condition_len: old.max_condition_len,
+            max_comments_len: old.max_comments_len,
+            max_media_hash_len: old.max_media_hash_len,
+            max_position_len: old.max_position_len,
+            max_note_len: old.max_note_len,
+            max_total_chapters: old.max_total_chapters,
+            max_note_recipients: old.max_note_recipients,
+            max_session_minutes: old.max_session_minutes,
+            max_review_len: old.max_review_len,
+            max_shelf_name_len: FieldLimits::default().max_shelf_name_len,
+            max_shelf_description_len: FieldLimits::default().max_shelf_description_len,
+            max_tag_len: FieldLimits::default().max_tag_len,
+            max_tags_per_book: FieldLimits::default().max_tags_per_book,
+        }
+    }
+}
+
+/// Limits the owner set are kept; the ones added since get their defaults
+impl From<FieldLimitsV3> for FieldLimits {
+    fn from(old: FieldLimitsV3) -> Self {
+        Self {
+            max_title_len: old.max_title_len,
+            max_author_len: old.max_author_len,
+            max_condition_len: old.max_condition_len,
+            max_comments_len: old.max_comments_len,
+            max_media_hash_len: old.max_media_hash_len,
+            max_position_len: old.max_position_len,
+            max_note_len: old.max_note_len,
+            max_total_chapters: old.max_total_chapters,
+            max_note_recipients: old.max_note_recipients,
+            max_session_minutes: old.max_session_minutes,
+            max_review_len: old.max_review_len,
+            max_shelf_name_len: old.max_shelf_name_len,
+            max_shelf_description_len: old.max_shelf_description_len,
+            max_tag_len: old.max_tag_len,
+            max_tags_per_book: old.max_tags_per_book,
+            ..FieldLimits::default()
+        }
+    }
+}
+
+/// `Contract` layout of state version 1, before rating tallies
+#[near(serializers = [borsh])]
+pub struct ContractV1 {
+    pub state_version: StateVersion,
+    pub libraries: LookupMap<AccountId, IterableMap<String, VersionedBookEntry>>,
+    pub followed_accounts: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub followers: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub follow_approval: LookupSet<AccountId>,
+    pub follow_requests: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub blocked_accounts: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub muted_accounts: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub activity: LookupMap<AccountId, ActivityLog>,
+    pub reading_sessions: LookupMap<AccountId, IterableMap<String, Vec<ReadingSession>>>,
+    pub privacy_settings: LookupMap<AccountId, PrivacySettings>,
+    pub note_public_keys: LookupMap<AccountId, Base64VecU8>,
+    pub total_books: u64,
+    pub storage_accounts: LookupMap<AccountId, AccountStorage>,
+    pub field_limits: FieldLimitsV1,
+    pub access: AccessControl,
+}
+
+/// No book was rated yet, so the tallies start out empty
+impl From<ContractV1> for ContractV2 {
+    fn from(old: ContractV1) -> Self {
+        Self {
+            state_version: StateVersion { version: 2, ..StateVersion::current() },
+            libraries: old.libraries,
+            followed_accounts: old.followed_accounts,
+            followers: old.followers,
+            follow_approval: old.follow_approval,
+            follow_requests: old.follow_requests,
+            blocked_accounts: old.blocked_accounts,
+            muted_accounts: old.muted_accounts,
+            activity: old.activity,
+            reading_sessions: old.reading_sessions,
+            rating_tallies: LookupMap::new(StorageKey::RatingTallies),
+            privacy_settings: old.privacy_settings,
+            note_public_keys: old.note_public_keys,
+            total_books: old.total_books,
+            storage_accounts: old.storage_accounts,
+            field_limits: old.field_limits.into(),
+            access: old.access,
+        }
+    }
+}
+
+/// `Contract` layout of state version 2, before shelves and tags
+#[near(serializers = [borsh])]
+pub struct ContractV2 {
+    pub state_version: StateVersion,
+    pub libraries: LookupMap<AccountId, IterableMap<String, VersionedBookEntry>>,
+    pub followed_accounts: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub followers: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub follow_approval: LookupSet<AccountId>,
+    pub follow_requests: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub blocked_accounts: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub muted_accounts: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub activity: LookupMap<AccountId, ActivityLog>,
+    pub reading_sessions: LookupMap<AccountId, IterableMap<String, Vec<ReadingSession>>>,
+    pub rating_tallies: LookupMap<String, RatingTally>,
+    pub privacy_settings: LookupMap<AccountId, PrivacySettings>,
+    pub note_public_keys: LookupMap<AccountId, Base64VecU8>,
+    pub total_books: u64,
+    pub storage_accounts: LookupMap<AccountId, AccountStorage>,
+    pub field_limits: FieldLimitsV2,
+    pub access: AccessControl,
+}
+
+/// Nobody has shelves or tags yet
+impl From<ContractV2> for ContractV3 {
+    fn from(old: ContractV2) -> Self {
+        Self {
+            state_version: StateVersion { version: 3, ..StateVersion::current() },
+            libraries: old.libraries,
+            followed_accounts: old.followed_accounts,
+            followers: old.followers,
+            follow_approval: old.follow_approval,
+            follow_requests: old.follow_requests,
+            blocked_accounts: old.blocked_accounts,
+            muted_accounts: old.muted_accounts,
+            activity: old.activity,
+            reading_sessions: old.reading_sessions,
+            rating_tallies: old.rating_tallies,
+            shelves: LookupMap::new(StorageKey::Shelves),
+            tag_index: LookupMap::new(StorageKey::TagIndexes),
+            privacy_settings: old.privacy_settings,
+            note_public_keys: old.note_public_keys,
+            total_books: old.total_books,
+            storage_accounts: old.storage_accounts,
+            field_limits: old.field_limits.into(),
+            access: old.access,
+        }
+    }
+}
+
+/// `Contract` layout of state version 3, before the catalog
+#[near(serializers = [borsh])]
+pub struct ContractV3 {
+    pub state_version: StateVersion,
+    pub libraries: LookupMap<AccountId, IterableMap<String, VersionedBookEntry>>,
+    pub followed_accounts: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub followers: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub follow_approval: LookupSet<AccountId>,
+    pub follow_requests: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub blocked_accounts: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub muted_accounts: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub activity: LookupMap<AccountId, ActivityLog>,
+    pub reading_sessions: LookupMap<AccountId, IterableMap<String, Vec<ReadingSession>>>,
+    pub rating_tallies: LookupMap<String, RatingTally>,
+    pub shelves: LookupMap<AccountId, AccountShelves>,
+    pub tag_index: LookupMap<AccountId, IterableMap<String, Vec<String>>>,
+    pub privacy_settings: LookupMap<AccountId, PrivacySettings>,
+    pub note_public_keys: LookupMap<AccountId, Base64VecU8>,
+    pub total_books: u64,
+    pub storage_accounts: LookupMap<AccountId, AccountStorage>,
+    pub field_limits: FieldLimitsV3,
+    pub access: AccessControl,
+}
+
+/// The catalog starts out empty
+impl From<ContractV3> for ContractV4 {
+    fn from(old: ContractV3) -> Self {
+        Self {
+            state_version: StateVersion { version: 4, ..StateVersion::current() },
+            libraries: old.libraries,
+            followed_accounts: old.followed_accounts,
+            followers: old.followers,
+            follow_approval: old.follow_approval,
+            follow_requests: old.follow_requests,
+            blocked_accounts: old.blocked_accounts,
+            muted_accounts: old.muted_accounts,
+            activity: old.activity,
+            reading_sessions: old.reading_sessions,
+            rating_tallies: old.rating_tallies,
+            shelves: old.shelves,
+            tag_index: old.tag_index,
+            catalog: LookupMap::new(StorageKey::Catalog),
+            privacy_settings: old.privacy_settings,
+            note_public_keys: old.note_public_keys,
+            total_books: old.total_books,
+            storage_accounts: old.storage_accounts,
+            field_limits: old.field_limits,
+            access: old.access,
+        }
+    }
+}
+
+/// `Contract` layout of state version 4, before limits on owned copies
+#[near(serializers = [borsh])]
+pub struct ContractV4 {
+    pub state_version: StateVersion,
+    pub libraries: LookupMap<AccountId, IterableMap<String, VersionedBookEntry>>,
+    pub followed_accounts: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub followers: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub follow_approval: LookupSet<AccountId>,
+    pub follow_requests: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub blocked_accounts: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub muted_accounts: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub activity: LookupMap<AccountId, ActivityLog>,
+    pub reading_sessions: LookupMap<AccountId, IterableMap<String, Vec<ReadingSession>>>,
+    pub rating_tallies: LookupMap<String, RatingTally>,
+    pub shelves: LookupMap<AccountId, AccountShelves>,
+    pub tag_index: LookupMap<AccountId, IterableMap<String, Vec<String>>>,
+    pub catalog: LookupMap<String, VersionedCatalogEntry>,
+    pub privacy_settings: LookupMap<AccountId, PrivacySettings>,
+    pub note_public_keys: LookupMap<AccountId, Base64VecU8>,
+    pub total_books: u64,
+    pub storage_accounts: LookupMap<AccountId, AccountStorage>,
+    pub field_limits: FieldLimitsV3,
+    pub access: AccessControl,
+}
+
+/// Limits on owned copies start at their defaults
+impl From<ContractV4> for ContractV5 {
+    fn from(old: ContractV4) -> Self {
+        Self {
+            state_version: StateVersion { version: 5, ..StateVersion::current() },
+            libraries: old.libraries,
+            followed_accounts: old.followed_accounts,
+            followers: old.followers,
+            follow_approval: old.follow_approval,
+            follow_requests: old.follow_requests,
+            blocked_accounts: old.blocked_accounts,
+            muted_accounts: old.muted_accounts,
+            activity: old.activity,
+            reading_sessions: old.reading_sessions,
+            rating_tallies: old.rating_tallies,
+            shelves: old.shelves,
+            tag_index: old.tag_index,
+            catalog: old.catalog,
+            privacy_settings: old.privacy_settings,
+            note_public_keys: old.note_public_keys,
+            total_books: old.total_books,
+            storage_accounts: old.storage_accounts,
+            field_limits: old.field_limits.into(),
+            access: old.access,
+        }
+    }
+}
+
+/// `Contract` layout of state version 5, before follow requests were indexed by requester
+#[near(serializers = [borsh])]
+pub struct ContractV5 {
+    pub state_version: StateVersion,
+    pub libraries: LookupMap<AccountId, IterableMap<String, VersionedBookEntry>>,
+    pub followed_accounts: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub followers: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub follow_approval: LookupSet<AccountId>,
+    pub follow_requests: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub blocked_accounts: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub muted_accounts: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub activity: LookupMap<AccountId, ActivityLog>,
+    pub reading_sessions: LookupMap<AccountId, IterableMap<String, Vec<ReadingSession>>>,
+    pub rating_tallies: LookupMap<String, RatingTally>,
+    pub shelves: LookupMap<AccountId, AccountShelves>,
+    pub tag_index: LookupMap<AccountId, IterableMap<String, Vec<String>>>,
+    pub catalog: LookupMap<String, VersionedCatalogEntry>,
+    pub privacy_settings: LookupMap<AccountId, PrivacySettings>,
+    pub note_public_keys: LookupMap<AccountId, Base64VecU8>,
+    pub total_books: u64,
+    pub storage_accounts: LookupMap<AccountId, AccountStorage>,
+    pub field_limits: FieldLimits,
+    pub access: AccessControl,
+}
+
+/// Pending follow requests are indexed by requester afterwards, through
+/// `migrate_follow_requests`, since `follow_requests` can't be enumerated here
+impl From<ContractV5> for ContractV6 {
+    fn from(old: ContractV5) -> Self {
+        Self {
+            state_version: StateVersion { version: 6, ..StateVersion::current() },
+            libraries: old.libraries,
+            followed_accounts: old.followed_accounts,
+            followers: old.followers,
+            follow_approval: old.follow_approval,
+            follow_requests: old.follow_requests,
+            sent_follow_requests: LookupMap::new(StorageKey::SentFollowRequests),
+            blocked_accounts: old.blocked_accounts,
+            muted_accounts: old.muted_accounts,
+            activity: old.activity,
+            reading_sessions: old.reading_sessions,
+            rating_tallies: old.rating_tallies,
+            shelves: old.shelves,
+            tag_index: old.tag_index,
+            catalog: old.catalog,
+            privacy_settings: old.privacy_settings,
+            note_public_keys: old.note_public_keys,
+            total_books: old.total_books,
+            storage_accounts: old.storage_accounts,
+            field_limits: old.field_limits,
+            access: old.access,
+        }
+    }
+}
+
+/// `Contract` layout of state version 6, before catalog bytes were tracked per account
+#[near(serializers = [borsh])]
+pub struct ContractV6 {
+    pub state_version: StateVersion,
+    pub libraries: LookupMap<AccountId, IterableMap<String, VersionedBookEntry>>,
+    pub followed_accounts: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub followers: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub follow_approval: LookupSet<AccountId>,
+    pub follow_requests: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub sent_follow_requests: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub blocked_accounts: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub muted_accounts: LookupMap<AccountId, IterableSet<AccountId>>,
+    pub activity: LookupMap<AccountId, ActivityLog>,
+    pub reading_sessions: LookupMap<AccountId, IterableMap<String, Vec<ReadingSession>>>,
+    pub rating_tallies: LookupMap<String, RatingTally>,
+    pub shelves: LookupMap<AccountId, AccountShelves>,
+    pub tag_index: LookupMap<AccountId, IterableMap<String, Vec<String>>>,
+    pub catalog: LookupMap<String, VersionedCatalogEntry>,
+    pub privacy_settings: LookupMap<AccountId, PrivacySettings>,
+    pub note_public_keys: LookupMap<AccountId, Base64VecU8>,
+    pub total_books: u64,
+    pub storage_accounts: LookupMap<AccountId, AccountStorage>,
+    pub field_limits: FieldLimits,
+    pub access: AccessControl,
+}
+
+/// Catalog entries added before this version aren't attributed to anyone, so
+/// their adders' deposits are refunded in full when they unregister
+impl From<ContractV6> for Contract {
+    fn from(old: ContractV6) -> Self {
+        Self {
+            libraries: old.libraries,
+            followed_accounts: old.followed_accounts,
+            followers: old.followers,
+            follow_approval: old.follow_approval,
+            follow_requests: old.follow_requests,
+            sent_follow_requests: old.sent_follow_requests,
+            blocked_accounts: old.blocked_accounts,
+            muted_accounts: old.muted_accounts,
+            activity: old.activity,
+            reading_sessions: old.reading_sessions,
+            rating_tallies: old.rating_tallies,
+            shelves: old.shelves,
+            tag_index: old.tag_index,
+            catalog: old.catalog,
+            privacy_settings: old.privacy_settings,
+            note_public_keys: old.note_public_keys,
+            total_books: old.total_books,
+            storage_accounts: old.storage_accounts,
+            field_limits: old.field_limits,
+            access: old.access,
+            ..Contract::default()
+        }
+    }
+}
+
+/// Every layout the contract state has been stored in
+pub enum VersionedContract {
+    V0(ContractV0),
+    V1(ContractV1),
+    V2(ContractV2),
+    V3(ContractV3),
+    V4(ContractV4),
+    V5(ContractV5),
+    V6(ContractV6),
+    V7(Contract),
+}
+
+impl VersionedContract {
+    /// Read the stored state in whichever layout it was written
+    fn read() -> Self {
+        let state = env::storage_read(b"STATE").unwrap_or_else(|| env::panic_str("No contract state to migrate"));
+        if !state.starts_with(&STATE_MAGIC) {
+            return Self::V0(near_sdk::borsh::from_slice(&state).expect("Unrecognized contract state"));
+        }
+
+        let version: StateVersion = near_sdk::borsh::BorshDeserialize::deserialize(&mut &state[..])
+            .expect("Unrecognized contract state");
+        match version.version {
+            1 => Self::V1(near_sdk::borsh::from_slice(&state).expect("Corrupt version 1 state")),
+            2 => Self::V2(near_sdk::borsh::from_slice(&state).expect("Corrupt version 2 state")),
+            3 => Self::V3(near_sdk::borsh::from_slice(&state).expect("Corrupt version 3 state")),
+            4 => Self::V4(near_sdk::borsh::from_slice(&state).expect("Corrupt version 4 state")),
+            5 => Self::V5(near_sdk::borsh::from_slice(&state).expect("Corrupt version 5 state")),
+            6 => Self::V6(near_sdk::borsh::from_slice(&state).expect("Corrupt version 6 state")),
+            7 => Self::V7(near_sdk::borsh::from_slice(&state).expect("Corrupt version 7 state")),
+            other => env::panic_str(&format!("Unknown state version {}", other)),
+        }
+    }
+}
+
+/// Least gas `migrate` is given. It also gets all the gas `upgrade` leaves unused,
+/// so attach as much as the migration of the stored state needs.
+const MIN_MIGRATE_GAS: Gas = Gas::from_tgas(50);
+
+/// Most ISBN collisions `migrate` logs one by one, well within the logs a call may write
+const MAX_LOGGED_COLLISIONS: usize = 50;
+
+/// Outcome of re-keying one account's library by canonical ISBN-13
+#[near(serializers = [json])]
+pub struct IsbnMigrationReport {
+    pub account_id: AccountId,
+    pub rekeyed: u32,
+    /// Entries left under their old key because another entry already holds the canonical ISBN
+    pub collisions: Vec<IsbnCollision>,
+    /// Entries left under their old key because the ISBN does not validate
+    pub invalid: Vec<String>,
+}
+
+#[near(serializers = [json])]
+pub struct IsbnCollision {
+    pub isbn: String,
+    pub canonical_isbn: String,
+}
+
+#[near]
+impl Contract {
+    /// Convert the stored state, in any earlier layout, into the current one.
+    /// Must be called by the contract account right after deploying new code;
+    /// `upgrade` does this in the same transaction.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let mut state = VersionedContract::read();
+        loop {
+            state = match state {
+                VersionedContract::V0(old) => return Self::from_v0(old),
+                VersionedContract::V1(old) => VersionedContract::V2(old.into()),
+                VersionedContract::V2(old) => VersionedContract::V3(old.into()),
+                VersionedContract::V3(old) => VersionedContract::V4(old.into()),
+                VersionedContract::V4(old) => VersionedContract::V5(old.into()),
+                VersionedContract::V5(old) => VersionedContract::V6(old.into()),
+                VersionedContract::V6(old) => VersionedContract::V7(old.into()),
+                VersionedContract::V7(contract) => return contract,
+            };
+        }
+    }
+
+    /// Deploy the wasm passed as the raw call input (not JSON) and call `migrate`
+    /// in the same receipt, so a failing migration also reverts the deploy
+    #[handle_result]
+    pub fn upgrade(&mut self) -> Result<Promise, BookyError> {
+        self.access.require_owner()?;
+        let code = env::input().filter(|code| !code.is_empty()).ok_or(BookyError::NoCodeAttached)?;
+
+        Ok(Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call_weight(
+                "migrate",
+                Vec::new(),
+                NearToken::from_yoctonear(0),
+                MIN_MIGRATE_GAS,
+                GasWeight(1),
+            ))
+    }
+
+    /// Layout version of the stored state
+    pub fn get_state_version(&self) -> u16 {
+        self.state_version.version
+    }
+
+    /// Re-key the given accounts' books by canonical ISBN-13. Libraries can't be
+    /// enumerated on-chain, so the caller supplies the accounts (e.g. from
+    /// book_added events) and should batch them to stay within gas and log limits.
+    /// Colliding and invalid entries stay reachable by their exact old ISBN.
+    #[handle_result]
+    pub fn migrate_isbns(&mut self, account_ids: Vec<AccountId>) -> Result<Vec<IsbnMigrationReport>, BookyError> {
+        self.access.require_owner()?;
+        Ok(account_ids
+            .into_iter()
+            .map(|account_id| self.rekey_library(account_id))
+            .collect())
+    }
+
+    /// Index the follow requests pending for the given accounts under their
+    /// requesters, which state versions before 6 did not do, so unregistering a
+    /// requester also drops its requests. Like `migrate_isbns`, the caller supplies
+    /// the targets (e.g. from follow_requested events). Returns how many requests
+    /// were indexed; the contract pays for the index entries.
+    #[handle_result]
+    pub fn migrate_follow_requests(&mut self, account_ids: Vec<AccountId>) -> Result<u32, BookyError> {
+        self.access.require_owner()?;
+        let mut indexed = 0;
+        for target in account_ids {
+            let requesters: Vec<AccountId> = match self.follow_requests.get(&target) {
+                Some(requests) => requests.iter().cloned().collect(),
+                None => continue,
+            };
+            for requester in requesters {
+                let sent = self.sent_follow_requests.entry(requester.clone()).or_insert_with(|| {
+                    IterableSet::new(StorageKey::SentFollowRequest {
+                        account_hash: env::sha256_array(requester.as_bytes()),
+                    })
+                });
+                indexed += sent.insert(target.clone()) as u32;
+            }
+        }
+        Ok(indexed)
+    }
+}
+
+impl Contract {
+    /// Convert the HashMap-based state into the collection-based layout
+    fn from_v0(old: ContractV0) -> Self {
+        let mut contract = Contract::default();
+        let mut collisions = Vec::new();
+
+        for (account_id, books) in old.libraries {
+            for book in books {
+                // Entries are keyed by canonical ISBN-13, as add_book keys them; invalid ISBNs
+                // are kept as they were. update_book never checked for ISBN collisions, and
+                // spellings of one ISBN collide once canonical, so later entries with a taken
+                // ISBN are kept under a numbered key, e.g. "9780451524935#2"
+                let mut book = VersionedBookEntry::V1(book.into()).into_current();
+                let stored_isbn = book.isbn.clone();
+                let isbn = normalize_isbn(&stored_isbn).unwrap_or_else(|_| stored_isbn.clone());
+                book.isbn = isbn.clone();
+                let mut copy = 1;
+                while contract.libraries.get(&account_id).is_some_and(|library| library.contains_key(&book.isbn)) {
+                    copy += 1;
+                    book.isbn = format!("{}#{}", isbn, copy);
+                }
+                if copy > 1 {
+                    collisions.push((account_id.clone(), stored_isbn, book.isbn.clone()));
+                }
+                contract.insert_book(account_id.clone(), book);
+            }
+        }
+
+        for (account_id, followed) in old.followed_accounts {
+            for followed_id in followed {
+                contract.insert_follow(account_id.clone(), followed_id);
+            }
+        }
+
+        log!("Migrated {} books, {} under a numbered key for a duplicate ISBN", contract.total_books, collisions.len());
+        for (account_id, stored_isbn, key) in collisions.iter().take(MAX_LOGGED_COLLISIONS) {
+            log!("Kept {} of {} under {}", stored_isbn, account_id, key);
+        }
+        contract
+    }
+
+    fn rekey_library(&mut self, account_id: AccountId) -> IsbnMigrationReport {
+        let mut report = IsbnMigrationReport {
+            account_id,
+            rekeyed: 0,
+            collisions: Vec::new(),
+            invalid: Vec::new(),
+        };
+
+        let library_privacy = self.library_privacy(&report.account_id);
+        let library = match self.libraries.get_mut(&report.account_id) {
+            Some(library) => library,
+            None => return report,
+        };
+
+        let keys: Vec<String> = library.keys().cloned().collect();
+        let mut moved = Vec::new();
+        for isbn in keys {
+            let canonical_isbn = match normalize_isbn(&isbn) {
+                Ok(canonical) => canonical,
+                Err(_) => {
+                    report.invalid.push(isbn);
+                    continue;
+                }
+            };
+
+            if canonical_isbn == isbn {
+                continue;
+            }
+            if library.contains_key(&canonical_isbn) {
+                report.collisions.push(IsbnCollision { isbn, canonical_isbn });
+                continue;
+            }
+
+            let mut entry = library.remove(&isbn).unwrap();
+            let book = entry.current_mut();
+            book.isbn = canonical_isbn.clone();
+            if is_published(book, library_privacy) {
+                BookyEvent::IsbnRekeyed { account_id: &report.account_id, old_isbn: &isbn, isbn: &canonical_isbn }
+                    .emit();
+            }
+            moved.push((isbn, canonical_isbn.clone(), book.rating, book.tags.clone()));
+            library.insert(canonical_isbn, entry);
+            report.rekeyed += 1;
+        }
+
+        for (isbn, canonical_isbn, rating, tags) in moved {
+            self.move_book_records(&report.account_id, &isbn, Some(&canonical_isbn), rating, &tags);
+            self.merge_legacy_tally(&isbn);
+        }
+        report
+    }
+}
@@ -0,0 +1,139 @@
+// Chapter notes and comments stored either as plain text or as a client-side encrypted envelope
+use crate::{is_published, BookyError, BookyEvent, Contract, ContractExt};
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::{env, near, AccountId};
+
+/// Longest algorithm identifier, in bytes
+pub const MAX_ALGORITHM_LEN: usize = 64;
+/// Longest nonce, in bytes
+pub const MAX_NONCE_LEN: usize = 64;
+/// Longest wrapped content key, in bytes
+pub const MAX_WRAPPED_KEY_LEN: usize = 512;
+/// Longest published public key, in bytes
+pub const MAX_PUBLIC_KEY_LEN: usize = 128;
+
+/// A note or comment. In JSON, plain text is a string and an encrypted note is an object.
+#[near(serializers = [json, borsh])]
+#[serde(untagged)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NoteContent {
+    Plain(String),
+    Encrypted(EncryptedEnvelope),
+}
+
+/// Ciphertext encrypted by the client under a random content key, with that
+/// key wrapped to each reader's public key. The contract never sees plaintext.
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncryptedEnvelope {
+    /// Cipher suite identifier, e.g. "x25519-xsalsa20-poly1305"
+    pub algorithm: String,
+    pub nonce: Base64VecU8,
+    pub ciphertext: Base64VecU8,
+    pub recipients: Vec<RecipientKey>,
+}
+
+/// The content key of an envelope, encrypted to one account's public key
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecipientKey {
+    pub account_id: AccountId,
+    pub wrapped_key: Base64VecU8,
+}
+
+impl NoteContent {
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Self::Plain(text) if text.is_empty())
+    }
+}
+
+impl Default for NoteContent {
+    fn default() -> Self {
+        Self::Plain(String::new())
+    }
+}
+
+impl From<String> for NoteContent {
+    fn from(text: String) -> Self {
+        Self::Plain(text)
+    }
+}
+
+impl From<&str> for NoteContent {
+    fn from(text: &str) -> Self {
+        Self::Plain(text.to_string())
+    }
+}
+
+#[near]
+impl Contract {
+    /// Publish the caller's public key for encrypted notes, or remove it with null
+    #[handle_result]
+    pub fn set_note_public_key(&mut self, public_key: Option<Base64VecU8>) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+
+        match &public_key {
+            Some(key) if key.0.is_empty() || key.0.len() > MAX_PUBLIC_KEY_LEN => {
+                return Err(BookyError::InvalidPublicKey { max_length: MAX_PUBLIC_KEY_LEN as u32 });
+            }
+            Some(key) => {
+                self.note_public_keys.insert(account_id.clone(), key.clone());
+            }
+            None => {
+                self.note_public_keys.remove(&account_id);
+            }
+        }
+        BookyEvent::NotePublicKeyChanged { account_id: &account_id, public_key: public_key.as_ref() }.emit();
+
+        self.charge_storage(&account_id, initial_storage)
+    }
+
+    pub fn get_note_public_key(&self, account_id: AccountId) -> Option<Base64VecU8> {
+        self.note_public_keys.get(&account_id).cloned()
+    }
+
+    /// Grant `recipient` access to an encrypted chapter note by adding the note's
+    /// content key wrapped to their published public key. Sharing again replaces
+    /// their wrapped key.
+    #[handle_result]
+    pub fn share_note_key(
+        &mut self,
+        isbn: String,
+        chapter: u32,
+        recipient: AccountId,
+        wrapped_key: Base64VecU8,
+    ) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+
+        if !self.note_public_keys.contains_key(&recipient) {
+            return Err(BookyError::NoPublicKey(recipient));
+        }
+        let limits = self.field_limits;
+        let library_privacy = self.library_privacy(&account_id);
+        let book = self.book_mut(&account_id, &isbn)?;
+        let envelope = match book.chapter_notes.get_mut(&chapter) {
+            Some(NoteContent::Encrypted(envelope)) => envelope,
+            Some(NoteContent::Plain(_)) => return Err(BookyError::NoteNotEncrypted { chapter }),
+            None => return Err(BookyError::NoteNotFound { chapter }),
+        };
+
+        let mut shared = envelope.clone();
+        match shared.recipients.iter_mut().find(|r| r.account_id == recipient) {
+            Some(existing) => existing.wrapped_key = wrapped_key,
+            None => shared.recipients.push(RecipientKey { account_id: recipient.clone(), wrapped_key }),
+        }
+        limits.check_envelope("note", &shared, limits.max_note_len)?;
+        *envelope = shared;
+
+        if is_published(book, library_privacy) {
+            BookyEvent::NoteKeyShared { account_id: &account_id, isbn: &book.isbn, chapter, recipient: &recipient }
+                .emit();
+        }
+
+        self.charge_storage(&account_id, initial_storage)
+    }
+}
@@ -0,0 +1,76 @@
+// Structured reading positions, so progress can be computed for books read by page,
+// on e-readers or as audiobooks
+use crate::contents::Contents;
+use crate::{BookEntry, ReadingStatus};
+use near_sdk::near;
+use std::fmt;
+
+/// Where the reader is, in the unit that suits how the book is read
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadingPosition {
+    /// Out of the book's `total_pages`
+    Page { page: u32 },
+    Percent { percent: u8 },
+    /// E-reader location, out of `total_locations` when the reader shows it
+    Location { location: u32, total_locations: Option<u32> },
+    /// Seconds into an audiobook, out of `total_seconds` when known
+    Timestamp { seconds: u32, total_seconds: Option<u32> },
+    /// A paragraph within a chapter, or a section on books with a table of contents
+    ChapterParagraph { chapter: u32, paragraph: u32 },
+}
+
+impl ReadingPosition {
+    /// Share of the book before this position, if it can be told from the position
+    /// alone or with the book's page count
+    pub fn percent(&self, total_pages: Option<u32>) -> Option<u8> {
+        let share = |done: u32, total: u32| (total > 0).then(|| (done.min(total) as u64 * 100 / total as u64) as u8);
+        match *self {
+            Self::Page { page } => share(page, total_pages?),
+            Self::Percent { percent } => Some(percent),
+            Self::Location { location, total_locations } => share(location, total_locations?),
+            Self::Timestamp { seconds, total_seconds } => share(seconds, total_seconds?),
+            Self::ChapterParagraph { .. } => None,
+        }
+    }
+
+    /// Chapter the position is in, for positions that name one
+    pub fn chapter(&self) -> Option<u32> {
+        match *self {
+            Self::ChapterParagraph { chapter, .. } => Some(chapter),
+            _ => None,
+        }
+    }
+}
+
+/// The text kept in `last_read_position` when an update only gives a structured position
+impl fmt::Display for ReadingPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Page { page } => write!(f, "page {}", page),
+            Self::Percent { percent } => write!(f, "{}%", percent),
+            Self::Location { location, .. } => write!(f, "location {}", location),
+            Self::Timestamp { seconds, .. } => {
+                write!(f, "{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+            }
+            Self::ChapterParagraph { chapter, paragraph } => write!(f, "chapter {} paragraph {}", chapter, paragraph),
+        }
+    }
+}
+
+impl BookEntry {
+    /// Share of the book read: from the reading position where it tells, or else the
+    /// share of sections read. Null when neither is known, and 100 once completed.
+    pub fn progress_percent(&self) -> Option<u8> {
+        if self.reading_status == ReadingStatus::Completed {
+            return Some(100);
+        }
+        if let Some(percent) = self.position.and_then(|position| position.percent(self.total_pages)) {
+            return Some(percent);
+        }
+        let contents = Contents::of(self);
+        let total = contents.count()?;
+        let read = self.chapters_read.iter().filter(|c| contents.contains(**c)).count() as u64;
+        Some((read * 100 / total as u64) as u8)
+    }
+}
@@ -0,0 +1,153 @@
+// Who can see a library or book, and which personal fields other viewers get
+use crate::{emit_book_written, BookEntry, BookyError, BookyEvent, Contract, ContractExt, NoteContent};
+use near_sdk::{env, near, AccountId};
+use std::borrow::Cow;
+
+/// Who can see a library or book
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub enum Visibility {
+    /// Anyone, including views called without a viewer
+    #[default]
+    Public,
+    /// The owner and accounts following them
+    FollowersOnly,
+    /// Only the owner
+    Private,
+}
+
+/// Visibility, plus personal fields hidden from everyone but the owner
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PrivacySettings {
+    #[serde(default)]
+    pub visibility: Visibility,
+    /// Hide `chapter_notes` from other viewers
+    #[serde(default)]
+    pub hide_notes: bool,
+    /// Hide `personal_comments`, and the price and location of owned copies, from other viewers
+    #[serde(default)]
+    pub hide_comments: bool,
+}
+
+/// How a viewer relates to a library's owner, from least to most access
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Audience {
+    /// Blocked by the owner; sees nothing, whatever the visibility
+    Blocked,
+    Public,
+    Follower,
+    Owner,
+}
+
+impl PrivacySettings {
+    /// Combine library-wide settings with a book's own, keeping whichever is stricter
+    pub fn restrict(self, book: Option<PrivacySettings>) -> Self {
+        match book {
+            Some(book) => Self {
+                visibility: self.visibility.max(book.visibility),
+                hide_notes: self.hide_notes || book.hide_notes,
+                hide_comments: self.hide_comments || book.hide_comments,
+            },
+            None => self,
+        }
+    }
+
+    pub fn allows(&self, audience: Audience) -> bool {
+        match self.visibility {
+            Visibility::Public => audience >= Audience::Public,
+            Visibility::FollowersOnly => audience >= Audience::Follower,
+            Visibility::Private => audience == Audience::Owner,
+        }
+    }
+
+    /// `book` as `audience` may see it under these library-wide settings,
+    /// or None if it is hidden from them
+    pub fn view(self, book: Cow<'_, BookEntry>, audience: Audience) -> Option<Cow<'_, BookEntry>> {
+        let settings = self.restrict(book.privacy);
+        if !settings.allows(audience) {
+            return None;
+        }
+
+        let hides_notes = settings.hide_notes && !book.chapter_notes.is_empty();
+        let hides_comments = settings.hide_comments
+            && (!book.personal_comments.is_empty()
+                || book.copies.iter().any(|copy| copy.price_paid.is_some() || copy.location.is_some()));
+        if audience == Audience::Owner || !(hides_notes || hides_comments) {
+            return Some(book);
+        }
+
+        let mut book = book.into_owned();
+        if hides_notes {
+            book.chapter_notes.clear();
+        }
+        if hides_comments {
+            book.personal_comments = NoteContent::default();
+            for copy in &mut book.copies {
+                copy.price_paid = None;
+                copy.location = None;
+            }
+        }
+        Some(Cow::Owned(book))
+    }
+}
+
+#[near]
+impl Contract {
+    /// Privacy settings that apply to every book in an account's library
+    pub fn get_library_privacy(&self, account_id: AccountId) -> PrivacySettings {
+        self.library_privacy(&account_id)
+    }
+
+    /// Set the caller's library-wide privacy. A book with its own settings
+    /// gets the stricter of the two.
+    #[handle_result]
+    pub fn set_library_privacy(&mut self, settings: PrivacySettings) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+
+        // Public is the default, so don't pay for storing it
+        if settings == PrivacySettings::default() {
+            self.privacy_settings.remove(&account_id);
+        } else {
+            self.privacy_settings.insert(account_id.clone(), settings);
+        }
+        BookyEvent::LibraryPrivacyChanged { account_id: &account_id, settings }.emit();
+
+        self.charge_storage(&account_id, initial_storage)
+    }
+
+    /// Set or clear a book's own privacy settings
+    #[handle_result]
+    pub fn set_book_privacy(&mut self, isbn: String, privacy: Option<PrivacySettings>) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let library_privacy = self.library_privacy(&account_id);
+        let book = self.book_mut(&account_id, &isbn)?;
+
+        let old = book.clone();
+        book.privacy = privacy;
+        emit_book_written(&account_id, None, Some(&old), book, library_privacy);
+
+        self.charge_storage(&account_id, initial_storage)
+    }
+}
+
+impl Contract {
+    pub(crate) fn library_privacy(&self, account_id: &AccountId) -> PrivacySettings {
+        self.privacy_settings.get(account_id).copied().unwrap_or_default()
+    }
+
+    /// How `viewer` relates to `account_id`; no viewer is the public
+    pub(crate) fn audience(&self, account_id: &AccountId, viewer: Option<&AccountId>) -> Audience {
+        match viewer {
+            Some(viewer) if viewer == account_id => Audience::Owner,
+            Some(viewer) if self.has_blocked(account_id, viewer) => Audience::Blocked,
+            // Only approved follows count, pending requests don't
+            Some(viewer) if self.is_following_account(viewer, account_id) => Audience::Follower,
+            _ => Audience::Public,
+        }
+    }
+}
@@ -0,0 +1,129 @@
+// Pagination, filtering and sorting for list views
+use crate::{BookEntry, ReadingStatus};
+use near_sdk::near;
+use std::cmp::Ordering;
+
+/// Page size used when a view is called without `limit`
+pub const DEFAULT_PAGE_LIMIT: u32 = 50;
+/// Largest page a view will return, to stay under view-call size limits
+pub const MAX_PAGE_LIMIT: u32 = 100;
+
+/// One page of a list view
+#[near(serializers = [json, borsh])]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Number of items matching the query across all pages
+    pub total: u32,
+    /// `from_index` to pass for the next page, or null on the last page
+    pub next_index: Option<u32>,
+}
+
+/// Field to sort library views by
+#[near(serializers = [json])]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SortBy {
+    Title,
+    Author,
+    LastReadDate,
+    AcquisitionDate,
+}
+
+/// Filters and sort order for library views. Every field is optional.
+#[near(serializers = [json])]
+#[derive(Default)]
+pub struct LibraryQuery {
+    pub reading_status: Option<ReadingStatus>,
+    /// Case-insensitive substring of the author name
+    pub author: Option<String>,
+    /// Earliest acquisition date, inclusive (YYYY-MM-DD)
+    pub acquired_from: Option<String>,
+    /// Latest acquisition date, inclusive (YYYY-MM-DD)
+    pub acquired_to: Option<String>,
+    pub sort_by: Option<SortBy>,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+impl LibraryQuery {
+    /// Whether the query only pages through the library in storage order
+    pub fn is_unfiltered(&self) -> bool {
+        self.reading_status.is_none()
+            && self.author.is_none()
+            && self.acquired_from.is_none()
+            && self.acquired_to.is_none()
+            && self.sort_by.is_none()
+    }
+
+    pub fn matches(&self, book: &BookEntry) -> bool {
+        if let Some(status) = self.reading_status {
+            if book.reading_status != status {
+                return false;
+            }
+        }
+        if let Some(author) = &self.author {
+            if !book.author.to_lowercase().contains(&author.to_lowercase()) {
+                return false;
+            }
+        }
+        // ISO dates compare correctly as strings
+        if let Some(from) = &self.acquired_from {
+            if book.acquisition_date.as_str() < from.as_str() {
+                return false;
+            }
+        }
+        if let Some(to) = &self.acquired_to {
+            if book.acquisition_date.as_str() > to.as_str() {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Sort books in place; books without a last read date always sort last
+    pub fn sort(&self, books: &mut [&BookEntry]) {
+        let sort_by = match self.sort_by {
+            Some(sort_by) => sort_by,
+            None => return,
+        };
+
+        books.sort_by(|a, b| {
+            let ordering = match sort_by {
+                SortBy::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+                SortBy::Author => a.author.to_lowercase().cmp(&b.author.to_lowercase()),
+                SortBy::AcquisitionDate => a.acquisition_date.cmp(&b.acquisition_date),
+                SortBy::LastReadDate => match (&a.last_read_date, &b.last_read_date) {
+                    (Some(a), Some(b)) => a.cmp(b),
+                    (Some(_), None) => return Ordering::Less,
+                    (None, Some(_)) => return Ordering::Greater,
+                    (None, None) => Ordering::Equal,
+                },
+            };
+            if self.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+}
+
+/// Take one page out of `items`, which yields `total` items in all. A `limit` of 0
+/// is raised to 1, so following `next_index` always gets somewhere.
+pub fn paginate<T>(
+    items: impl Iterator<Item = T>,
+    total: u32,
+    from_index: Option<u32>,
+    limit: Option<u32>,
+) -> Page<T> {
+    let from_index = from_index.unwrap_or(0);
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT);
+
+    let items: Vec<T> = items.skip(from_index as usize).take(limit as usize).collect();
+    let end = from_index.saturating_add(items.len() as u32);
+
+    Page {
+        next_index: if end < total { Some(end) } else { None },
+        items,
+        total,
+    }
+}
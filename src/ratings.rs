@@ -0,0 +1,165 @@
+// Ratings and reviews, with a running tally of every library's rating of each ISBN
+use crate::cycles::{check_rating, MAX_RATING};
+use crate::{is_published, normalize_isbn, BookyError, BookyEvent, Contract, ContractExt};
+use near_sdk::json_types::U64;
+use near_sdk::{env, near};
+
+/// Fewest ratings a summary shows, so no single private rating can be read off it
+pub const MIN_RATINGS_SHOWN: u32 = 3;
+
+/// A reader's review of a book
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Review {
+    pub text: String,
+    /// Whether the text gives away the plot, so clients can hide it until asked
+    pub spoiler: bool,
+    /// Block timestamp of the last edit, in nanoseconds
+    pub written_at: U64,
+}
+
+/// Count of each rating given to an ISBN, across all libraries
+#[near(serializers = [borsh])]
+#[derive(Default)]
+pub struct RatingTally {
+    histogram: [u32; MAX_RATING as usize],
+}
+
+/// Ratings given to an ISBN across all libraries
+#[near(serializers = [json])]
+#[derive(Debug, PartialEq)]
+pub struct RatingSummary {
+    pub isbn: String,
+    pub ratings: u32,
+    /// Average rating times 100, e.g. 725 for 7.25; null when there are no ratings
+    pub average_x100: Option<u32>,
+    /// Number of ratings of each value; index 0 counts ratings of 1
+    pub histogram: Vec<u32>,
+}
+
+impl RatingTally {
+    fn count(&self) -> u32 {
+        self.histogram.iter().sum()
+    }
+
+    fn summary(&self, isbn: String) -> RatingSummary {
+        let ratings = self.count();
+        let total: u64 = (1..).zip(self.histogram).map(|(rating, count)| rating * count as u64).sum();
+        RatingSummary {
+            isbn,
+            ratings,
+            average_x100: (ratings > 0).then(|| (total * 100 / ratings as u64) as u32),
+            histogram: self.histogram.to_vec(),
+        }
+    }
+}
+
+#[near]
+impl Contract {
+    /// Rate a book from 1 to 10 half-stars, or clear the rating with null
+    #[handle_result]
+    pub fn rate_book(&mut self, isbn: String, rating: Option<u8>) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        if let Some(rating) = rating {
+            check_rating(rating)?;
+        }
+        let library_privacy = self.library_privacy(&account_id);
+        let book = self.book_mut(&account_id, &isbn)?;
+
+        let previous = std::mem::replace(&mut book.rating, rating);
+        if is_published(book, library_privacy) {
+            BookyEvent::BookRated { account_id: &account_id, isbn: &book.isbn, rating }.emit();
+        }
+        let isbn = book.isbn.clone();
+        let tally_bytes = self.tally_rating(&isbn, previous, rating);
+
+        self.charge_storage(&account_id, initial_storage.saturating_add_signed(tally_bytes))
+    }
+
+    /// Write or replace the caller's review of a book, or delete it with a null `text`
+    #[handle_result]
+    pub fn write_review(&mut self, isbn: String, text: Option<String>, spoiler: Option<bool>) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let limits = self.field_limits;
+        let library_privacy = self.library_privacy(&account_id);
+        let review = match text {
+            Some(text) => {
+                limits.check_review(&text)?;
+                Some(Review { text, spoiler: spoiler.unwrap_or(false), written_at: U64(env::block_timestamp()) })
+            }
+            None => None,
+        };
+        let book = self.book_mut(&account_id, &isbn)?;
+
+        book.review = review;
+        if is_published(book, library_privacy) {
+            BookyEvent::ReviewWritten { account_id: &account_id, isbn: &book.isbn, review: book.review.as_ref() }
+                .emit();
+        }
+
+        self.charge_storage(&account_id, initial_storage)
+    }
+
+    /// Average and histogram of the ratings every library has given an ISBN, in any
+    /// form. Ratings count whatever the library's privacy, so the summary stays empty
+    /// until `MIN_RATINGS_SHOWN` libraries have rated the ISBN.
+    pub fn get_book_rating_summary(&self, isbn: String) -> RatingSummary {
+        let isbn = tally_key(&isbn);
+        match self.rating_tallies.get(&isbn) {
+            Some(tally) if tally.count() >= MIN_RATINGS_SHOWN => tally.summary(isbn),
+            _ => RatingTally::default().summary(isbn),
+        }
+    }
+}
+
+impl Contract {
+    /// Replace one library's rating of `isbn` in the tally, dropping tallies with no ratings left.
+    /// Tallies are shared, so the contract pays for them rather than whoever rates first:
+    /// returns the bytes the tally grew by (negative if it shrank), for the caller to leave
+    /// out of what it charges.
+    pub(crate) fn tally_rating(&mut self, isbn: &str, previous: Option<u8>, rating: Option<u8>) -> i64 {
+        if previous == rating {
+            return 0;
+        }
+        self.rating_tallies.flush();
+        let initial_storage = env::storage_usage();
+        let isbn = tally_key(isbn);
+        let tally = self.rating_tallies.entry(isbn.clone()).or_default();
+        if let Some(previous) = previous {
+            tally.histogram[previous as usize - 1] -= 1;
+        }
+        if let Some(rating) = rating {
+            tally.histogram[rating as usize - 1] += 1;
+        }
+        if tally.count() == 0 {
+            self.rating_tallies.remove(&isbn);
+        }
+        self.rating_tallies.flush();
+        env::storage_usage() as i64 - initial_storage as i64
+    }
+
+    /// Fold a tally stored under a library key that isn't a canonical ISBN-13, as
+    /// tallies of legacy entries were before `tally_key`, into the canonical one
+    pub(crate) fn merge_legacy_tally(&mut self, isbn: &str) {
+        let key = tally_key(isbn);
+        if key == isbn {
+            return;
+        }
+        if let Some(legacy) = self.rating_tallies.remove(isbn) {
+            let tally = self.rating_tallies.entry(key).or_default();
+            for (count, legacy_count) in tally.histogram.iter_mut().zip(legacy.histogram) {
+                *count += legacy_count;
+            }
+        }
+    }
+}
+
+/// The key of an ISBN's rating tally: its canonical ISBN-13, so ratings of entries
+/// still keyed by another spelling count towards the same summary
+pub(crate) fn tally_key(isbn: &str) -> String {
+    normalize_isbn(isbn).unwrap_or_else(|_| isbn.to_string())
+}
@@ -0,0 +1,171 @@
+// Per-book log of reading sessions, one per progress update, and the reading pace derived from it
+use crate::{Contract, ContractExt, StorageKey};
+use near_sdk::json_types::U64;
+use near_sdk::store::IterableMap;
+use near_sdk::{env, near, AccountId};
+
+/// Most sessions kept per book; past this the two oldest are merged into one
+pub const MAX_SESSIONS_PER_BOOK: usize = 50;
+
+const NANOS_PER_WEEK: u128 = 7 * 24 * 60 * 60 * 1_000_000_000;
+
+/// One progress update, or several consecutive ones merged by compaction
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReadingSession {
+    /// Block timestamp in nanoseconds; a merged session keeps its first one
+    pub timestamp: U64,
+    pub from_chapter: u32,
+    pub to_chapter: u32,
+    /// `last_read_position` after the update
+    pub position: String,
+    pub minutes: Option<u32>,
+    /// Number of progress updates this entry covers
+    pub updates: u32,
+}
+
+/// Timestamps in nanoseconds, both ends inclusive. A missing end is unbounded.
+#[near(serializers = [json])]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TimeRange {
+    pub from: Option<U64>,
+    pub to: Option<U64>,
+}
+
+/// Reading pace of one book, derived from its session log
+#[near(serializers = [json])]
+#[derive(Debug, PartialEq)]
+pub struct ReadingPace {
+    pub updates: u32,
+    /// Chapters moved forward across all sessions
+    pub chapters_advanced: u32,
+    /// Minutes read, over the sessions that reported them
+    pub minutes_read: u64,
+    pub first_session: U64,
+    pub last_session: U64,
+    /// Chapters advanced per week between the first and last session
+    pub chapters_per_week: Option<u32>,
+    /// Average minutes per chapter, over the sessions that reported minutes
+    pub minutes_per_chapter: Option<u32>,
+}
+
+impl ReadingSession {
+    /// Combine two consecutive sessions, `self` being the older
+    fn merge(self, newer: ReadingSession) -> ReadingSession {
+        let minutes = match (self.minutes, newer.minutes) {
+            (Some(older), Some(newer)) => Some(older.saturating_add(newer)),
+            (older, newer) => older.or(newer),
+        };
+        ReadingSession {
+            timestamp: self.timestamp,
+            from_chapter: self.from_chapter,
+            to_chapter: newer.to_chapter,
+            position: newer.position,
+            minutes,
+            updates: self.updates + newer.updates,
+        }
+    }
+
+    fn chapters_advanced(&self) -> u32 {
+        self.to_chapter.saturating_sub(self.from_chapter)
+    }
+}
+
+impl TimeRange {
+    fn contains(&self, timestamp: U64) -> bool {
+        self.from.is_none_or(|from| timestamp.0 >= from.0) && self.to.is_none_or(|to| timestamp.0 <= to.0)
+    }
+}
+
+/// Pace over a non-empty, oldest-first session log
+fn reading_pace(sessions: &[ReadingSession]) -> Option<ReadingPace> {
+    let (first, last) = (sessions.first()?, sessions.last()?);
+    let chapters_advanced: u32 = sessions.iter().map(ReadingSession::chapters_advanced).sum();
+    let timed = sessions.iter().filter(|session| session.minutes.is_some());
+    let minutes_read: u64 = timed.clone().filter_map(|session| session.minutes).map(u64::from).sum();
+    let timed_chapters: u64 = timed.map(|session| session.chapters_advanced() as u64).sum();
+
+    let span = (last.timestamp.0 - first.timestamp.0) as u128;
+    Some(ReadingPace {
+        updates: sessions.iter().map(|session| session.updates).sum(),
+        chapters_advanced,
+        minutes_read,
+        first_session: first.timestamp,
+        last_session: last.timestamp,
+        chapters_per_week: (span > 0).then(|| (chapters_advanced as u128 * NANOS_PER_WEEK / span) as u32),
+        minutes_per_chapter: (timed_chapters > 0).then(|| (minutes_read / timed_chapters) as u32),
+    })
+}
+
+#[near]
+impl Contract {
+    /// Get a book's reading sessions within `range`, oldest first, if `viewer` may see the book
+    pub fn get_reading_sessions(
+        &self,
+        account_id: AccountId,
+        isbn: String,
+        range: Option<TimeRange>,
+        viewer: Option<AccountId>,
+    ) -> Vec<ReadingSession> {
+        let range = range.unwrap_or_default();
+        self.visible_sessions(&account_id, &isbn, viewer.as_ref())
+            .iter()
+            .filter(|session| range.contains(session.timestamp))
+            .cloned()
+            .collect()
+    }
+
+    /// Reading pace of a book, or null if it has no sessions or `viewer` may not see it
+    pub fn get_reading_pace(&self, account_id: AccountId, isbn: String, viewer: Option<AccountId>) -> Option<ReadingPace> {
+        reading_pace(self.visible_sessions(&account_id, &isbn, viewer.as_ref()))
+    }
+}
+
+impl Contract {
+    /// Append a session to a book's log, merging the oldest two once it is full
+    pub(crate) fn record_session(&mut self, account_id: &AccountId, isbn: &str, session: ReadingSession) {
+        let books = self.reading_sessions.entry(account_id.clone()).or_insert_with(|| {
+            IterableMap::new(StorageKey::Sessions {
+                account_hash: env::sha256_array(account_id.as_bytes()),
+            })
+        });
+        let sessions = books.entry(isbn.to_string()).or_default();
+        sessions.push(session);
+        if sessions.len() > MAX_SESSIONS_PER_BOOK {
+            let newer = sessions.remove(1);
+            sessions[0] = sessions[0].clone().merge(newer);
+        }
+    }
+
+    /// Drop a book's session log, or move it to a new ISBN
+    pub(crate) fn move_sessions(&mut self, account_id: &AccountId, isbn: &str, new_isbn: Option<&str>) {
+        let books = match self.reading_sessions.get_mut(account_id) {
+            Some(books) => books,
+            None => return,
+        };
+        let sessions = match books.remove(isbn) {
+            Some(sessions) => sessions,
+            None => return,
+        };
+        match new_isbn {
+            Some(new_isbn) => {
+                books.insert(new_isbn.to_string(), sessions);
+            }
+            None if books.is_empty() => {
+                self.reading_sessions.remove(account_id);
+            }
+            None => {}
+        }
+    }
+
+    fn visible_sessions(&self, account_id: &AccountId, isbn: &str, viewer: Option<&AccountId>) -> &[ReadingSession] {
+        let book = match self.visible_book(account_id, isbn, viewer) {
+            Some(book) => book,
+            None => return &[],
+        };
+        self.reading_sessions
+            .get(account_id)
+            .and_then(|books| books.get(&book.isbn))
+            .map_or(&[], Vec::as_slice)
+    }
+}
@@ -0,0 +1,396 @@
+// User-defined shelves and free-form tags, indexed per account so views don't scan the library
+use crate::privacy::Audience;
+use crate::query::paginate;
+use crate::{is_published, BookEntry, BookyError, BookyEvent, Contract, ContractExt, Page, StorageKey};
+use near_sdk::json_types::U64;
+use near_sdk::store::IterableMap;
+use near_sdk::{env, log, near, AccountId};
+
+/// Most shelves one account can have
+pub const MAX_SHELVES: u32 = 100;
+/// Most books one shelf can hold
+pub const MAX_SHELF_BOOKS: usize = 1000;
+
+/// A named, ordered list of books in one library
+#[near(serializers = [borsh])]
+pub struct Shelf {
+    pub name: String,
+    pub description: String,
+    /// ISBNs in the owner's chosen order
+    pub books: Vec<String>,
+    pub created_at: U64,
+}
+
+/// A shelf without its books
+#[near(serializers = [json])]
+pub struct ShelfInfo {
+    pub shelf_id: u32,
+    pub name: String,
+    pub description: String,
+    pub book_count: u32,
+    pub created_at: U64,
+}
+
+/// A shelf with one page of the books on it that the viewer may see
+#[near(serializers = [json])]
+pub struct ShelfPage {
+    pub shelf: ShelfInfo,
+    pub books: Page<BookEntry>,
+}
+
+#[near(serializers = [json])]
+pub struct TagCount {
+    pub tag: String,
+    pub books: u32,
+}
+
+/// An account's shelves, by id in creation order
+#[near(serializers = [borsh])]
+pub struct AccountShelves {
+    next_id: u32,
+    shelves: IterableMap<u32, Shelf>,
+}
+
+impl AccountShelves {
+    fn new(account_id: &AccountId) -> Self {
+        Self {
+            next_id: 0,
+            shelves: IterableMap::new(StorageKey::Shelf {
+                account_hash: env::sha256_array(account_id.as_bytes()),
+            }),
+        }
+    }
+
+    pub fn flush(&mut self) {
+        self.shelves.flush();
+    }
+
+    pub fn clear(&mut self) {
+        self.shelves.clear();
+    }
+}
+
+impl Shelf {
+    fn info(&self, shelf_id: u32) -> ShelfInfo {
+        ShelfInfo {
+            shelf_id,
+            name: self.name.clone(),
+            description: self.description.clone(),
+            book_count: self.books.len() as u32,
+            created_at: self.created_at,
+        }
+    }
+}
+
+/// Trimmed, lowercased and deduplicated tags, in the order given
+pub fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized
+}
+
+#[near]
+impl Contract {
+    /// Create an empty shelf and return its id
+    #[handle_result]
+    pub fn create_shelf(&mut self, name: String, description: Option<String>) -> Result<u32, BookyError> {
+        self.access.require_not_paused()?;
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let description = description.unwrap_or_default();
+        self.field_limits.check_shelf(&name, &description)?;
+
+        let account_shelves = self.shelves
+            .entry(account_id.clone())
+            .or_insert_with(|| AccountShelves::new(&account_id));
+        if account_shelves.shelves.len() >= MAX_SHELVES {
+            return Err(BookyError::TooManyShelves { max: MAX_SHELVES });
+        }
+        let shelf_id = account_shelves.next_id;
+        account_shelves.next_id += 1;
+        account_shelves.shelves.insert(
+            shelf_id,
+            Shelf { name, description, books: Vec::new(), created_at: U64(env::block_timestamp()) },
+        );
+        if self.library_privacy(&account_id).allows(Audience::Public) {
+            BookyEvent::ShelfCreated { account_id: &account_id, shelf_id }.emit();
+        }
+
+        self.charge_storage(&account_id, initial_storage)?;
+        Ok(shelf_id)
+    }
+
+    /// Delete a shelf. The books on it stay in the library.
+    #[handle_result]
+    pub fn delete_shelf(&mut self, shelf_id: u32) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+
+        let account_shelves = self.shelves.get_mut(&account_id).ok_or(BookyError::ShelfNotFound { shelf_id })?;
+        if account_shelves.shelves.remove(&shelf_id).is_none() {
+            return Err(BookyError::ShelfNotFound { shelf_id });
+        }
+        if self.library_privacy(&account_id).allows(Audience::Public) {
+            BookyEvent::ShelfDeleted { account_id: &account_id, shelf_id }.emit();
+        }
+
+        self.charge_storage(&account_id, initial_storage)
+    }
+
+    /// Put a book from the caller's library on a shelf, at `position` or at the end
+    #[handle_result]
+    pub fn add_to_shelf(&mut self, shelf_id: u32, isbn: String, position: Option<u32>) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let (isbn, published) = self.shelf_entry(&account_id, &isbn)?;
+
+        let shelf = self.shelf_mut(&account_id, shelf_id)?;
+        if shelf.books.contains(&isbn) {
+            log!("Book {} is already on shelf {}", isbn, shelf_id);
+            return Ok(());
+        }
+        if shelf.books.len() >= MAX_SHELF_BOOKS {
+            return Err(BookyError::ShelfFull { max: MAX_SHELF_BOOKS as u32 });
+        }
+        let position = position.map_or(shelf.books.len(), |p| (p as usize).min(shelf.books.len()));
+        shelf.books.insert(position, isbn.clone());
+        if published {
+            BookyEvent::BookShelved { account_id: &account_id, shelf_id, isbn: &isbn, position: position as u32 }
+                .emit();
+        }
+
+        self.charge_storage(&account_id, initial_storage)
+    }
+
+    #[handle_result]
+    pub fn remove_from_shelf(&mut self, shelf_id: u32, isbn: String) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let (isbn, published) = self.shelf_entry(&account_id, &isbn)?;
+
+        let shelf = self.shelf_mut(&account_id, shelf_id)?;
+        match shelf.books.iter().position(|shelved| *shelved == isbn) {
+            Some(index) => {
+                shelf.books.remove(index);
+            }
+            None => return Err(BookyError::NotOnShelf { shelf_id, isbn }),
+        }
+        if published {
+            BookyEvent::BookUnshelved { account_id: &account_id, shelf_id, isbn: &isbn }.emit();
+        }
+
+        self.charge_storage(&account_id, initial_storage)
+    }
+
+    /// Put a shelf's books in a new order. `isbns` must list exactly the books on the shelf.
+    #[handle_result]
+    pub fn reorder_shelf(&mut self, shelf_id: u32, isbns: Vec<String>) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let isbns: Vec<String> = isbns
+            .into_iter()
+            .map(|isbn| self.find_book(&account_id, &isbn).map_or(isbn, |book| book.isbn.clone()))
+            .collect();
+        let published = self.library_privacy(&account_id).allows(Audience::Public);
+
+        let shelf = self.shelf_mut(&account_id, shelf_id)?;
+        let mut current = shelf.books.clone();
+        let mut reordered = isbns.clone();
+        current.sort();
+        reordered.sort();
+        if current != reordered {
+            return Err(BookyError::InvalidShelfOrder { shelf_id });
+        }
+        shelf.books = isbns;
+        if published {
+            BookyEvent::ShelfReordered { account_id: &account_id, shelf_id }.emit();
+        }
+
+        self.charge_storage(&account_id, initial_storage)
+    }
+
+    /// Replace a book's tags. Tags are trimmed and lowercased; an empty list removes them all.
+    #[handle_result]
+    pub fn tag_book(&mut self, isbn: String, tags: Vec<String>) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let tags = normalize_tags(tags);
+        self.field_limits.check_tags(&tags)?;
+        let library_privacy = self.library_privacy(&account_id);
+        let book = self.book_mut(&account_id, &isbn)?;
+
+        let previous = std::mem::replace(&mut book.tags, tags.clone());
+        if is_published(book, library_privacy) {
+            BookyEvent::BookTagged { account_id: &account_id, isbn: &book.isbn, tags: &tags }.emit();
+        }
+        let isbn = book.isbn.clone();
+        self.index_tags(&account_id, &isbn, &previous, &tags);
+
+        self.charge_storage(&account_id, initial_storage)
+    }
+
+    /// Every shelf of an account, in creation order, if `viewer` may see the library
+    pub fn get_shelves(&self, account_id: AccountId, viewer: Option<AccountId>) -> Vec<ShelfInfo> {
+        let audience = self.audience(&account_id, viewer.as_ref());
+        match self.shelves.get(&account_id) {
+            Some(account_shelves) if self.library_privacy(&account_id).allows(audience) => {
+                account_shelves.shelves.iter().map(|(shelf_id, shelf)| shelf.info(*shelf_id)).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// A shelf with a page of its books, in shelf order, that `viewer` may see
+    pub fn get_shelf(
+        &self,
+        account_id: AccountId,
+        shelf_id: u32,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+        viewer: Option<AccountId>,
+    ) -> Option<ShelfPage> {
+        let audience = self.audience(&account_id, viewer.as_ref());
+        if !self.library_privacy(&account_id).allows(audience) {
+            return None;
+        }
+        let shelf = self.shelves.get(&account_id)?.shelves.get(&shelf_id)?;
+        Some(ShelfPage {
+            shelf: shelf.info(shelf_id),
+            books: self.books_page(&account_id, audience, &shelf.books, from_index, limit),
+        })
+    }
+
+    /// A page of the books in an account's library with a tag, that `viewer` may see
+    pub fn get_books_by_tag(
+        &self,
+        account_id: AccountId,
+        tag: String,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+        viewer: Option<AccountId>,
+    ) -> Page<BookEntry> {
+        let audience = self.audience(&account_id, viewer.as_ref());
+        let tag = tag.trim().to_lowercase();
+        match self.tag_index.get(&account_id).and_then(|tags| tags.get(&tag)) {
+            Some(isbns) if self.library_privacy(&account_id).allows(audience) => {
+                self.books_page(&account_id, audience, isbns, from_index, limit)
+            }
+            _ => paginate(std::iter::empty(), 0, from_index, limit),
+        }
+    }
+
+    /// Every tag on books in an account's library that `viewer` may see, with the number of those books
+    pub fn get_tags(&self, account_id: AccountId, viewer: Option<AccountId>) -> Vec<TagCount> {
+        let audience = self.audience(&account_id, viewer.as_ref());
+        let privacy = self.library_privacy(&account_id);
+        let tags = match self.tag_index.get(&account_id) {
+            Some(tags) if privacy.allows(audience) => tags,
+            _ => return Vec::new(),
+        };
+        tags.iter()
+            .map(|(tag, isbns)| TagCount { tag: tag.clone(), books: self.visible_count(&account_id, audience, isbns) })
+            .filter(|tag| tag.books > 0)
+            .collect()
+    }
+}
+
+impl Contract {
+    /// The stored ISBN of a book in the library, and whether events about it may be published
+    fn shelf_entry(&self, account_id: &AccountId, isbn: &str) -> Result<(String, bool), BookyError> {
+        let book = self.find_book(account_id, isbn).ok_or(BookyError::BookNotFound)?;
+        Ok((book.isbn.clone(), is_published(&book, self.library_privacy(account_id))))
+    }
+
+    fn shelf_mut(&mut self, account_id: &AccountId, shelf_id: u32) -> Result<&mut Shelf, BookyError> {
+        self.shelves
+            .get_mut(account_id)
+            .and_then(|account_shelves| account_shelves.shelves.get_mut(&shelf_id))
+            .ok_or(BookyError::ShelfNotFound { shelf_id })
+    }
+
+    /// Move a book's tags in the account's tag index from `previous` to `tags`
+    pub(crate) fn index_tags(&mut self, account_id: &AccountId, isbn: &str, previous: &[String], tags: &[String]) {
+        if previous == tags {
+            return;
+        }
+        let index = self.tag_index.entry(account_id.clone()).or_insert_with(|| {
+            IterableMap::new(StorageKey::Tags {
+                account_hash: env::sha256_array(account_id.as_bytes()),
+            })
+        });
+        for tag in previous.iter().filter(|tag| !tags.contains(tag)) {
+            if let Some(isbns) = index.get_mut(tag) {
+                isbns.retain(|tagged| tagged != isbn);
+                if isbns.is_empty() {
+                    index.remove(tag);
+                }
+            }
+        }
+        for tag in tags.iter().filter(|tag| !previous.contains(tag)) {
+            index.entry(tag.clone()).or_default().push(isbn.to_string());
+        }
+        if index.is_empty() {
+            self.tag_index.remove(account_id);
+        }
+    }
+
+    /// Drop a book from the account's shelves, or move it to a new ISBN
+    pub(crate) fn reshelve(&mut self, account_id: &AccountId, isbn: &str, new_isbn: Option<&str>) {
+        let account_shelves = match self.shelves.get_mut(account_id) {
+            Some(account_shelves) => account_shelves,
+            None => return,
+        };
+        for (_, shelf) in account_shelves.shelves.iter_mut() {
+            match (shelf.books.iter().position(|shelved| shelved == isbn), new_isbn) {
+                (Some(index), Some(new_isbn)) => shelf.books[index] = new_isbn.to_string(),
+                (Some(index), None) => {
+                    shelf.books.remove(index);
+                }
+                (None, _) => {}
+            }
+        }
+    }
+
+    /// One page of the listed books, in order, that `audience` may see
+    fn books_page(
+        &self,
+        account_id: &AccountId,
+        audience: Audience,
+        isbns: &[String],
+        from_index: Option<u32>,
+        limit: Option<u32>,
+    ) -> Page<BookEntry> {
+        let privacy = self.library_privacy(account_id);
+        let visible = || {
+            isbns
+                .iter()
+                .filter_map(|isbn| self.find_book(account_id, isbn))
+                .filter_map(move |book| privacy.view(book, audience))
+                .map(|book| book.into_owned())
+        };
+        let total = match audience {
+            Audience::Owner => isbns.len() as u32,
+            _ => self.visible_count(account_id, audience, isbns),
+        };
+        paginate(visible(), total, from_index, limit)
+    }
+
+    fn visible_count(&self, account_id: &AccountId, audience: Audience, isbns: &[String]) -> u32 {
+        let privacy = self.library_privacy(account_id);
+        isbns
+            .iter()
+            .filter_map(|isbn| self.find_book(account_id, isbn))
+            .filter(|book| privacy.restrict(book.privacy).allows(audience))
+            .count() as u32
+    }
+}
@@ -0,0 +1,324 @@
+// Follow requests for accounts that approve their followers, blocking and muting
+use crate::query::{paginate, MAX_PAGE_LIMIT};
+use crate::{BookyError, BookyEvent, Contract, ContractExt, Page, StorageKey};
+use near_sdk::store::{IterableSet, LookupMap};
+use near_sdk::{env, log, near, AccountId};
+
+#[near]
+impl Contract {
+    /// Require (or stop requiring) approval of new followers. Existing followers stay.
+    /// Turning approval off approves up to `MAX_PAGE_LIMIT` pending requests; call it
+    /// again to approve the rest.
+    #[handle_result]
+    pub fn set_follow_approval(&mut self, required: bool) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+
+        if required {
+            self.follow_approval.insert(account_id.clone());
+        } else {
+            self.follow_approval.remove(&account_id);
+        }
+        self.charge_storage(&account_id, initial_storage)?;
+
+        if !required {
+            self.approve_pending_follows(&account_id);
+        }
+        Ok(())
+    }
+
+    /// Whether `follow_account` on this account creates a request instead of a follow
+    pub fn requires_follow_approval(&self, account_id: AccountId) -> bool {
+        self.follow_approval.contains(&account_id)
+    }
+
+    /// Get a page of accounts waiting for `account_id` to approve their follow
+    pub fn get_follow_requests(&self, account_id: AccountId, from_index: Option<u32>, limit: Option<u32>) -> Page<AccountId> {
+        match self.follow_requests.get(&account_id) {
+            Some(requests) => paginate(requests.iter().cloned(), requests.len(), from_index, limit),
+            None => paginate(std::iter::empty(), 0, from_index, limit),
+        }
+    }
+
+    /// Accept a pending follow request. The requester pays for the follow, as if
+    /// they had followed directly.
+    #[handle_result]
+    pub fn approve_follow(&mut self, account_id: AccountId) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let target = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+
+        if !self.remove_follow_request(&target, &account_id) {
+            return Err(BookyError::FollowRequestNotFound(account_id));
+        }
+        self.insert_follow(account_id.clone(), target.clone());
+        BookyEvent::FollowApproved { account_id: &target, follower_id: &account_id }.emit();
+
+        self.charge_storage(&account_id, initial_storage)
+    }
+
+    /// Decline a pending follow request
+    #[handle_result]
+    pub fn reject_follow(&mut self, account_id: AccountId) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let target = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+
+        if !self.remove_follow_request(&target, &account_id) {
+            return Err(BookyError::FollowRequestNotFound(account_id));
+        }
+        BookyEvent::FollowRejected { account_id: &target, requester_id: &account_id }.emit();
+
+        // The requester paid for the request, so the refund is theirs
+        self.charge_storage(&account_id, initial_storage)
+    }
+
+    /// Stop `account_id` from following the caller
+    #[handle_result]
+    pub fn remove_follower(&mut self, account_id: AccountId) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let target = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+
+        if !self.remove_follow(&account_id, &target) {
+            return Err(BookyError::NotAFollower(account_id));
+        }
+        BookyEvent::FollowerRemoved { account_id: &target, follower_id: &account_id }.emit();
+
+        self.charge_storage(&account_id, initial_storage)
+    }
+
+    /// Block an account: it stops following the caller, its pending request is
+    /// dropped, it can't follow or request to follow again, and views show it
+    /// none of the caller's data
+    #[handle_result]
+    pub fn block_account(&mut self, account_id: AccountId) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let caller = env::predecessor_account_id();
+        if account_id == caller {
+            return Err(BookyError::SelfBlock);
+        }
+
+        // The blocked account paid for its follow and request, so it gets the refund
+        let initial_storage = env::storage_usage();
+        if self.remove_follow(&account_id, &caller) {
+            BookyEvent::FollowerRemoved { account_id: &caller, follower_id: &account_id }.emit();
+        }
+        self.remove_follow_request(&caller, &account_id);
+        self.charge_storage(&account_id, initial_storage)?;
+
+        let initial_storage = env::storage_usage();
+        let inserted = self.blocked_accounts
+            .entry(caller.clone())
+            .or_insert_with(|| {
+                IterableSet::new(StorageKey::Blocked {
+                    account_hash: env::sha256_array(caller.as_bytes()),
+                })
+            })
+            .insert(account_id.clone());
+        if !inserted {
+            log!("Already blocking {}", account_id);
+            return Ok(());
+        }
+        BookyEvent::AccountBlocked { account_id: &caller, blocked_account_id: &account_id }.emit();
+
+        self.charge_storage(&caller, initial_storage)
+    }
+
+    /// Lift a block. The account can follow again, but isn't restored as a follower.
+    #[handle_result]
+    pub fn unblock_account(&mut self, account_id: AccountId) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let caller = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+
+        if !remove_from_set(&mut self.blocked_accounts, &caller, &account_id) {
+            return Err(BookyError::NotBlocked(account_id));
+        }
+        BookyEvent::AccountUnblocked { account_id: &caller, unblocked_account_id: &account_id }.emit();
+
+        self.charge_storage(&caller, initial_storage)
+    }
+
+    /// Get a page of accounts `account_id` has blocked
+    pub fn get_blocked_accounts(&self, account_id: AccountId, from_index: Option<u32>, limit: Option<u32>) -> Page<AccountId> {
+        match self.blocked_accounts.get(&account_id) {
+            Some(blocked) => paginate(blocked.iter().cloned(), blocked.len(), from_index, limit),
+            None => paginate(std::iter::empty(), 0, from_index, limit),
+        }
+    }
+
+    /// Whether `account_id` has blocked `blocked_account_id`
+    pub fn is_blocked(&self, account_id: AccountId, blocked_account_id: AccountId) -> bool {
+        self.has_blocked(&account_id, &blocked_account_id)
+    }
+
+    /// Hide a followed account from the caller's feed views without unfollowing it
+    #[handle_result]
+    pub fn mute_account(&mut self, account_id: AccountId) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let caller = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        if account_id == caller {
+            return Err(BookyError::SelfMute);
+        }
+
+        let inserted = self.muted_accounts
+            .entry(caller.clone())
+            .or_insert_with(|| {
+                IterableSet::new(StorageKey::Muted {
+                    account_hash: env::sha256_array(caller.as_bytes()),
+                })
+            })
+            .insert(account_id.clone());
+        if !inserted {
+            log!("Already muting {}", account_id);
+            return Ok(());
+        }
+        BookyEvent::AccountMuted { account_id: &caller, muted_account_id: &account_id }.emit();
+
+        self.charge_storage(&caller, initial_storage)
+    }
+
+    #[handle_result]
+    pub fn unmute_account(&mut self, account_id: AccountId) -> Result<(), BookyError> {
+        self.access.require_not_paused()?;
+        let caller = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+
+        if !remove_from_set(&mut self.muted_accounts, &caller, &account_id) {
+            return Err(BookyError::NotMuted(account_id));
+        }
+        BookyEvent::AccountUnmuted { account_id: &caller, unmuted_account_id: &account_id }.emit();
+
+        self.charge_storage(&caller, initial_storage)
+    }
+
+    /// Get a page of accounts `account_id` has muted
+    pub fn get_muted_accounts(&self, account_id: AccountId, from_index: Option<u32>, limit: Option<u32>) -> Page<AccountId> {
+        match self.muted_accounts.get(&account_id) {
+            Some(muted) => paginate(muted.iter().cloned(), muted.len(), from_index, limit),
+            None => paginate(std::iter::empty(), 0, from_index, limit),
+        }
+    }
+}
+
+impl Contract {
+    /// Get a page of the accounts `account_id` follows and hasn't muted
+    pub(crate) fn feed_accounts(&self, account_id: &AccountId, from_index: Option<u32>, limit: Option<u32>) -> Page<AccountId> {
+        let followed = match self.followed_accounts.get(account_id) {
+            Some(followed) => followed,
+            None => return paginate(std::iter::empty(), 0, from_index, limit),
+        };
+        if self.muted_accounts.get(account_id).is_none_or(|muted| muted.is_empty()) {
+            return paginate(followed.iter().cloned(), followed.len(), from_index, limit);
+        }
+
+        let total = self.unmuted_followed(account_id).count() as u32;
+        paginate(self.unmuted_followed(account_id).cloned(), total, from_index, limit)
+    }
+
+    /// Every account `account_id` follows and hasn't muted
+    pub(crate) fn unmuted_followed<'a>(&'a self, account_id: &AccountId) -> impl Iterator<Item = &'a AccountId> + 'a {
+        let muted = self.muted_accounts.get(account_id);
+        self.followed_accounts
+            .get(account_id)
+            .into_iter()
+            .flat_map(|followed| followed.iter())
+            .filter(move |followed_id| !muted.is_some_and(|muted| muted.contains(*followed_id)))
+    }
+
+    pub(crate) fn has_blocked(&self, account_id: &AccountId, blocked_account_id: &AccountId) -> bool {
+        self.blocked_accounts
+            .get(account_id)
+            .is_some_and(|blocked| blocked.contains(blocked_account_id))
+    }
+
+    /// Record a request from `account_id` to follow `target`. The requester pays
+    /// for the entry. Returns false if one is already pending.
+    pub(crate) fn insert_follow_request(&mut self, account_id: AccountId, target: &AccountId) -> bool {
+        let requests = self.follow_requests.entry(target.clone()).or_insert_with(|| {
+            IterableSet::new(StorageKey::FollowRequest {
+                account_hash: env::sha256_array(target.as_bytes()),
+            })
+        });
+        let inserted = requests.insert(account_id.clone());
+        // Write it now so the storage delta charged to the requester includes it
+        requests.flush();
+        if inserted {
+            let sent = self.sent_follow_requests.entry(account_id.clone()).or_insert_with(|| {
+                IterableSet::new(StorageKey::SentFollowRequest {
+                    account_hash: env::sha256_array(account_id.as_bytes()),
+                })
+            });
+            sent.insert(target.clone());
+            sent.flush();
+        }
+        inserted
+    }
+
+    /// Approve a batch of the requests pending for `target`. A requester whose balance
+    /// can't cover the follow has its request dropped and refunded instead.
+    fn approve_pending_follows(&mut self, target: &AccountId) {
+        let requesters: Vec<AccountId> = match self.follow_requests.get(target) {
+            Some(requests) => requests.iter().take(MAX_PAGE_LIMIT as usize).cloned().collect(),
+            None => return,
+        };
+
+        for requester in requesters {
+            let initial_storage = env::storage_usage();
+            self.remove_follow_request(target, &requester);
+            self.insert_follow(requester.clone(), target.clone());
+            if self.charge_storage(&requester, initial_storage).is_ok() {
+                BookyEvent::FollowApproved { account_id: target, follower_id: &requester }.emit();
+                continue;
+            }
+
+            // The failed charge already counted the follow, so releasing it settles the refund
+            let followed_storage = env::storage_usage();
+            self.remove_follow(&requester, target);
+            let _ = self.charge_storage(&requester, followed_storage);
+            BookyEvent::FollowRejected { account_id: target, requester_id: &requester }.emit();
+        }
+
+        if let Some(requests) = self.follow_requests.get(target) {
+            log!("{} follow requests still pending; call set_follow_approval(false) again", requests.len());
+        }
+    }
+
+    /// Drop a pending request, and the target's request set if it becomes empty.
+    /// Returns false if there was no such request.
+    pub(crate) fn remove_follow_request(&mut self, target: &AccountId, account_id: &AccountId) -> bool {
+        remove_from_set(&mut self.sent_follow_requests, account_id, target);
+        let requests = match self.follow_requests.get_mut(target) {
+            Some(requests) => requests,
+            None => return false,
+        };
+        if !requests.remove(account_id) {
+            return false;
+        }
+        if requests.is_empty() {
+            self.follow_requests.remove(target);
+        } else {
+            requests.flush();
+        }
+        true
+    }
+}
+
+/// Remove `account_id` from `owner`'s set in `sets`, dropping the set once empty.
+/// Returns false if it wasn't there.
+fn remove_from_set(sets: &mut LookupMap<AccountId, IterableSet<AccountId>>, owner: &AccountId, account_id: &AccountId) -> bool {
+    let set = match sets.get_mut(owner) {
+        Some(set) => set,
+        None => return false,
+    };
+    if !set.remove(account_id) {
+        return false;
+    }
+    if set.is_empty() {
+        sets.remove(owner);
+    }
+    true
+}
@@ -0,0 +1,326 @@
+// NEP-145 Storage Management - callers prepay for the bytes their data occupies
+use crate::{BookyError, BookyEvent, Contract, ContractExt};
+use near_sdk::json_types::U128;
+use near_sdk::{env, log, near, AccountId, NearToken, Promise};
+
+/// Bytes reserved at registration to cover the account's own storage record
+pub const MIN_STORAGE_BYTES: u64 = 250;
+
+/// Per-account storage accounting kept by the contract
+#[near(serializers = [borsh])]
+pub struct AccountStorage {
+    /// Total yoctoNEAR deposited by the account
+    pub deposit: NearToken,
+    /// Bytes of contract storage currently attributed to the account
+    pub used_bytes: u64,
+}
+
+/// NEP-145 storage balance of an account
+#[near(serializers = [json])]
+#[derive(Debug, PartialEq)]
+pub struct StorageBalance {
+    pub total: U128,
+    pub available: U128,
+}
+
+/// NEP-145 minimum and maximum storage deposit
+#[near(serializers = [json])]
+pub struct StorageBalanceBounds {
+    pub min: U128,
+    pub max: Option<U128>,
+}
+
+impl AccountStorage {
+    fn locked(&self) -> NearToken {
+        env::storage_byte_cost().saturating_mul((MIN_STORAGE_BYTES + self.used_bytes) as u128)
+    }
+
+    fn available(&self) -> NearToken {
+        self.deposit.saturating_sub(self.locked())
+    }
+
+    fn to_balance(&self) -> StorageBalance {
+        StorageBalance {
+            total: U128(self.deposit.as_yoctonear()),
+            available: U128(self.available().as_yoctonear()),
+        }
+    }
+}
+
+fn min_storage_deposit() -> NearToken {
+    env::storage_byte_cost().saturating_mul(MIN_STORAGE_BYTES as u128)
+}
+
+/// Require exactly one yoctoNEAR attached, so full-access keys must confirm the call
+fn require_one_yocto() -> Result<(), BookyError> {
+    if env::attached_deposit() != NearToken::from_yoctonear(1) {
+        return Err(BookyError::OneYoctoRequired);
+    }
+    Ok(())
+}
+
+#[near]
+impl Contract {
+    /// Deposit NEAR to cover storage for `account_id` (defaults to the caller).
+    /// With `registration_only`, only the minimum deposit is kept and the rest refunded.
+    #[payable]
+    #[handle_result]
+    pub fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> Result<StorageBalance, BookyError> {
+        self.access.require_not_paused()?;
+        let amount = env::attached_deposit();
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let registration_only = registration_only.unwrap_or(false);
+
+        if let Some(storage) = self.storage_accounts.get_mut(&account_id) {
+            if registration_only {
+                log!("Account {} is already registered", account_id);
+                if !amount.is_zero() {
+                    let _ = Promise::new(env::predecessor_account_id()).transfer(amount);
+                }
+            } else {
+                storage.deposit = storage.deposit.saturating_add(amount);
+            }
+            return Ok(storage.to_balance());
+        }
+
+        let min = min_storage_deposit();
+        if amount < min {
+            return Err(BookyError::InsufficientDeposit { minimum: U128(min.as_yoctonear()) });
+        }
+
+        let deposit = if registration_only {
+            let refund = amount.saturating_sub(min);
+            if !refund.is_zero() {
+                let _ = Promise::new(env::predecessor_account_id()).transfer(refund);
+            }
+            min
+        } else {
+            amount
+        };
+
+        log!("Registered {} for storage", account_id);
+        let storage = AccountStorage { deposit, used_bytes: 0 };
+        let balance = storage.to_balance();
+        self.storage_accounts.insert(account_id, storage);
+        Ok(balance)
+    }
+
+    /// Withdraw unused storage deposit. Withdraws everything available if `amount` is omitted.
+    #[payable]
+    #[handle_result]
+    pub fn storage_withdraw(&mut self, amount: Option<U128>) -> Result<StorageBalance, BookyError> {
+        self.access.require_not_paused()?;
+        require_one_yocto()?;
+        let account_id = env::predecessor_account_id();
+
+        let storage = match self.storage_accounts.get_mut(&account_id) {
+            Some(storage) => storage,
+            None => return Err(BookyError::NotRegistered(account_id)),
+        };
+
+        let available = storage.available();
+        let amount = amount.map(|a| NearToken::from_yoctonear(a.0)).unwrap_or(available);
+        if amount > available {
+            return Err(BookyError::WithdrawExceedsAvailable { available: U128(available.as_yoctonear()) });
+        }
+
+        storage.deposit = storage.deposit.saturating_sub(amount);
+        if !amount.is_zero() {
+            let _ = Promise::new(account_id).transfer(amount);
+        }
+        Ok(storage.to_balance())
+    }
+
+    /// Close the caller's storage account and refund the deposit, less what pays for
+    /// the catalog entries it added, which stay.
+    /// Fails if the account still has stored data, unless `force` is set, in which
+    /// case everything the caller stored is deleted first, including follow requests
+    /// to and from it.
+    #[payable]
+    #[handle_result]
+    pub fn storage_unregister(&mut self, force: Option<bool>) -> Result<bool, BookyError> {
+        self.access.require_not_paused()?;
+        require_one_yocto()?;
+        let account_id = env::predecessor_account_id();
+
+        let storage = match self.storage_accounts.get(&account_id) {
+            Some(storage) => storage,
+            None => {
+                log!("Account {} is not registered", account_id);
+                return Ok(false);
+            }
+        };
+
+        if storage.used_bytes > 0 {
+            if !force.unwrap_or(false) {
+                return Err(BookyError::StorageInUse);
+            }
+            self.remove_account_data(&account_id);
+            BookyEvent::AccountUnregistered { account_id: &account_id }.emit();
+        }
+
+        let storage = self.storage_accounts.remove(&account_id).unwrap();
+        // Catalog entries stay after the account that added them, so the deposit
+        // paying for them stays with the contract
+        let catalog_bytes = self.catalog_bytes.remove(&account_id).unwrap_or(0);
+        let refund = storage.deposit.saturating_sub(env::storage_byte_cost().saturating_mul(catalog_bytes as u128));
+        if !refund.is_zero() {
+            let _ = Promise::new(account_id.clone()).transfer(refund);
+        }
+        log!("Unregistered {}", account_id);
+        Ok(true)
+    }
+
+    /// Storage balance of an account, or null if it is not registered
+    pub fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.storage_accounts.get(&account_id).map(|s| s.to_balance())
+    }
+
+    /// Minimum deposit to register; there is no maximum since libraries can grow
+    pub fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: U128(min_storage_deposit().as_yoctonear()),
+            max: None,
+        }
+    }
+}
+
+impl Contract {
+    /// Charge (or refund) `account_id` for the storage bytes added (or freed)
+    /// since `initial_usage` was read at the start of the call
+    pub(crate) fn charge_storage(&mut self, account_id: &AccountId, initial_usage: u64) -> Result<(), BookyError> {
+        self.flush_account(account_id);
+        let current_usage = env::storage_usage();
+
+        let storage = match self.storage_accounts.get_mut(account_id) {
+            Some(storage) => storage,
+            // Data stored before storage staking was introduced may still be freed
+            None if current_usage <= initial_usage => return Ok(()),
+            None => return Err(BookyError::NotRegistered(account_id.clone())),
+        };
+
+        if current_usage >= initial_usage {
+            storage.used_bytes += current_usage - initial_usage;
+            let locked = storage.locked();
+            if locked > storage.deposit {
+                let required = locked.saturating_sub(storage.deposit).as_yoctonear();
+                return Err(BookyError::InsufficientStorageBalance { required: U128(required) });
+            }
+        } else {
+            storage.used_bytes = storage.used_bytes.saturating_sub(initial_usage - current_usage);
+        }
+        Ok(())
+    }
+
+    /// Write pending collection changes for an account so `env::storage_usage` reflects them
+    fn flush_account(&mut self, account_id: &AccountId) {
+        if let Some(library) = self.libraries.get_mut(account_id) {
+            library.flush();
+        }
+        if let Some(followed) = self.followed_accounts.get_mut(account_id) {
+            followed.flush();
+        }
+        if let Some(sent) = self.sent_follow_requests.get_mut(account_id) {
+            sent.flush();
+        }
+        if let Some(blocked) = self.blocked_accounts.get_mut(account_id) {
+            blocked.flush();
+        }
+        if let Some(muted) = self.muted_accounts.get_mut(account_id) {
+            muted.flush();
+        }
+        if let Some(activity) = self.activity.get_mut(account_id) {
+            activity.flush();
+        }
+        if let Some(sessions) = self.reading_sessions.get_mut(account_id) {
+            sessions.flush();
+        }
+        if let Some(shelves) = self.shelves.get_mut(account_id) {
+            shelves.flush();
+        }
+        if let Some(tags) = self.tag_index.get_mut(account_id) {
+            tags.flush();
+        }
+        self.libraries.flush();
+        self.followed_accounts.flush();
+        self.followers.flush();
+        self.follow_requests.flush();
+        self.sent_follow_requests.flush();
+        self.blocked_accounts.flush();
+        self.muted_accounts.flush();
+        self.activity.flush();
+        self.reading_sessions.flush();
+        self.rating_tallies.flush();
+        self.shelves.flush();
+        self.tag_index.flush();
+        self.catalog.flush();
+        self.privacy_settings.flush();
+        self.note_public_keys.flush();
+    }
+
+    /// Delete everything an account has stored in the contract
+    fn remove_account_data(&mut self, account_id: &AccountId) {
+        // Requests to the account were paid for by their requesters, so they get the
+        // refund. This runs first so nothing else freed is counted in their refunds.
+        let requesters: Vec<AccountId> = match self.follow_requests.get(account_id) {
+            Some(requests) => requests.iter().cloned().collect(),
+            None => Vec::new(),
+        };
+        for requester in requesters {
+            let initial_storage = env::storage_usage();
+            self.remove_follow_request(account_id, &requester);
+            let _ = self.charge_storage(&requester, initial_storage);
+        }
+        let targets: Vec<AccountId> = match self.sent_follow_requests.get(account_id) {
+            Some(sent) => sent.iter().cloned().collect(),
+            None => Vec::new(),
+        };
+        for target in targets {
+            self.remove_follow_request(&target, account_id);
+        }
+
+        if let Some(mut library) = self.libraries.remove(account_id) {
+            self.total_books -= library.len() as u64;
+            let ratings: Vec<(String, u8)> = library
+                .iter()
+                .filter_map(|(isbn, entry)| Some((isbn.clone(), entry.current().rating?)))
+                .collect();
+            for (isbn, rating) in ratings {
+                self.tally_rating(&isbn, Some(rating), None);
+            }
+            library.clear();
+        }
+        self.privacy_settings.remove(account_id);
+        self.note_public_keys.remove(account_id);
+        self.follow_approval.remove(account_id);
+        if let Some(mut blocked) = self.blocked_accounts.remove(account_id) {
+            blocked.clear();
+        }
+        if let Some(mut muted) = self.muted_accounts.remove(account_id) {
+            muted.clear();
+        }
+        if let Some(mut activity) = self.activity.remove(account_id) {
+            activity.clear();
+        }
+        if let Some(mut sessions) = self.reading_sessions.remove(account_id) {
+            sessions.clear();
+        }
+        if let Some(mut shelves) = self.shelves.remove(account_id) {
+            shelves.clear();
+        }
+        if let Some(mut tags) = self.tag_index.remove(account_id) {
+            tags.clear();
+        }
+        let followed: Vec<AccountId> = match self.followed_accounts.get(account_id) {
+            Some(followed) => followed.iter().cloned().collect(),
+            None => Vec::new(),
+        };
+        for followed_account_id in followed {
+            self.remove_follow(account_id, &followed_account_id);
+        }
+    }
+}
@@ -0,0 +1,481 @@
+// Field size limits and format checks applied before anything is written
+use crate::contents::Contents;
+use crate::notes::{MAX_ALGORITHM_LEN, MAX_NONCE_LEN, MAX_WRAPPED_KEY_LEN};
+use crate::{
+    BookEntry, BookyError, CatalogEntry, Contract, ContractExt, EncryptedEnvelope, NoteContent, OwnedCopy,
+    ProgressUpdate, ReadingPosition, Section,
+};
+use std::collections::HashSet;
+use near_sdk::near;
+
+/// Longest language tag in the catalog
+const MAX_LANGUAGE_LEN: u32 = 35;
+
+/// Maximum sizes for user-supplied fields. String limits are in bytes.
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldLimits {
+    pub max_title_len: u32,
+    pub max_author_len: u32,
+    pub max_condition_len: u32,
+    pub max_comments_len: u32,
+    pub max_media_hash_len: u32,
+    pub max_position_len: u32,
+    pub max_note_len: u32,
+    /// Largest `total_chapters` and most sections in a table of contents, and the
+    /// highest chapter number on books without either
+    pub max_total_chapters: u32,
+    /// Most accounts an encrypted note or comment can be shared with
+    pub max_note_recipients: u32,
+    /// Most minutes one progress update can report
+    pub max_session_minutes: u32,
+    pub max_review_len: u32,
+    pub max_shelf_name_len: u32,
+    pub max_shelf_description_len: u32,
+    pub max_tag_len: u32,
+    pub max_tags_per_book: u32,
+    pub max_copies_per_book: u32,
+    pub max_location_len: u32,
+}
+
+impl Default for FieldLimits {
+    fn default() -> Self {
+        Self {
+            max_title_len: 256,
+            max_author_len: 256,
+            max_condition_len: 64,
+            max_comments_len: 2048,
+            max_media_hash_len: 128,
+            max_position_len: 256,
+            max_note_len: 4096,
+            max_total_chapters: 1000,
+            max_note_recipients: 16,
+            max_session_minutes: 24 * 60,
+            max_review_len: 8192,
+            max_shelf_name_len: 64,
+            max_shelf_description_len: 512,
+            max_tag_len: 32,
+            max_tags_per_book: 20,
+            max_copies_per_book: 20,
+            max_location_len: 128,
+        }
+    }
+}
+
+impl FieldLimits {
+    /// Check every user-supplied field of a book, with the catalog's metadata filled in
+    pub fn check_book(&self, book: &BookEntry) -> Result<(), BookyError> {
+        check_len("title", &book.title, self.max_title_len)?;
+        check_len("author", &book.author, self.max_author_len)?;
+        check_len("condition", &book.condition, self.max_condition_len)?;
+        self.check_content("personal_comments", &book.personal_comments, self.max_comments_len)?;
+        if let Some(media_hash) = &book.media_hash {
+            check_len("media_hash", media_hash, self.max_media_hash_len)?;
+        }
+        check_len("last_read_position", &book.last_read_position, self.max_position_len)?;
+
+        check_date("acquisition_date", &book.acquisition_date)?;
+        self.check_copies(&book.copies)?;
+        if let Some(date) = &book.last_read_date {
+            check_date("last_read_date", date)?;
+        }
+
+        if let Some(total) = book.total_chapters {
+            if total > self.max_total_chapters {
+                return Err(BookyError::TooManyChapters { total_chapters: total, max: self.max_total_chapters });
+            }
+        }
+        self.check_sections(&book.sections)?;
+        let contents = Contents::of(book);
+        if book.current_chapter != 0 {
+            self.check_chapter(book.current_chapter, &contents)?;
+        }
+        for chapter in &book.chapters_read {
+            self.check_chapter(*chapter, &contents)?;
+        }
+        if let Some(position) = &book.position {
+            self.check_position(position, &contents)?;
+        }
+        for (chapter, note) in &book.chapter_notes {
+            self.check_chapter(*chapter, &contents)?;
+            self.check_content("chapter_notes", note, self.max_note_len)?;
+        }
+        Ok(())
+    }
+
+    /// Check the fields of a progress update against the book it applies to
+    pub(crate) fn check_progress(&self, progress: &ProgressUpdate, contents: &Contents) -> Result<(), BookyError> {
+        if let Some(chapter) = progress.current_chapter.filter(|c| *c != 0) {
+            self.check_chapter(chapter, contents)?;
+        }
+        for chapter in &progress.chapters_completed {
+            self.check_chapter(*chapter, contents)?;
+        }
+        if let Some(position) = &progress.last_read_position {
+            check_len("last_read_position", position, self.max_position_len)?;
+        }
+        if let Some(position) = &progress.position {
+            self.check_position(position, contents)?;
+        }
+        if let Some(date) = &progress.last_read_date {
+            check_date("last_read_date", date)?;
+        }
+        if let Some(minutes) = progress.minutes_read.filter(|m| *m > self.max_session_minutes) {
+            return Err(BookyError::SessionTooLong { minutes, max: self.max_session_minutes });
+        }
+        Ok(())
+    }
+
+    pub fn check_copies(&self, copies: &[OwnedCopy]) -> Result<(), BookyError> {
+        if copies.len() > self.max_copies_per_book as usize {
+            return Err(BookyError::TooManyCopies { max: self.max_copies_per_book });
+        }
+        for copy in copies {
+            check_len("condition", &copy.condition, self.max_condition_len)?;
+            check_date("acquisition_date", &copy.acquisition_date)?;
+            if let Some(location) = &copy.location {
+                check_len("location", location, self.max_location_len)?;
+            }
+            if let Some(price) = &copy.price_paid {
+                if price.currency.len() != 3 || !price.currency.bytes().all(|b| b.is_ascii_uppercase()) {
+                    return Err(BookyError::InvalidCurrency(price.currency.clone()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn check_review(&self, text: &str) -> Result<(), BookyError> {
+        check_len("review", text, self.max_review_len)
+    }
+
+    pub fn check_shelf(&self, name: &str, description: &str) -> Result<(), BookyError> {
+        if name.trim().is_empty() {
+            return Err(BookyError::EmptyField { field: "shelf name" });
+        }
+        check_len("shelf name", name, self.max_shelf_name_len)?;
+        check_len("shelf description", description, self.max_shelf_description_len)
+    }
+
+    /// Check tags that have already been normalized
+    pub fn check_tags(&self, tags: &[String]) -> Result<(), BookyError> {
+        if tags.len() > self.max_tags_per_book as usize {
+            return Err(BookyError::TooManyTags { max: self.max_tags_per_book });
+        }
+        for tag in tags {
+            if tag.is_empty() {
+                return Err(BookyError::EmptyField { field: "tag" });
+            }
+            check_len("tag", tag, self.max_tag_len)?;
+        }
+        Ok(())
+    }
+
+    /// Check catalog metadata. Titles and authors share the book limits, so entries
+    /// that fall back to the catalog still fit them.
+    pub fn check_catalog_entry(&self, entry: &CatalogEntry) -> Result<(), BookyError> {
+        if entry.title.trim().is_empty() {
+            return Err(BookyError::EmptyField { field: "title" });
+        }
+        check_len("title", &entry.title, self.max_title_len)?;
+        if let Some(subtitle) = &entry.subtitle {
+            check_len("subtitle", subtitle, self.max_title_len)?;
+        }
+        check_len("authors", &entry.author(), self.max_author_len)?;
+        if let Some(publisher) = &entry.publisher {
+            check_len("publisher", publisher, self.max_author_len)?;
+        }
+        if let Some(language) = &entry.language {
+            check_len("language", language, MAX_LANGUAGE_LEN)?;
+        }
+        self.check_sections(&entry.chapters)
+    }
+
+    /// Section ids must be unique and above 0, which stands for not started
+    pub fn check_sections(&self, sections: &[Section]) -> Result<(), BookyError> {
+        let total = sections.len() as u32;
+        if total > self.max_total_chapters {
+            return Err(BookyError::TooManyChapters { total_chapters: total, max: self.max_total_chapters });
+        }
+        let mut seen = HashSet::new();
+        for section in sections {
+            let invalid = |reason| Err(BookyError::InvalidSection { section: section.id, reason });
+            if section.id == 0 {
+                return invalid("section ids start at 1");
+            }
+            if !seen.insert(section.id) {
+                return invalid("section listed more than once");
+            }
+            if let (Some(start), Some(end)) = (section.start_page, section.end_page) {
+                if end < start {
+                    return invalid("section ends before it starts");
+                }
+            }
+            check_len("section label", &section.label, self.max_title_len)?;
+        }
+        Ok(())
+    }
+
+    pub fn check_note(&self, note: &NoteContent) -> Result<(), BookyError> {
+        self.check_content("note", note, self.max_note_len)
+    }
+
+    /// Plain text is limited to `max_len` bytes, and so is the ciphertext of an envelope
+    fn check_content(&self, field: &'static str, content: &NoteContent, max_len: u32) -> Result<(), BookyError> {
+        match content {
+            NoteContent::Plain(text) => check_len(field, text, max_len),
+            NoteContent::Encrypted(envelope) => self.check_envelope(field, envelope, max_len),
+        }
+    }
+
+    /// Check the shape of an encrypted envelope. The contract can't check that it decrypts.
+    pub fn check_envelope(&self, field: &'static str, envelope: &EncryptedEnvelope, max_len: u32) -> Result<(), BookyError> {
+        let invalid = |reason| Err(BookyError::InvalidEnvelope { field, reason });
+        if envelope.algorithm.is_empty() || envelope.algorithm.len() > MAX_ALGORITHM_LEN {
+            return invalid("algorithm must be between 1 and 64 bytes");
+        }
+        if envelope.nonce.0.is_empty() || envelope.nonce.0.len() > MAX_NONCE_LEN {
+            return invalid("nonce must be between 1 and 64 bytes");
+        }
+        if envelope.ciphertext.0.is_empty() {
+            return invalid("ciphertext is empty");
+        }
+        if envelope.ciphertext.0.len() > max_len as usize {
+            return Err(BookyError::FieldTooLong { field, max_length: max_len });
+        }
+
+        if envelope.recipients.len() > self.max_note_recipients as usize {
+            return Err(BookyError::TooManyRecipients { max: self.max_note_recipients });
+        }
+        let mut seen = HashSet::new();
+        for recipient in &envelope.recipients {
+            if recipient.wrapped_key.0.is_empty() || recipient.wrapped_key.0.len() > MAX_WRAPPED_KEY_LEN {
+                return invalid("wrapped keys must be between 1 and 512 bytes");
+            }
+            if !seen.insert(&recipient.account_id) {
+                return invalid("recipient listed more than once");
+            }
+        }
+        Ok(())
+    }
+
+    /// A position must fall within the book, where the book's size is known
+    pub(crate) fn check_position(&self, position: &ReadingPosition, contents: &Contents) -> Result<(), BookyError> {
+        let invalid = |reason| Err(BookyError::InvalidPosition { reason });
+        match *position {
+            ReadingPosition::Page { page: 0 } => invalid("pages start at 1"),
+            ReadingPosition::Page { page } if contents.total_pages().is_some_and(|total| page > total) => {
+                invalid("page is past the book's total_pages")
+            }
+            ReadingPosition::Percent { percent } if percent > 100 => invalid("percent is above 100"),
+            ReadingPosition::Location { location, total_locations: Some(total) } if location > total => {
+                invalid("location is past total_locations")
+            }
+            ReadingPosition::Timestamp { seconds, total_seconds: Some(total) } if seconds > total => {
+                invalid("timestamp is past total_seconds")
+            }
+            ReadingPosition::ChapterParagraph { paragraph: 0, .. } => invalid("paragraphs start at 1"),
+            ReadingPosition::ChapterParagraph { chapter, .. } => self.check_chapter(chapter, contents),
+            _ => Ok(()),
+        }
+    }
+
+    /// Chapter numbers are section ids on books with a table of contents. Otherwise
+    /// chapters are numbered from 1 up to the book's total, or up to `max_total_chapters`
+    /// when the book has no total.
+    pub(crate) fn check_chapter(&self, chapter: u32, contents: &Contents) -> Result<(), BookyError> {
+        if contents.has_sections() {
+            if !contents.contains(chapter) {
+                return Err(BookyError::SectionNotFound { section: chapter });
+            }
+            return Ok(());
+        }
+        let total_chapters = contents.total_chapters();
+        let max = total_chapters.unwrap_or(self.max_total_chapters);
+        if chapter == 0 || chapter > max {
+            return Err(BookyError::ChapterOutOfRange { chapter, total_chapters });
+        }
+        Ok(())
+    }
+}
+
+fn check_len(field: &'static str, value: &str, max: u32) -> Result<(), BookyError> {
+    if value.len() > max as usize {
+        return Err(BookyError::FieldTooLong { field, max_length: max });
+    }
+    Ok(())
+}
+
+fn check_date(field: &'static str, value: &str) -> Result<(), BookyError> {
+    if !is_iso_date(value) {
+        return Err(BookyError::InvalidDate { field, value: value.to_string() });
+    }
+    Ok(())
+}
+
+/// Whether `value` is an ISO-8601 calendar date (YYYY-MM-DD), optionally
+/// followed by a time of day (THH:MM[:SS[.fff]]) and a Z or ±HH:MM offset
+pub fn is_iso_date(value: &str) -> bool {
+    let (date, time) = match value.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (value, None),
+    };
+
+    let b = date.as_bytes();
+    if b.len() != 10 || b[4] != b'-' || b[7] != b'-' {
+        return false;
+    }
+    let (year, month, day) = match (number(&date[..4]), number(&date[5..7]), number(&date[8..])) {
+        (Some(y), Some(m), Some(d)) => (y, m, d),
+        _ => return false,
+    };
+    if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+        return false;
+    }
+
+    time.is_none_or(is_iso_time)
+}
+
+fn is_iso_time(time: &str) -> bool {
+    let (clock, offset) = if let Some(clock) = time.strip_suffix('Z') {
+        (clock, None)
+    } else if let Some(i) = time.rfind(['+', '-']) {
+        (&time[..i], Some(&time[i + 1..]))
+    } else {
+        (time, None)
+    };
+
+    let hms = |s: &str, max_hour: u32| -> bool {
+        let mut parts = s.split(':');
+        let hour = parts.next().filter(|p| p.len() == 2).and_then(number);
+        let minute = parts.next().filter(|p| p.len() == 2).and_then(number);
+        matches!((hour, minute), (Some(h), Some(m)) if h <= max_hour && m <= 59)
+            && parts.next().is_none_or(|seconds| {
+                let whole = seconds.split_once('.').map_or(seconds, |(whole, fraction)| {
+                    if fraction.is_empty() || number(fraction).is_none() { "" } else { whole }
+                });
+                whole.len() == 2 && number(whole).is_some_and(|s| s <= 60)
+            })
+            && parts.next().is_none()
+    };
+
+    hms(clock, 23) && offset.is_none_or(|offset| offset.len() == 5 && hms(offset, 14))
+}
+
+fn number(s: &str) -> Option<u32> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Format a block timestamp (nanoseconds since the Unix epoch) as YYYY-MM-DD
+pub fn iso_date_from_timestamp(timestamp_ns: u64) -> String {
+    // Civil-from-days conversion, see http://howardhinnant.github.io/date_algorithms.html
+    let days = (timestamp_ns / 1_000_000_000 / 86_400) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Midnight UTC, in nanoseconds since the Unix epoch, of the date an ISO-8601
+/// value falls on. None for invalid values, dates before 1970 and dates past
+/// what a u64 of nanoseconds can hold (2554-07-21).
+pub fn timestamp_from_iso_date(value: &str) -> Option<u64> {
+    if !is_iso_date(value) {
+        return None;
+    }
+    let (year, month, day) = (number(&value[..4])? as i64, number(&value[5..7])? as i64, number(&value[8..10])? as i64);
+
+    // Days-from-civil conversion, the inverse of `iso_date_from_timestamp`
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    u64::try_from(days).ok()?.checked_mul(86_400 * 1_000_000_000)
+}
+
+#[near]
+impl Contract {
+    /// Current field size limits
+    pub fn get_field_limits(&self) -> FieldLimits {
+        self.field_limits
+    }
+
+    /// Replace the field size limits. Existing entries are not re-checked.
+    #[handle_result]
+    pub fn set_field_limits(&mut self, limits: FieldLimits) -> Result<(), BookyError> {
+        self.access.require_owner()?;
+        self.field_limits = limits;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_iso_dates_and_datetimes() {
+        assert!(is_iso_date("2024-01-15"));
+        assert!(is_iso_date("2024-02-29"));
+        assert!(is_iso_date("2000-02-29"));
+        assert!(is_iso_date("2024-12-22T18:30"));
+        assert!(is_iso_date("2024-12-22T18:30:05Z"));
+        assert!(is_iso_date("2024-12-22T18:30:05.123+02:00"));
+        assert!(is_iso_date("2024-12-22T08:00:00-05:30"));
+    }
+
+    #[test]
+    fn rejects_malformed_dates() {
+        assert!(!is_iso_date(""));
+        assert!(!is_iso_date("2024-1-15"));
+        assert!(!is_iso_date("15/01/2024"));
+        assert!(!is_iso_date("2023-02-29"));
+        assert!(!is_iso_date("1900-02-29"));
+        assert!(!is_iso_date("2024-13-01"));
+        assert!(!is_iso_date("2024-04-31"));
+        assert!(!is_iso_date("2024-12-22T24:00"));
+        assert!(!is_iso_date("2024-12-22T18:30:05."));
+        assert!(!is_iso_date("2024-12-22T18:30+2"));
+        assert!(!is_iso_date("2024-12-22 garbage"));
+        assert!(!is_iso_date("+024-12-22"));
+    }
+
+    #[test]
+    fn formats_block_timestamps() {
+        assert_eq!(iso_date_from_timestamp(0), "1970-01-01");
+        // 2024-02-29T12:00:00Z
+        assert_eq!(iso_date_from_timestamp(1_709_208_000_000_000_000), "2024-02-29");
+        assert!(is_iso_date(&iso_date_from_timestamp(u64::MAX)));
+    }
+
+    #[test]
+    fn parses_dates_to_timestamps() {
+        assert_eq!(timestamp_from_iso_date("1970-01-01"), Some(0));
+        assert_eq!(timestamp_from_iso_date("2024-02-29T12:00:00Z"), Some(1_709_164_800_000_000_000));
+        assert_eq!(iso_date_from_timestamp(timestamp_from_iso_date("2023-12-31").unwrap()), "2023-12-31");
+        assert_eq!(timestamp_from_iso_date("1969-12-31"), None);
+        assert_eq!(timestamp_from_iso_date("2024-02-30"), None);
+        assert!(timestamp_from_iso_date("2554-07-21").is_some());
+        assert_eq!(timestamp_from_iso_date("2554-07-22"), None);
+        assert_eq!(timestamp_from_iso_date("9999-12-31"), None);
+    }
+}